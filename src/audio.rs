@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+
+use rodio::{Decoder, OutputStream, Source};
+
+/// Played by `tower_targeting` for every projectile launched
+pub const FIRE_SOUND: &str = "tower_fire";
+/// Played by `move_projectiles` for every enemy killed
+pub const EXPLOSION_SOUND: &str = "enemy_explosion";
+/// Sounds read from `assets/sounds/<name>.wav` at startup
+pub const SOUNDS: [&str; 2] = [FIRE_SOUND, EXPLOSION_SOUND];
+
+/// Plays preloaded sounds on a thread of its own, as the audio output stream can not be shared
+/// between the systems
+pub struct Audio {
+	sender: Mutex<Sender<(String, f32)>>,
+}
+
+impl Audio {
+	/// Reads the sounds and starts the playback thread, missing sounds and a missing output device
+	/// are reported once and keep the game silent
+	pub fn load(names: &[&str]) -> Self {
+		let mut sounds = HashMap::new();
+		for name in names {
+			let path = format!("assets/sounds/{}.wav", name);
+			match std::fs::read(&path) {
+				Ok(bytes) => {
+					sounds.insert(String::from(*name), bytes);
+				}
+				Err(err) => log::warn!("Unable to read sound {}: {}", path, err),
+			}
+		}
+
+		let (sender, receiver) = mpsc::channel::<(String, f32)>();
+		std::thread::spawn(move || {
+			let (_stream, handle) = match OutputStream::try_default() {
+				Ok(output) => output,
+				Err(err) => {
+					log::warn!("No audio output available: {}", err);
+					return;
+				}
+			};
+
+			for (name, volume) in receiver {
+				let bytes = match sounds.get(&name) {
+					Some(bytes) => bytes.clone(),
+					None => continue,
+				};
+				let played = Decoder::new(Cursor::new(bytes))
+					.map_err(|err| err.to_string())
+					.and_then(|source| {
+						handle
+							.play_raw(source.convert_samples().amplify(volume))
+							.map_err(|err| err.to_string())
+					});
+				if let Err(err) = played {
+					log::warn!("Unable to play sound {}: {}", name, err);
+				}
+			}
+		});
+
+		Self {
+			sender: Mutex::new(sender),
+		}
+	}
+
+	/// Plays the sound at `volume` between 0.0 and 1.0, nothing is played at 0.0
+	pub fn play_sound(&self, name: &str, volume: f32) {
+		if volume <= 0.0 {
+			return;
+		}
+
+		// Fails only if the playback thread has stopped, which is already reported
+		if let Ok(sender) = self.sender.lock() {
+			sender
+				.send((String::from(name), volume))
+				.ok();
+		}
+	}
+}
@@ -0,0 +1,29 @@
+pub const STARTING_LIVES: u32 = 20;
+
+/// Lives of the base enemies are walking to, one is lost for every enemy reaching the goal
+pub struct BaseHealth {
+	pub lives: u32,
+	/// No lives are lost while set, toggled by the `god` console command
+	pub invulnerable: bool,
+}
+
+impl BaseHealth {
+	/// Takes a life, returns `true` if it was the last one
+	pub fn lose(&mut self) -> bool {
+		if self.invulnerable {
+			return false;
+		}
+		let alive = self.lives > 0;
+		self.lives = self.lives.saturating_sub(1);
+		alive && self.lives == 0
+	}
+}
+
+impl Default for BaseHealth {
+	fn default() -> Self {
+		Self {
+			lives: STARTING_LIVES,
+			invulnerable: false,
+		}
+	}
+}
@@ -0,0 +1,187 @@
+use crate::towers::ProjectilePool;
+
+/// Enemies and projectiles kept alive by `--bench` unless a count is given after the flag
+pub const DEFAULT_COUNT: usize = 500;
+/// Frames run before the measuring starts, the spawning and the first GPU uploads are slower
+pub const WARMUP_FRAMES: usize = 60;
+/// Frames the average frame time is taken over
+pub const MEASURED_FRAMES: usize = 600;
+/// Seconds a benchmark projectile flies if it hits nothing
+pub const PROJECTILE_LIFETIME: f32 = 2.0;
+/// Height above the terrain base benchmark projectiles fly at, about the middle of the enemies
+pub const PROJECTILE_HEIGHT: f32 = 0.2;
+/// Cells along each side of the terrain `--bench-terrain` builds unless a size is given after the flag
+pub const DEFAULT_TERRAIN_SIZE: usize = 200;
+/// Height a terrain benchmark edit raises the vertices of a cell by, every frame edits the next cell
+pub const TERRAIN_EDIT_HEIGHT: f32 = 0.01;
+
+/// Load test started with `--bench [count]`, see `run_benchmark`. The enemy and projectile counts are
+/// topped up every frame, so the load stays the same while the towers kill enemies and projectiles expire.
+pub struct Benchmark {
+	pub enemies: usize,
+	pub projectiles: usize,
+	/// Towers are placed once, on the first frame
+	pub towers_placed: bool,
+	/// Frames since the start, the warmup included
+	pub frames: usize,
+	/// Frame times in seconds of the measured frames
+	pub deltas: Vec<f32>,
+}
+
+impl Benchmark {
+	pub fn new(count: usize) -> Self {
+		Self {
+			enemies: count,
+			projectiles: count,
+			towers_placed: false,
+			frames: 0,
+			deltas: Vec::with_capacity(MEASURED_FRAMES),
+		}
+	}
+
+	/// Counts the frame, its time is kept once the warmup is over. Returns `true` once the window is full.
+	pub fn record(&mut self, delta: f32) -> bool {
+		self.frames += 1;
+		if self.frames > WARMUP_FRAMES {
+			self.deltas.push(delta);
+		}
+		self.deltas.len() >= MEASURED_FRAMES
+	}
+
+	/// Average, shortest and longest frame time of the measured frames, in milliseconds, and how many
+	/// projectile entities were spawned for the whole run against the shots that reused pooled ones
+	pub fn report(&self, pool: &ProjectilePool) -> String {
+		let (average, min, max) = milliseconds(&self.deltas);
+		format!(
+			"Benchmark: {} enemies, {} projectiles, {} frames: average {:.3} ms ({:.1} FPS), min {:.3} ms, max {:.3} ms, \
+			 projectile entities spawned {}, reused {}",
+			self.enemies,
+			self.projectiles,
+			self.deltas.len(),
+			average,
+			if average > 0.0 { 1000.0 / average } else { 0.0 },
+			min,
+			max,
+			pool.spawned,
+			pool.reused
+		)
+	}
+}
+
+/// Terrain layouts `--bench-terrain [size]` measures one after the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerrainLayout {
+	/// Whole terrain in one mesh
+	Single,
+	/// Chunks of `TerrainConfig::chunk_size` tiles
+	Chunked,
+}
+
+impl TerrainLayout {
+	pub const ALL: [TerrainLayout; 2] = [TerrainLayout::Single, TerrainLayout::Chunked];
+}
+
+/// Frame and mesh rebuild times in seconds of the measured frames of a layout
+#[derive(Default)]
+pub struct LayoutTimes {
+	pub frames: Vec<f32>,
+	pub rebuilds: Vec<f32>,
+}
+
+/// Comparison of the terrain built as a single mesh against the chunked layout, see `run_terrain_benchmark`.
+/// Every frame edits a cell and rebuilds the meshes the edit touches, the layouts run for
+/// `WARMUP_FRAMES` and `MEASURED_FRAMES` each.
+pub struct TerrainBenchmark {
+	/// Cells along each side of the terrain
+	pub size: usize,
+	pub chunk_size: usize,
+	/// Index in `TerrainLayout::ALL` of the layout measured now
+	pub layout: usize,
+	/// Frames since the layout was spawned, `None` until it is
+	pub frames: Option<usize>,
+	pub times: [LayoutTimes; 2],
+}
+
+impl TerrainBenchmark {
+	pub fn new(size: usize, chunk_size: usize) -> Self {
+		Self {
+			size,
+			chunk_size,
+			layout: 0,
+			frames: None,
+			times: Default::default(),
+		}
+	}
+
+	/// Layout measured now, `None` once all of them are done
+	pub fn layout(&self) -> Option<TerrainLayout> {
+		TerrainLayout::ALL
+			.get(self.layout)
+			.copied()
+	}
+
+	/// Chunk size spawning the terrain in the `layout`
+	pub fn chunk_size(&self, layout: TerrainLayout) -> usize {
+		match layout {
+			TerrainLayout::Single => self.size,
+			TerrainLayout::Chunked => self.chunk_size,
+		}
+	}
+
+	/// Counts the frame of the current layout, its frame and rebuild times are kept once the warmup is over.
+	/// Moves on to the next layout once the window is full.
+	pub fn record(&mut self, delta: f32, rebuild: f32) {
+		let frames = self.frames.unwrap_or(0) + 1;
+		self.frames = Some(frames);
+		if frames <= WARMUP_FRAMES {
+			return;
+		}
+
+		let times = &mut self.times[self.layout];
+		times.frames.push(delta);
+		times.rebuilds.push(rebuild);
+		if times.frames.len() >= MEASURED_FRAMES {
+			self.layout += 1;
+			self.frames = None;
+		}
+	}
+
+	/// Average, shortest and longest frame and rebuild times of every layout, in milliseconds
+	pub fn report(&self) -> String {
+		let mut report = format!("Terrain benchmark: {}x{} cells, {} frames per layout", self.size, self.size, MEASURED_FRAMES);
+		for (layout, times) in TerrainLayout::ALL
+			.iter()
+			.zip(self.times.iter())
+		{
+			let (frame_average, frame_min, frame_max) = milliseconds(&times.frames);
+			let (rebuild_average, rebuild_min, rebuild_max) = milliseconds(&times.rebuilds);
+			report.push_str(&format!(
+				"\n  {:?} (chunks of {} tiles): frame average {:.3} ms, min {:.3} ms, max {:.3} ms; \
+				 rebuild average {:.3} ms, min {:.3} ms, max {:.3} ms",
+				layout,
+				self.chunk_size(*layout),
+				frame_average,
+				frame_min,
+				frame_max,
+				rebuild_average,
+				rebuild_min,
+				rebuild_max
+			));
+		}
+		report
+	}
+}
+
+/// Average, shortest and longest of the times in seconds, in milliseconds
+fn milliseconds(times: &[f32]) -> (f32, f32, f32) {
+	let count = times.len().max(1) as f32;
+	let average = times.iter().sum::<f32>() / count * 1000.0;
+	let min = times
+		.iter()
+		.copied()
+		.reduce(f32::min)
+		.unwrap_or(0.0)
+		* 1000.0;
+	let max = times.iter().copied().fold(0.0, f32::max) * 1000.0;
+	(average, min, max)
+}
@@ -0,0 +1,36 @@
+use dotrix::math::Point3;
+use dotrix::Camera;
+use serde::{Deserialize, Serialize};
+
+pub const BOOKMARK_SLOTS: usize = 4;
+
+/// Camera view saved in a bookmark slot
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct CameraBookmark {
+	pub target: [f32; 3],
+	pub xz_angle: f32,
+	/// Distance from the eye to the target
+	pub distance: f32,
+}
+
+impl CameraBookmark {
+	pub fn from_camera(camera: &Camera) -> Self {
+		Self {
+			target: [camera.target.x, camera.target.y, camera.target.z],
+			xz_angle: camera.xz_angle,
+			distance: camera.distance,
+		}
+	}
+
+	pub fn apply(&self, camera: &mut Camera) {
+		camera.target = Point3::new(self.target[0], self.target[1], self.target[2]);
+		camera.xz_angle = self.xz_angle;
+		camera.distance = self.distance;
+	}
+}
+
+/// Camera views saved with SHIFT and a number key, see `camera_bookmarks`
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CameraBookmarks {
+	pub slots: [Option<CameraBookmark>; BOOKMARK_SLOTS],
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::terrain::TerrainConfig;
+
+/// File the window and terrain configuration is read from at startup, see `load_config`
+pub const CONFIG_PATH: &str = "config.toml";
+
+/// Layout of `config.toml`, the window fields at the top level and the terrain ones in a `[terrain]`
+/// table, for example `width = 200`, `depth = 200` and `chunk_size = 16` on separate lines below it
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	#[serde(flatten)]
+	pub window: WindowConfig,
+	pub terrain: TerrainConfig,
+}
+
+/// Window the game starts in, for example
+/// `title = "TD"`, `width = 1920`, `height = 1080`, `fullscreen = false` on separate lines.
+/// Fields missing from the file are taken from the defaults.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+	pub title: String,
+	/// Inner size of the window in pixels, ignored in fullscreen
+	pub width: u32,
+	pub height: u32,
+	pub fullscreen: bool,
+}
+
+impl WindowConfig {
+	/// Keeps the size within what a window can be created with
+	pub fn clamped(self) -> Self {
+		Self {
+			width: self.width.max(1),
+			height: self.height.max(1),
+			..self
+		}
+	}
+}
+
+impl Default for WindowConfig {
+	fn default() -> Self {
+		Self {
+			title: String::from("Isometric TD Tech Demo"),
+			width: 1280,
+			height: 720,
+			fullscreen: false,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+	Io(std::io::Error),
+	/// Display of the TOML error includes the line and column
+	Format(toml::de::Error),
+}
+
+impl ConfigError {
+	/// Config file does not exist, the defaults are used then
+	pub fn is_missing(&self) -> bool {
+		matches!(self, ConfigError::Io(err) if err.kind() == std::io::ErrorKind::NotFound)
+	}
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ConfigError::Io(err) => write!(f, "config file I/O error: {}", err),
+			ConfigError::Format(err) => write!(f, "malformed config file: {}", err),
+		}
+	}
+}
+
+impl From<std::io::Error> for ConfigError {
+	fn from(err: std::io::Error) -> Self {
+		ConfigError::Io(err)
+	}
+}
+
+impl From<toml::de::Error> for ConfigError {
+	fn from(err: toml::de::Error) -> Self {
+		ConfigError::Format(err)
+	}
+}
+
+pub fn load_config(path: &str) -> Result<Config, ConfigError> {
+	let text = std::fs::read_to_string(path)?;
+	let config: Config = toml::from_str(&text)?;
+	Ok(Config {
+		window: config.window.clamped(),
+		terrain: config.terrain.clamped(),
+	})
+}
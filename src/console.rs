@@ -0,0 +1,131 @@
+use crate::enemies::EnemyKind;
+
+/// Lines of output the console keeps, the oldest ones are dropped
+pub const OUTPUT_LINES: usize = 100;
+/// Commands kept for browsing with up and down
+pub const HISTORY_LENGTH: usize = 50;
+/// Most enemies a single `spawn` may add
+pub const MAX_SPAWN: u32 = 200;
+
+pub const HELP: &str = "Commands: gold <amount>, spawn <fast|tank|swarm> [count], clear, tp <x> <z>, god, help";
+
+pub enum Command {
+	/// Sets the gold to the amount, the score is left as it is
+	Gold(u32),
+	/// Spawns enemies of the kind at the start of the path
+	Spawn(EnemyKind, u32),
+	/// Removes every enemy
+	Clear,
+	/// Moves the camera target to the X and Z position
+	Teleport(f32, f32),
+	/// Toggles whether the base loses lives
+	God,
+	Help,
+}
+
+/// Parses a console line, the error is the message printed for it
+pub fn parse(line: &str) -> Result<Command, String> {
+	let words = line
+		.split_whitespace()
+		.collect::<Vec<_>>();
+
+	match words.as_slice() {
+		["gold", amount] => amount
+			.parse()
+			.map(Command::Gold)
+			.map_err(|_| format!("Not an amount of gold: {}", amount)),
+		["spawn", kind] => parse_kind(kind).map(|kind| Command::Spawn(kind, 1)),
+		["spawn", kind, count] => {
+			let kind = parse_kind(kind)?;
+			match count.parse() {
+				Ok(count) if count <= MAX_SPAWN => Ok(Command::Spawn(kind, count)),
+				_ => Err(format!("Not a count from 0 to {}: {}", MAX_SPAWN, count)),
+			}
+		}
+		["clear"] => Ok(Command::Clear),
+		["tp", x, z] => match (x.parse::<f32>(), z.parse::<f32>()) {
+			(Ok(x), Ok(z)) if x.is_finite() && z.is_finite() => Ok(Command::Teleport(x, z)),
+			_ => Err(format!("Not a position: {} {}", x, z)),
+		},
+		["god"] => Ok(Command::God),
+		["help"] => Ok(Command::Help),
+		[] => Err(String::from("Empty command")),
+		[name, ..] => Err(format!("Unknown command or arguments: {}, type help for the commands", name)),
+	}
+}
+
+fn parse_kind(name: &str) -> Result<EnemyKind, String> {
+	EnemyKind::ALL
+		.iter()
+		.copied()
+		.find(|kind| format!("{:?}", kind).eq_ignore_ascii_case(name))
+		.ok_or_else(|| format!("Unknown enemy: {}", name))
+}
+
+/// Developer console toggled with `Action::ToggleConsole`, see `ui_console`
+#[derive(Default)]
+pub struct Console {
+	pub open: bool,
+	/// Line being typed
+	pub input: String,
+	pub output: Vec<String>,
+	/// Submitted lines, the latest last
+	history: Vec<String>,
+	/// Entry of `history` in the input while browsing it, `None` while typing a new line
+	browsing: Option<usize>,
+}
+
+impl Console {
+	pub fn print(&mut self, line: impl Into<String>) {
+		self.output.push(line.into());
+		if self.output.len() > OUTPUT_LINES {
+			self.output.remove(0);
+		}
+	}
+
+	/// Takes the typed line, echoes it and keeps it in the history. `None` if nothing is typed.
+	pub fn submit(&mut self) -> Option<String> {
+		let line = std::mem::take(&mut self.input)
+			.trim()
+			.to_string();
+		self.browsing = None;
+		if line.is_empty() {
+			return None;
+		}
+
+		self.print(format!("> {}", line));
+		if self.history.last() != Some(&line) {
+			self.history.push(line.clone());
+			if self.history.len() > HISTORY_LENGTH {
+				self.history.remove(0);
+			}
+		}
+		Some(line)
+	}
+
+	/// Puts the entry before the one shown into the input, up from the latest one
+	pub fn history_previous(&mut self) {
+		let index = match self.browsing {
+			Some(index) => index.saturating_sub(1),
+			None if self.history.is_empty() => return,
+			None => self.history.len() - 1,
+		};
+		self.browsing = Some(index);
+		self.input = self.history[index].clone();
+	}
+
+	/// Puts the entry after the one shown into the input, past the latest one the input is emptied
+	pub fn history_next(&mut self) {
+		match self.browsing {
+			Some(index) if index + 1 < self.history.len() => {
+				self.browsing = Some(index + 1);
+				self.input = self.history[index + 1].clone();
+			}
+			Some(_) => {
+				self.browsing = None;
+				self.input.clear();
+			}
+			None => {}
+		}
+	}
+}
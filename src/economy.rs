@@ -0,0 +1,56 @@
+pub const STARTING_GOLD: u32 = 100;
+/// Score of every life left when the last wave is defeated
+pub const SCORE_PER_LIFE: u32 = 50;
+
+pub struct Economy {
+	pub gold: u32,
+	/// Gold earned since the game started, the score shown on game over
+	pub earned: u32,
+	/// Towers bought since the game started
+	pub towers_built: u32,
+}
+
+impl Economy {
+	/// Deducts `amount` if the player can afford it
+	pub fn spend(&mut self, amount: u32) -> bool {
+		if self.gold < amount {
+			return false;
+		}
+		self.gold -= amount;
+		true
+	}
+
+	/// Score shown on victory, the gold left plus `SCORE_PER_LIFE` for every life left
+	pub fn victory_score(&self, lives: u32) -> u32 {
+		self.gold.saturating_add(lives.saturating_mul(SCORE_PER_LIFE))
+	}
+
+	pub fn earn(&mut self, amount: u32) {
+		self.gold = self.gold.saturating_add(amount);
+		self.earned = self.earned.saturating_add(amount);
+	}
+
+	/// Gives back gold spent, unlike `earn` it does not count for the score
+	pub fn reimburse(&mut self, amount: u32) {
+		self.gold = self.gold.saturating_add(amount);
+	}
+
+	/// Takes back gold given by `earn` if the player still has it, the score drops with it
+	pub fn take_back(&mut self, amount: u32) -> bool {
+		if !self.spend(amount) {
+			return false;
+		}
+		self.earned = self.earned.saturating_sub(amount);
+		true
+	}
+}
+
+impl Default for Economy {
+	fn default() -> Self {
+		Self {
+			gold: STARTING_GOLD,
+			earned: 0,
+			towers_built: 0,
+		}
+	}
+}
@@ -0,0 +1,180 @@
+use dotrix::ecs::Entity;
+
+/// Effect a projectile leaves on the enemy it hits, see `TowerKind::effect`
+#[derive(Debug, Clone, Copy)]
+pub enum StatusEffect {
+	/// Multiplies the speed of the enemy by `factor`
+	Slow { factor: f32, duration: f32 },
+	Poison { damage_per_second: f32, duration: f32 },
+}
+
+struct Slow {
+	factor: f32,
+	/// Seconds until the slow wears off
+	remaining: f32,
+}
+
+struct Poison {
+	damage_per_second: f32,
+	/// Seconds until the poison wears off
+	remaining: f32,
+	/// Tower credited with the kill if the poison finishes the enemy off
+	source: Entity,
+}
+
+/// Active effects of an enemy. Effects of one kind do not stack, a new one refreshes the duration
+/// to the longer of the two and keeps the stronger of the two factors or damages.
+#[derive(Default)]
+pub struct StatusEffects {
+	slow: Option<Slow>,
+	poison: Option<Poison>,
+}
+
+impl StatusEffects {
+	pub fn apply(&mut self, effect: StatusEffect, source: Entity) {
+		match effect {
+			StatusEffect::Slow {
+				factor,
+				duration,
+			} => {
+				self.slow = Some(match self.slow.take() {
+					Some(slow) => Slow {
+						factor: slow.factor.min(factor),
+						remaining: slow.remaining.max(duration),
+					},
+					None => Slow {
+						factor,
+						remaining: duration,
+					},
+				});
+			}
+			StatusEffect::Poison {
+				damage_per_second,
+				duration,
+			} => {
+				self.poison = Some(match self.poison.take() {
+					// The stronger poison is credited with the kill
+					Some(poison) if poison.damage_per_second > damage_per_second => Poison {
+						remaining: poison.remaining.max(duration),
+						..poison
+					},
+					Some(poison) => Poison {
+						damage_per_second,
+						remaining: poison.remaining.max(duration),
+						source,
+					},
+					None => Poison {
+						damage_per_second,
+						remaining: duration,
+						source,
+					},
+				});
+			}
+		}
+	}
+
+	/// Factor the speed of the enemy is multiplied by, 1.0 without a slow
+	pub fn speed_factor(&self) -> f32 {
+		self.slow
+			.as_ref()
+			.map(|slow| slow.factor)
+			.unwrap_or(1.0)
+	}
+
+	/// Advances the effects by `delta` seconds and drops the expired ones. Returns the poison damage
+	/// dealt meanwhile and the tower it is credited to.
+	pub fn tick(&mut self, delta: f32) -> Option<(f32, Entity)> {
+		if let Some(slow) = self.slow.as_mut() {
+			slow.remaining -= delta;
+			if slow.remaining <= 0.0 {
+				self.slow = None;
+			}
+		}
+
+		let poison = self.poison.as_mut()?;
+		// The last tick deals only the damage of the time left
+		let damage = poison.damage_per_second * delta.min(poison.remaining);
+		let source = poison.source;
+		poison.remaining -= delta;
+		if poison.remaining <= 0.0 {
+			self.poison = None;
+		}
+		Some((damage, source))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn slow(factor: f32, duration: f32) -> StatusEffect {
+		StatusEffect::Slow {
+			factor,
+			duration,
+		}
+	}
+
+	fn poison(damage_per_second: f32, duration: f32) -> StatusEffect {
+		StatusEffect::Poison {
+			damage_per_second,
+			duration,
+		}
+	}
+
+	#[test]
+	fn slows_refresh_instead_of_stacking() {
+		let tower = Entity::from(1);
+		let mut effects = StatusEffects::default();
+		effects.apply(slow(0.5, 1.0), tower);
+		effects.apply(slow(0.5, 3.0), tower);
+		assert_eq!(effects.speed_factor(), 0.5);
+
+		// The second slow refreshed the duration to 3 seconds rather than adding up to 4
+		effects.tick(2.5);
+		assert_eq!(effects.speed_factor(), 0.5);
+		effects.tick(1.0);
+		assert_eq!(effects.speed_factor(), 1.0);
+	}
+
+	#[test]
+	fn slow_is_capped_at_the_strongest() {
+		let tower = Entity::from(1);
+		let mut effects = StatusEffects::default();
+		effects.apply(slow(0.5, 2.0), tower);
+		effects.apply(slow(0.8, 2.0), tower);
+		assert_eq!(effects.speed_factor(), 0.5);
+		effects.apply(slow(0.5, 2.0), tower);
+		assert_eq!(effects.speed_factor(), 0.5);
+		effects.apply(slow(0.25, 2.0), tower);
+		assert_eq!(effects.speed_factor(), 0.25);
+	}
+
+	#[test]
+	fn effects_are_removed_after_their_duration() {
+		let tower = Entity::from(1);
+		let mut effects = StatusEffects::default();
+		assert_eq!(effects.tick(1.0), None);
+
+		effects.apply(slow(0.5, 1.0), tower);
+		effects.apply(poison(4.0, 1.5), tower);
+		assert_eq!(effects.tick(1.0), Some((4.0, tower)));
+		assert_eq!(effects.speed_factor(), 1.0);
+		// The last tick only deals the damage of the half second left
+		assert_eq!(effects.tick(1.0), Some((2.0, tower)));
+		assert_eq!(effects.tick(1.0), None);
+	}
+
+	#[test]
+	fn stronger_poison_takes_the_credit() {
+		let first = Entity::from(1);
+		let second = Entity::from(2);
+		let mut effects = StatusEffects::default();
+		effects.apply(poison(4.0, 1.0), first);
+		effects.apply(poison(2.0, 3.0), second);
+		assert_eq!(effects.tick(1.0), Some((4.0, first)));
+		effects.apply(poison(8.0, 1.0), second);
+		assert_eq!(effects.tick(1.0), Some((8.0, second)));
+		assert_eq!(effects.tick(1.0), Some((8.0, second)));
+		assert_eq!(effects.tick(1.0), None);
+	}
+}
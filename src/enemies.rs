@@ -0,0 +1,296 @@
+use dotrix::math::{InnerSpace, Vec3};
+use dotrix::ecs::Entity;
+use dotrix::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::effects::StatusEffects;
+use crate::terrain::TerrainGrid;
+
+/// Distance to a waypoint at which it counts as reached
+pub const WAYPOINT_EPSILON: f32 = 0.01;
+/// Share of the kind health the health of a spawned enemy may differ by either way
+pub const HEALTH_VARIANCE: f32 = 0.2;
+/// Largest distance of a spawned enemy from the spawn point across the path, unless the wave sets its own
+pub const SPAWN_JITTER: f32 = 0.2;
+/// Distance the last spawned enemy has to walk away from the spawn point before the next one spawns,
+/// unless the wave sets its own
+pub const SPAWN_SPACING: f32 = 0.3;
+/// Enemies closer than this push each other apart, see `separation`
+pub const SEPARATION_RADIUS: f32 = 0.3;
+/// Speed in world units per second two enemies at the same position are pushed apart with, it falls
+/// to nothing at `SEPARATION_RADIUS`
+pub const SEPARATION_STRENGTH: f32 = 1.0;
+/// Largest share of its own speed an enemy is pushed with, the rest keeps it walking to its waypoint
+pub const SEPARATION_MAX_SHARE: f32 = 0.5;
+/// Name of the mesh asset the path end markers are spawned with
+pub const PATH_MARKER_MESH: &str = "path_marker";
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
+pub enum EnemyKind {
+	/// Quick and fragile
+	Fast,
+	/// Slow and hard to take down
+	Tank,
+	/// Weak, comes in large numbers
+	Swarm,
+}
+
+impl EnemyKind {
+	pub const ALL: [EnemyKind; 3] = [EnemyKind::Fast, EnemyKind::Tank, EnemyKind::Swarm];
+
+	pub fn health(&self) -> f32 {
+		match self {
+			EnemyKind::Fast => 6.0,
+			EnemyKind::Tank => 30.0,
+			EnemyKind::Swarm => 4.0,
+		}
+	}
+
+	/// Movement speed in world units per second
+	pub fn speed(&self) -> f32 {
+		match self {
+			EnemyKind::Fast => 2.5,
+			EnemyKind::Tank => 0.8,
+			EnemyKind::Swarm => 1.5,
+		}
+	}
+
+	/// Gold awarded for killing the enemy
+	pub fn reward(&self) -> u32 {
+		match self {
+			EnemyKind::Fast => 6,
+			EnemyKind::Tank => 15,
+			EnemyKind::Swarm => 2,
+		}
+	}
+
+	/// Footprint and height of the enemy mesh
+	pub fn dimensions(&self) -> [f32; 3] {
+		match self {
+			EnemyKind::Fast => [0.2, 0.35, 0.2],
+			EnemyKind::Tank => [0.45, 0.4, 0.45],
+			EnemyKind::Swarm => [0.18, 0.18, 0.18],
+		}
+	}
+
+	/// Color the enemy is rendered with
+	pub fn color(&self) -> Color {
+		match self {
+			EnemyKind::Fast => Color::rgb(1.0, 0.7, 0.1),
+			EnemyKind::Tank => Color::rgb(0.5, 0.1, 0.1),
+			EnemyKind::Swarm => Color::rgb(0.8, 0.2, 0.6),
+		}
+	}
+
+	/// Name of the mesh asset stored by `init_enemy_meshes`
+	pub fn mesh_name(&self) -> &'static str {
+		match self {
+			EnemyKind::Fast => "enemy_fast",
+			EnemyKind::Tank => "enemy_tank",
+			EnemyKind::Swarm => "enemy_swarm",
+		}
+	}
+}
+
+pub struct Enemy {
+	pub kind: EnemyKind,
+	pub health: f32,
+	/// Health the enemy was spawned with, `health` drops from it with damage
+	pub max_health: f32,
+	/// Movement speed in world units per second
+	pub speed: f32,
+	/// Route to the goal, the spawn `Path` until towers make the enemy reroute
+	pub waypoints: Vec<Vec3>,
+	/// Index of the waypoint the enemy is walking to
+	pub waypoint_index: usize,
+	/// Slows and poisons of the projectiles that hit the enemy, see `tick_status_effects`
+	pub effects: StatusEffects,
+}
+
+impl Enemy {
+	/// Enemy with the stats of its kind and the health scaled by `health_factor`
+	pub fn new(kind: EnemyKind, health_factor: f32, waypoints: Vec<Vec3>) -> Self {
+		let health = kind.health() * health_factor;
+		Self {
+			kind,
+			health,
+			max_health: health,
+			speed: kind.speed(),
+			waypoints,
+			waypoint_index: 0,
+			effects: StatusEffects::default(),
+		}
+	}
+
+	/// Skips the first waypoint, the spawn point the enemy was placed next to, so an enemy spawned off the
+	/// path joins it at the second waypoint instead of walking back to the spawn point first
+	pub fn leaving_spawn(mut self) -> Self {
+		if self.waypoints.len() > 1 {
+			self.waypoint_index = 1;
+		}
+		self
+	}
+
+	/// Distance left to walk from `position` through the remaining waypoints, compares the progress
+	/// of enemies whose routes were recalculated from different cells
+	pub fn remaining_distance(&self, position: Vec3) -> f32 {
+		let remaining = self
+			.waypoints
+			.get(self.waypoint_index..)
+			.unwrap_or_default();
+
+		remaining
+			.iter()
+			.fold((position, 0.0), |(from, distance), waypoint| (*waypoint, distance + (*waypoint - from).magnitude()))
+			.1
+	}
+}
+
+/// Ordered waypoints enemies walk along from the spawn, see `update_navigation`
+#[derive(Default)]
+pub struct Path {
+	pub waypoints: Vec<Vec3>,
+}
+
+impl Path {
+	/// Path through the centers of the given grid cells
+	pub fn from_cells(grid: &TerrainGrid, cells: &[(i32, i32)]) -> Self {
+		Self {
+			waypoints: cells
+				.iter()
+				.map(|cell| grid.cell_to_world_center(*cell))
+				.collect(),
+		}
+	}
+}
+
+/// Marker floating above an end of the enemy path, see `animate_path_markers`
+#[derive(Clone, Copy)]
+pub enum PathMarker {
+	Spawn,
+	Goal,
+}
+
+/// Offset of `distance` from the first waypoint across the first leg of the path, along X axis for a path
+/// of a single waypoint
+pub fn lateral_offset(waypoints: &[Vec3], distance: f32) -> Vec3 {
+	let direction = match waypoints {
+		[first, second, ..] => Vec3::new(second.x - first.x, 0.0, second.z - first.z),
+		_ => Vec3::new(0.0, 0.0, 0.0),
+	};
+	if direction.magnitude2() < WAYPOINT_EPSILON * WAYPOINT_EPSILON {
+		return Vec3::new(distance, 0.0, 0.0);
+	}
+
+	let direction = direction.normalize();
+	Vec3::new(-direction.z, 0.0, direction.x) * distance
+}
+
+/// Velocity pushing the enemy at `position` away from the neighbors closer than `SEPARATION_RADIUS`,
+/// stronger the closer they are, by X and Z axis only and at most `max_speed`. The neighbors are
+/// positions of the enemy entities, `entity` itself is skipped and the ones at the very same position
+/// are pushed apart along X axis in the order of their ids.
+pub fn separation<'a>(position: Vec3, entity: Entity, neighbors: impl Iterator<Item = &'a (Vec3, Entity)>, max_speed: f32) -> Vec3 {
+	let mut push = Vec3::new(0.0, 0.0, 0.0);
+	for (other, other_entity) in neighbors {
+		if *other_entity == entity {
+			continue;
+		}
+		let offset = Vec3::new(position.x - other.x, 0.0, position.z - other.z);
+		let distance = offset.magnitude();
+		if distance >= SEPARATION_RADIUS {
+			continue;
+		}
+
+		let direction = if distance > WAYPOINT_EPSILON {
+			offset / distance
+		} else if entity.id() > other_entity.id() {
+			Vec3::new(1.0, 0.0, 0.0)
+		} else {
+			Vec3::new(-1.0, 0.0, 0.0)
+		};
+		push += direction * (SEPARATION_STRENGTH * (1.0 - distance / SEPARATION_RADIUS));
+	}
+
+	let speed = push.magnitude();
+	if speed > max_speed {
+		push * (max_speed.max(0.0) / speed)
+	} else {
+		push
+	}
+}
+
+/// Moves `position` towards `target` by at most `step`, returns `true` when the target is reached
+pub fn step_towards(position: &mut Vec3, target: Vec3, step: f32) -> bool {
+	let offset = target - *position;
+	let distance = offset.magnitude();
+
+	if distance <= step.max(WAYPOINT_EPSILON) {
+		*position = target;
+		return true;
+	}
+
+	*position += offset * (step / distance);
+	false
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn enemy_takes_the_stats_of_its_kind() {
+		for kind in EnemyKind::ALL {
+			let enemy = Enemy::new(kind, 1.5, Vec::new());
+			assert_eq!(enemy.kind, kind);
+			assert_eq!(enemy.speed, kind.speed());
+			assert_eq!(enemy.health, kind.health() * 1.5);
+			assert_eq!(enemy.max_health, enemy.health);
+		}
+	}
+
+	#[test]
+	fn kinds_have_their_own_meshes() {
+		for (index, kind) in EnemyKind::ALL
+			.iter()
+			.enumerate()
+		{
+			for other in &EnemyKind::ALL[index + 1..] {
+				assert_ne!(kind.mesh_name(), other.mesh_name());
+				assert_ne!(kind.dimensions(), other.dimensions());
+			}
+		}
+	}
+
+	#[test]
+	fn separation_is_capped_at_the_share_of_the_speed() {
+		let max_speed = EnemyKind::Tank.speed() * SEPARATION_MAX_SHARE;
+		let position = Vec3::new(0.0, 0.0, 0.0);
+		let neighbors = (1..=8)
+			.map(|id| (Vec3::new(0.01 * id as f32, 0.0, 0.0), Entity::from(id)))
+			.collect::<Vec<_>>();
+
+		let push = separation(position, Entity::from(0), neighbors.iter(), max_speed);
+		assert!((push.magnitude() - max_speed).abs() < 1e-4);
+		assert!(push.x < 0.0);
+	}
+
+	#[test]
+	fn separation_skips_the_enemy_itself() {
+		let position = Vec3::new(1.0, 0.0, 2.0);
+		let neighbors = [(position, Entity::from(1)), (Vec3::new(5.0, 0.0, 2.0), Entity::from(2))];
+		let push = separation(position, Entity::from(1), neighbors.iter(), 1.0);
+		assert_eq!(push.magnitude(), 0.0);
+	}
+
+	#[test]
+	fn enemies_at_the_same_position_are_split() {
+		let position = Vec3::new(1.0, 0.0, 2.0);
+		let neighbors = [(position, Entity::from(1)), (position, Entity::from(2))];
+		let first = separation(position, Entity::from(1), neighbors.iter(), 1.0);
+		let second = separation(position, Entity::from(2), neighbors.iter(), 1.0);
+		assert!(first.magnitude() > 0.0);
+		assert_eq!(first, -second);
+		assert_eq!((first.y, first.z), (0.0, 0.0));
+	}
+}
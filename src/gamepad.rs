@@ -0,0 +1,193 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+use serde::{Deserialize, Serialize};
+
+/// Share of the stick travel around the center that is ignored, a resting stick rarely reads exactly zero
+pub const STICK_DEAD_ZONE: f32 = 0.2;
+/// Share of the trigger travel that is ignored at rest
+pub const TRIGGER_DEAD_ZONE: f32 = 0.1;
+/// Time between two reads of the controller on the gamepad thread
+const POLL_INTERVAL: Duration = Duration::from_millis(4);
+
+/// Camera input of a controller, recorded with the frame like the keyboard and mouse
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GamepadAxes {
+	/// Left stick in the screen space of `CameraMotion`, X to the right and Z down, up to a magnitude of 1.0
+	pub pan: [f32; 2],
+	/// Right stick and triggers from -1.0 to 1.0, positive zooms in
+	pub zoom: f32,
+}
+
+impl GamepadAxes {
+	/// Maps the raw axes of a controller, the sticks report Y up and the triggers 0.0 at rest. Pushing the
+	/// right stick up or the right trigger zooms in, pulling the stick down or the left trigger zooms out.
+	pub fn from_axes(left_stick: (f32, f32), right_stick_y: f32, triggers: (f32, f32)) -> Self {
+		let (x, y) = dead_zone(left_stick, STICK_DEAD_ZONE);
+		let (left_trigger, right_trigger) = triggers;
+		let zoom = axis_dead_zone(right_stick_y, STICK_DEAD_ZONE) + axis_dead_zone(right_trigger, TRIGGER_DEAD_ZONE)
+			- axis_dead_zone(left_trigger, TRIGGER_DEAD_ZONE);
+		Self {
+			pan: [x, -y],
+			zoom: zoom.clamp(-1.0, 1.0),
+		}
+	}
+
+	pub fn is_panning(&self) -> bool {
+		self.pan != [0.0, 0.0]
+	}
+}
+
+/// Radial dead zone of a stick, the travel past it is rescaled to start from zero, so the camera speeds up
+/// smoothly from the edge of the zone
+pub fn dead_zone(stick: (f32, f32), zone: f32) -> (f32, f32) {
+	let (x, y) = stick;
+	let magnitude = x.hypot(y);
+	if magnitude <= zone {
+		return (0.0, 0.0);
+	}
+
+	let scale = ((magnitude - zone) / (1.0 - zone)).min(1.0) / magnitude;
+	(x * scale, y * scale)
+}
+
+/// Dead zone of a single axis, rescaled the same way as `dead_zone`
+pub fn axis_dead_zone(value: f32, zone: f32) -> f32 {
+	let (_, value) = dead_zone((0.0, value), zone);
+	value
+}
+
+/// Reads the first connected controller on a thread of its own, as `Gilrs` can not be shared between the
+/// systems. Without a controller, or without gamepad support on the platform, the axes stay at rest and
+/// the keyboard and mouse are all there is.
+pub struct Gamepad {
+	axes: Arc<Mutex<GamepadAxes>>,
+}
+
+impl Gamepad {
+	pub fn start() -> Self {
+		let axes = Arc::new(Mutex::new(GamepadAxes::default()));
+		let shared = Arc::clone(&axes);
+
+		std::thread::spawn(move || {
+			let mut gilrs = match Gilrs::new() {
+				Ok(gilrs) => gilrs,
+				Err(err) => {
+					log::warn!("No gamepad support available: {}", err);
+					return;
+				}
+			};
+
+			loop {
+				// Taking the events is what updates the state of the gamepads
+				while let Some(event) = gilrs.next_event() {
+					match event.event {
+						EventType::Connected => log::info!(
+							"Gamepad connected: {}",
+							gilrs
+								.gamepad(event.id)
+								.name()
+						),
+						EventType::Disconnected => log::info!("Gamepad disconnected"),
+						_ => {}
+					}
+				}
+
+				let read = gilrs
+					.gamepads()
+					.next()
+					.map(|(_, gamepad)| {
+						GamepadAxes::from_axes(
+							(gamepad.value(Axis::LeftStickX), gamepad.value(Axis::LeftStickY)),
+							gamepad.value(Axis::RightStickY),
+							(trigger(&gamepad, Button::LeftTrigger2), trigger(&gamepad, Button::RightTrigger2)),
+						)
+					})
+					.unwrap_or_default();
+				if let Ok(mut axes) = shared.lock() {
+					*axes = read;
+				}
+
+				std::thread::sleep(POLL_INTERVAL);
+			}
+		});
+
+		Self {
+			axes,
+		}
+	}
+
+	/// Latest axes of the controller, at rest if none is connected
+	pub fn axes(&self) -> GamepadAxes {
+		self.axes
+			.lock()
+			.map(|axes| *axes)
+			.unwrap_or_default()
+	}
+}
+
+/// Analog travel of a trigger from 0.0 to 1.0, triggers without one read as released
+fn trigger(gamepad: &gilrs::Gamepad<'_>, button: Button) -> f32 {
+	gamepad
+		.button_data(button)
+		.map(|data| data.value())
+		.unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_close(actual: f32, expected: f32) {
+		assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+	}
+
+	#[test]
+	fn resting_stick_is_ignored() {
+		assert_eq!(dead_zone((0.1, -0.15), STICK_DEAD_ZONE), (0.0, 0.0));
+		assert_eq!(axis_dead_zone(-0.2, STICK_DEAD_ZONE), 0.0);
+
+		let axes = GamepadAxes::from_axes((0.12, 0.1), 0.15, (0.05, 0.08));
+		assert!(!axes.is_panning());
+		assert_eq!(axes.zoom, 0.0);
+	}
+
+	#[test]
+	fn travel_past_the_dead_zone_is_rescaled() {
+		let (x, y) = dead_zone((0.6, 0.0), STICK_DEAD_ZONE);
+		assert_close(x, 0.5);
+		assert_close(y, 0.0);
+
+		// The direction is kept and a full push pans at full speed
+		let (x, y) = dead_zone((0.6, 0.8), STICK_DEAD_ZONE);
+		assert_close(x.hypot(y), 1.0);
+		assert_close(x / y, 0.75);
+
+		assert_close(axis_dead_zone(-1.0, STICK_DEAD_ZONE), -1.0);
+		assert_close(axis_dead_zone(0.55, TRIGGER_DEAD_ZONE), 0.5);
+	}
+
+	#[test]
+	fn left_stick_pans_in_screen_space() {
+		let up = GamepadAxes::from_axes((0.0, 1.0), 0.0, (0.0, 0.0));
+		assert_close(up.pan[0], 0.0);
+		assert_close(up.pan[1], -1.0);
+
+		let right = GamepadAxes::from_axes((1.0, 0.0), 0.0, (0.0, 0.0));
+		assert_close(right.pan[0], 1.0);
+		assert_close(right.pan[1], 0.0);
+		assert_eq!(right.zoom, 0.0);
+	}
+
+	#[test]
+	fn right_stick_and_triggers_zoom() {
+		assert_close(GamepadAxes::from_axes((0.0, 0.0), 1.0, (0.0, 0.0)).zoom, 1.0);
+		assert_close(GamepadAxes::from_axes((0.0, 0.0), -1.0, (0.0, 0.0)).zoom, -1.0);
+		assert_close(GamepadAxes::from_axes((0.0, 0.0), 0.0, (0.0, 1.0)).zoom, 1.0);
+		assert_close(GamepadAxes::from_axes((0.0, 0.0), 0.0, (1.0, 0.0)).zoom, -1.0);
+		// Both triggers cancel out, and the stick and a trigger together zoom no faster than either
+		assert_close(GamepadAxes::from_axes((0.0, 0.0), 0.0, (1.0, 1.0)).zoom, 0.0);
+		assert_close(GamepadAxes::from_axes((0.0, 0.0), 1.0, (0.0, 1.0)).zoom, 1.0);
+	}
+}
@@ -0,0 +1,63 @@
+use std::collections::{HashSet, VecDeque};
+
+use dotrix::ecs::Entity;
+
+use crate::towers::{TargetPriority, TowerKind};
+
+/// Number of build operations `ActionHistory` keeps, older ones can not be undone
+const HISTORY_LENGTH: usize = 32;
+
+/// Tower placed or sold by the player, with what it takes to reverse it
+pub enum BuildOperation {
+	/// Found by the cell, an undone sale spawns the tower again as another entity
+	Placed {
+		cell: (i32, i32),
+	},
+	Sold {
+		kind: TowerKind,
+		cell: (i32, i32),
+		level: u8,
+		priority: TargetPriority,
+		kills: u32,
+		/// Gold the player got for the tower
+		refund: u32,
+	},
+}
+
+/// Build operations undone with CTRL+Z, newest last. The history holds only while the towers are the
+/// ones it last saw, loading a game or restarting replaces them and so drops the history.
+#[derive(Default)]
+pub struct ActionHistory {
+	operations: VecDeque<BuildOperation>,
+	towers: HashSet<Entity>,
+}
+
+impl ActionHistory {
+	/// Adds an operation, `towers` are the tower entities right after it
+	pub fn record(&mut self, operation: BuildOperation, towers: HashSet<Entity>) {
+		if self.operations.len() == HISTORY_LENGTH {
+			self.operations.pop_front();
+		}
+		self.operations.push_back(operation);
+		self.towers = towers;
+	}
+
+	/// Takes the newest operation, `towers` are the tower entities now
+	pub fn pop(&mut self, towers: &HashSet<Entity>) -> Option<BuildOperation> {
+		if *towers != self.towers {
+			self.operations.clear();
+			return None;
+		}
+		self.operations.pop_back()
+	}
+
+	/// Puts back an operation `pop` took but could not be undone
+	pub fn restore(&mut self, operation: BuildOperation) {
+		self.operations.push_back(operation);
+	}
+
+	/// Updates the tower entities after an undo
+	pub fn sync(&mut self, towers: HashSet<Entity>) {
+		self.towers = towers;
+	}
+}
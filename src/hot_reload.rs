@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use dotrix::assets::Texture;
+use dotrix::ecs::Mut;
+use dotrix::pbr::Material;
+use dotrix::{Assets, Id, World};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Directory watched for changed textures
+pub const ASSETS_DIR: &str = "assets";
+/// Events of a file are merged until it stays unchanged this long, editors write a file in several steps
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches `ASSETS_DIR` and reimports the PNG textures changed in it, see `reload_textures`.
+/// Only built with the `dev` feature.
+pub struct AssetWatcher {
+	/// Kept alive for the events to keep coming, `None` if watching failed
+	_watcher: Option<Mutex<RecommendedWatcher>>,
+	events: Mutex<Receiver<DebouncedEvent>>,
+	/// Textures being reimported by name, with the id they had before
+	pending: HashMap<String, Id<Texture>>,
+}
+
+impl AssetWatcher {
+	/// Starts watching, a failure is logged and leaves the watcher idle
+	pub fn start() -> Self {
+		let (sender, receiver) = mpsc::channel();
+		let watcher = notify::watcher(sender, DEBOUNCE).and_then(|mut watcher| {
+			watcher.watch(ASSETS_DIR, RecursiveMode::Recursive)?;
+			Ok(watcher)
+		});
+
+		let watcher = match watcher {
+			Ok(watcher) => {
+				log::info!("Watching {} for changed textures", ASSETS_DIR);
+				Some(Mutex::new(watcher))
+			}
+			Err(err) => {
+				log::error!("Unable to watch {}, textures will not be reloaded: {}", ASSETS_DIR, err);
+				None
+			}
+		};
+
+		Self {
+			_watcher: watcher,
+			events: Mutex::new(receiver),
+			pending: HashMap::new(),
+		}
+	}
+
+	/// Paths of the PNG files written or created since the last call
+	fn changed_textures(&self) -> Vec<String> {
+		let events = self
+			.events
+			.lock()
+			.expect("Asset watcher events must not be poisoned");
+
+		let mut paths = Vec::new();
+		for event in events.try_iter() {
+			let path = match event {
+				DebouncedEvent::Write(path) | DebouncedEvent::Create(path) => path,
+				DebouncedEvent::Error(err, path) => {
+					log::warn!("Asset watcher error at {:?}: {}", path, err);
+					continue;
+				}
+				_ => continue,
+			};
+			let is_png = path
+				.extension()
+				.map(|extension| extension.eq_ignore_ascii_case("png"))
+				.unwrap_or(false);
+			if !is_png {
+				continue;
+			}
+			if let Some(path) = path.to_str() {
+				let path = path.replace('\\', "/");
+				if !paths.contains(&path) {
+					paths.push(path);
+				}
+			}
+		}
+
+		paths
+	}
+}
+
+/// Reimports changed textures and points the materials using the old texture to the reloaded one
+pub fn reload_textures(mut watcher: Mut<AssetWatcher>, mut assets: Mut<Assets>, mut world: Mut<World>) {
+	for path in watcher.changed_textures() {
+		let name = match Path::new(&path)
+			.file_stem()
+			.and_then(|stem| stem.to_str())
+		{
+			Some(name) => String::from(name),
+			None => continue,
+		};
+		let old = match assets.find::<Texture>(&name) {
+			Some(id) => id,
+			// Textures that were never imported are not used by any material
+			None => continue,
+		};
+
+		// Removed first, so the texture reads as loaded again only once the new file is imported
+		assets.remove(old);
+		assets.import(&path);
+		watcher.pending.insert(name, old);
+	}
+
+	let mut reloaded = Vec::new();
+	for (name, old) in watcher.pending.iter() {
+		let id = match assets.find::<Texture>(name) {
+			Some(id) if assets.get(id).is_some() => id,
+			_ => continue,
+		};
+
+		for (material,) in world.query::<(&mut Material,)>() {
+			if material.texture == *old {
+				material.texture = id;
+			}
+		}
+		log::info!("Reloaded texture {}", name);
+		reloaded.push(name.clone());
+	}
+
+	for name in reloaded {
+		watcher.pending.remove(&name);
+	}
+}
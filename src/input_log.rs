@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use dotrix::input::Modifiers;
+use dotrix::math::Vec2;
+
+/// File `InputLog::dump` writes to
+pub const INPUT_LOG_PATH: &str = "input_log.txt";
+/// Number of frames kept in the `InputLog`, a few seconds of play
+const INPUT_LOG_CAPACITY: usize = 300;
+
+/// Input seen in one frame
+pub struct InputEntry {
+	/// Frames recorded before this one since the log was created
+	pub frame: u64,
+	/// Actions pressed, held or released in the frame, each with its phase
+	pub actions: Vec<String>,
+	pub mouse: Option<Vec2>,
+	pub scroll: f32,
+	pub modifiers: Modifiers,
+}
+
+impl fmt::Display for InputEntry {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "#{}", self.frame)?;
+		match self.mouse {
+			Some(mouse) => write!(f, " mouse ({:.0}, {:.0})", mouse.x, mouse.y)?,
+			None => write!(f, " mouse outside")?,
+		}
+		if self.scroll != 0.0 {
+			write!(f, " scroll {:.1}", self.scroll)?;
+		}
+		for (held, name) in [(self.modifiers.shift(), "SHIFT"), (self.modifiers.ctrl(), "CTRL"), (self.modifiers.alt(), "ALT")] {
+			if held {
+				write!(f, " {}", name)?;
+			}
+		}
+		if !self.actions.is_empty() {
+			write!(f, " | {}", self.actions.join(", "))?;
+		}
+		Ok(())
+	}
+}
+
+/// Rolling history of the input of the last frames, oldest first. Recorded by `record_input` only
+/// while the input log debug option is on.
+pub struct InputLog {
+	entries: VecDeque<InputEntry>,
+	frames: u64,
+}
+
+impl InputLog {
+	/// Adds the input of a frame, `frame` of the entry is set here
+	pub fn push(&mut self, mut entry: InputEntry) {
+		if self.entries.len() == INPUT_LOG_CAPACITY {
+			self.entries.pop_front();
+		}
+		entry.frame = self.frames;
+		self.frames += 1;
+		self.entries.push_back(entry);
+	}
+
+	pub fn entries(&self) -> impl DoubleEndedIterator<Item = &InputEntry> + '_ {
+		self.entries.iter()
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+	}
+
+	/// Writes every entry to `path`, one line a frame
+	pub fn dump(&self, path: &str) -> std::io::Result<()> {
+		let text = self
+			.entries
+			.iter()
+			.map(|entry| format!("{}\n", entry))
+			.collect::<String>();
+		std::fs::write(path, text)
+	}
+}
+
+impl Default for InputLog {
+	fn default() -> Self {
+		Self {
+			entries: VecDeque::with_capacity(INPUT_LOG_CAPACITY),
+			frames: 0,
+		}
+	}
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pathfinding::NavGrid;
+use crate::terrain::TerrainGrid;
+
+/// File the level is read from at startup and written to by the level editor
+pub const LEVEL_PATH: &str = "level.json";
+
+/// What a click paints in the level editor, see `edit_level`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelBrush {
+	/// Moves the cell enemies are spawned at
+	Spawn,
+	/// Moves the cell enemies are walking to
+	Goal,
+	/// Blocks the cell for enemies and towers
+	Wall,
+	/// Removes walls
+	Eraser,
+}
+
+impl LevelBrush {
+	pub const ALL: [LevelBrush; 4] = [LevelBrush::Spawn, LevelBrush::Goal, LevelBrush::Wall, LevelBrush::Eraser];
+}
+
+impl Default for LevelBrush {
+	fn default() -> Self {
+		LevelBrush::Wall
+	}
+}
+
+/// Layout of `level.json`, for example
+/// `{ "width": 16, "depth": 16, "spawn": [0, 0], "goal": [15, 15], "walls": [[4, 2], [4, 3]] }`
+#[derive(Serialize, Deserialize)]
+pub struct Level {
+	/// Size of the grid the level was made for in cells, a level does not fit other terrain sizes
+	pub width: usize,
+	pub depth: usize,
+	pub spawn: (i32, i32),
+	pub goal: (i32, i32),
+	pub walls: Vec<(i32, i32)>,
+}
+
+impl Level {
+	/// Level of the grid as it is now, the walls are sorted so the file only changes with the level
+	pub fn from_nav_grid(nav_grid: &NavGrid) -> Self {
+		let mut walls = nav_grid
+			.walls()
+			.iter()
+			.copied()
+			.collect::<Vec<_>>();
+		walls.sort_unstable();
+		Self {
+			width: nav_grid.grid.width,
+			depth: nav_grid.grid.depth,
+			spawn: nav_grid.spawn,
+			goal: nav_grid.goal,
+			walls,
+		}
+	}
+
+	pub fn apply(&self, nav_grid: &mut NavGrid) {
+		let walls = self
+			.walls
+			.iter()
+			.copied()
+			.collect();
+		nav_grid.set_level(self.spawn, self.goal, walls);
+	}
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+	Io(std::io::Error),
+	/// Display of the JSON error includes the line and column
+	Format(serde_json::Error),
+	/// File parsed but describes a level that can not be played on the terrain
+	Invalid(String),
+}
+
+impl LevelError {
+	/// Level file does not exist, the default level is used then
+	pub fn is_missing(&self) -> bool {
+		matches!(self, LevelError::Io(err) if err.kind() == std::io::ErrorKind::NotFound)
+	}
+}
+
+impl fmt::Display for LevelError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LevelError::Io(err) => write!(f, "level file I/O error: {}", err),
+			LevelError::Format(err) => write!(f, "malformed level file: {}", err),
+			LevelError::Invalid(reason) => write!(f, "invalid level file: {}", reason),
+		}
+	}
+}
+
+impl From<std::io::Error> for LevelError {
+	fn from(err: std::io::Error) -> Self {
+		LevelError::Io(err)
+	}
+}
+
+impl From<serde_json::Error> for LevelError {
+	fn from(err: serde_json::Error) -> Self {
+		LevelError::Format(err)
+	}
+}
+
+pub fn load_level(path: &str, grid: &TerrainGrid) -> Result<Level, LevelError> {
+	let json = std::fs::read_to_string(path)?;
+	let level: Level = serde_json::from_str(&json)?;
+	validate(&level, grid)?;
+	Ok(level)
+}
+
+pub fn save_level(path: &str, level: &Level) -> Result<(), LevelError> {
+	let json = serde_json::to_string_pretty(level)?;
+	std::fs::write(path, json)?;
+	Ok(())
+}
+
+fn validate(level: &Level, grid: &TerrainGrid) -> Result<(), LevelError> {
+	if level.width != grid.width || level.depth != grid.depth {
+		return Err(LevelError::Invalid(format!(
+			"level is made for {}x{} cells, the terrain has {}x{}",
+			level.width, level.depth, grid.width, grid.depth
+		)));
+	}
+
+	let cells = [("spawn", level.spawn), ("goal", level.goal)]
+		.iter()
+		.copied()
+		.chain(
+			level
+				.walls
+				.iter()
+				.map(|cell| ("wall", *cell)),
+		);
+	for (name, cell) in cells {
+		if grid.cell_index(cell).is_none() {
+			return Err(LevelError::Invalid(format!("{} {:?} is outside of the terrain", name, cell)));
+		}
+	}
+
+	if level.spawn == level.goal {
+		return Err(LevelError::Invalid(String::from("spawn and goal are on the same cell")));
+	}
+
+	// Costs are not known before the heightmap is applied, `update_navigation` warns if they cut the goal off
+	let mut nav_grid = NavGrid::new(*grid, level.spawn, level.goal);
+	level.apply(&mut nav_grid);
+	if !nav_grid.is_walkable(level.spawn) || nav_grid.find_path(level.spawn, level.goal).is_none() {
+		return Err(LevelError::Invalid(String::from("goal can not be reached from the spawn")));
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::terrain::TerrainConfig;
+
+	fn grid() -> TerrainGrid {
+		TerrainGrid::from_config(&TerrainConfig {
+			width: 4,
+			depth: 3,
+			..Default::default()
+		})
+	}
+
+	fn level(spawn: (i32, i32), goal: (i32, i32), walls: &[(i32, i32)]) -> Level {
+		Level {
+			width: 4,
+			depth: 3,
+			spawn,
+			goal,
+			walls: walls.to_vec(),
+		}
+	}
+
+	#[test]
+	fn level_round_trips_through_json() {
+		let mut nav_grid = NavGrid::new(grid(), (0, 0), (3, 0));
+		assert!(nav_grid.move_spawn((0, 2)));
+		assert!(nav_grid.move_goal((3, 2)));
+		for cell in [(2, 2), (1, 0), (1, 2)] {
+			assert!(nav_grid.add_wall(cell));
+		}
+
+		let json = serde_json::to_string(&Level::from_nav_grid(&nav_grid)).unwrap();
+		let level: Level = serde_json::from_str(&json).unwrap();
+		assert_eq!(level.walls, vec![(1, 0), (1, 2), (2, 2)]);
+		assert!(validate(&level, &grid()).is_ok());
+
+		let mut loaded = NavGrid::new(grid(), (0, 0), (3, 0));
+		level.apply(&mut loaded);
+		assert_eq!(loaded.spawn, nav_grid.spawn);
+		assert_eq!(loaded.goal, nav_grid.goal);
+		assert_eq!(loaded.walls(), nav_grid.walls());
+	}
+
+	#[test]
+	fn size_mismatch_is_rejected() {
+		let level = Level {
+			width: 5,
+			..level((0, 0), (3, 0), &[])
+		};
+		assert!(matches!(validate(&level, &grid()), Err(LevelError::Invalid(_))));
+	}
+
+	#[test]
+	fn cells_outside_of_the_grid_are_rejected() {
+		for level in [level((-1, 0), (3, 0), &[]), level((0, 0), (4, 0), &[]), level((0, 0), (3, 0), &[(1, 3)])] {
+			assert!(matches!(validate(&level, &grid()), Err(LevelError::Invalid(_))));
+		}
+	}
+
+	#[test]
+	fn spawn_on_the_goal_is_rejected() {
+		assert!(matches!(validate(&level((1, 1), (1, 1), &[]), &grid()), Err(LevelError::Invalid(_))));
+	}
+
+	#[test]
+	fn walled_off_goal_is_rejected() {
+		let level = level((0, 0), (3, 0), &[(2, 0), (2, 1), (2, 2)]);
+		assert!(matches!(validate(&level, &grid()), Err(LevelError::Invalid(_))));
+	}
+}
@@ -0,0 +1,90 @@
+use std::f32::consts::PI;
+
+use dotrix::math::Vec3;
+use dotrix::Color;
+
+/// Distance of the sun from the scene center
+pub const SUN_DISTANCE: f32 = 1000.0;
+
+/// Runtime on/off switch of a light entity, toggled from the pause menu
+pub struct LightSwitch {
+	pub label: &'static str,
+	pub enabled: bool,
+}
+
+impl LightSwitch {
+	pub fn new(label: &'static str) -> Self {
+		Self {
+			label,
+			enabled: true,
+		}
+	}
+}
+
+/// Time of the day cycle, `t` runs from 0.0 to 1.0 where 0.0 is midnight and 0.5 is noon
+pub struct TimeOfDay {
+	pub t: f32,
+	pub day_length_seconds: f32,
+}
+
+impl TimeOfDay {
+	pub fn advance(&mut self, delta: f32) {
+		self.t = (self.t + delta / self.day_length_seconds).rem_euclid(1.0);
+	}
+
+	/// Hours and minutes on a 24 hour clock
+	pub fn clock(&self) -> (u32, u32) {
+		let minutes = (self.t * 24.0 * 60.0) as u32;
+		(minutes / 60 % 24, minutes % 60)
+	}
+
+	/// Sun position on a circle in the XY plane, below the horizon at night
+	pub fn sun_position(&self) -> Vec3 {
+		let angle = self.t * 2.0 * PI - PI / 2.0;
+		Vec3::new(angle.cos() * SUN_DISTANCE, angle.sin() * SUN_DISTANCE, 0.0)
+	}
+
+	/// Sun color and intensity blended between night, sunrise/sunset and noon
+	pub fn sun(&self) -> (Color, f32) {
+		let night = ([0.2, 0.2, 0.4], 0.05);
+		let sunrise = ([1.0, 0.6, 0.3], 0.3);
+		let noon = ([1.0, 1.0, 1.0], 0.5);
+
+		// Daylight rises from 0.0 at 6:00 to 1.0 at 12:00 and falls back by 18:00
+		let daylight = 1.0 - ((self.t - 0.5).abs() * 4.0).min(1.0);
+		let ((from_color, from_intensity), (to_color, to_intensity), k) = if daylight > 0.0 {
+			(sunrise, noon, daylight)
+		} else {
+			// Fade into the night during the hour after sunset and before sunrise
+			let darkness = (((self.t - 0.5).abs() - 0.25) * 24.0).min(1.0);
+			(sunrise, night, darkness)
+		};
+
+		let color = Color::rgb(
+			lerp(from_color[0], to_color[0], k),
+			lerp(from_color[1], to_color[1], k),
+			lerp(from_color[2], to_color[2], k),
+		);
+
+		(color, lerp(from_intensity, to_intensity, k))
+	}
+
+	/// Ambient light intensity, dimmer at night
+	pub fn ambient_intensity(&self) -> f32 {
+		let daylight = 1.0 - ((self.t - 0.5).abs() * 4.0).min(1.0);
+		lerp(0.1, 0.5, daylight)
+	}
+}
+
+impl Default for TimeOfDay {
+	fn default() -> Self {
+		Self {
+			t: 0.5,
+			day_length_seconds: 240.0,
+		}
+	}
+}
+
+fn lerp(from: f32, to: f32, k: f32) -> f32 {
+	from + (to - from) * k
+}
@@ -0,0 +1,126 @@
+use dotrix::egui::{self, epaint, Color32, Pos2, Vec2};
+
+/// Width in egui points of the transparent edge fading every line out, which smooths its sides
+const FEATHER: f32 = 1.0;
+/// Longest a corner of a line may stick out, in half widths, sharper corners are cut short
+const MITER_LIMIT: f32 = 4.0;
+/// Points closer than this in egui points are merged, a zero length segment has no direction
+const MIN_SEGMENT: f32 = 0.01;
+
+/// Width in egui points and color of a line drawn by `draw_polyline`
+#[derive(Clone, Copy)]
+pub struct LineStyle {
+	pub width: f32,
+	pub color: Color32,
+}
+
+impl LineStyle {
+	pub fn new(width: f32, color: Color32) -> Self {
+		Self {
+			width,
+			color,
+		}
+	}
+}
+
+/// Draws the line through the screen points, `None` points are behind the camera and break the line.
+/// A `closed` line joins its last point to the first one if none of its points is `None`.
+pub fn draw_polyline(painter: &egui::Painter, points: &[Option<Pos2>], closed: bool, style: LineStyle) {
+	if closed && points.iter().all(Option::is_some) {
+		let points = points
+			.iter()
+			.flatten()
+			.copied()
+			.collect::<Vec<_>>();
+		painter.add(egui::Shape::Mesh(polyline_mesh(&points, true, style)));
+		return;
+	}
+
+	for run in points.split(Option::is_none) {
+		let run = run
+			.iter()
+			.flatten()
+			.copied()
+			.collect::<Vec<_>>();
+		if run.len() >= 2 {
+			painter.add(egui::Shape::Mesh(polyline_mesh(&run, false, style)));
+		}
+	}
+}
+
+/// Quad strip along the points, `style.width` wide with a transparent `FEATHER` on either side.
+/// Every point gets four vertices across the line, the corners are mitered so the width stays the same
+/// through them.
+pub fn polyline_mesh(points: &[Pos2], closed: bool, style: LineStyle) -> epaint::Mesh {
+	let mut mesh = epaint::Mesh::default();
+
+	let mut points = points.to_vec();
+	points.dedup_by(|b, a| (*b - *a).length() < MIN_SEGMENT);
+	if closed && points.len() > 2 && (points[points.len() - 1] - points[0]).length() < MIN_SEGMENT {
+		points.pop();
+	}
+	let count = points.len();
+	if count < 2 {
+		return mesh;
+	}
+
+	let closed = closed && count > 2;
+	let segment_normal = |from: Pos2, to: Pos2| (to - from).normalized().rot90();
+	let half_width = style.width / 2.0;
+
+	for i in 0..count {
+		let previous = if i > 0 {
+			Some(segment_normal(points[i - 1], points[i]))
+		} else if closed {
+			Some(segment_normal(points[count - 1], points[0]))
+		} else {
+			None
+		};
+		let next = if i + 1 < count {
+			Some(segment_normal(points[i], points[i + 1]))
+		} else if closed {
+			Some(segment_normal(points[count - 1], points[0]))
+		} else {
+			None
+		};
+
+		let offset = match (previous, next) {
+			(Some(previous), Some(next)) => miter(previous, next),
+			(Some(normal), None) | (None, Some(normal)) => normal,
+			(None, None) => Vec2::new(0.0, 0.0),
+		};
+
+		let point = points[i];
+		mesh.colored_vertex(point + offset * (half_width + FEATHER), Color32::TRANSPARENT);
+		mesh.colored_vertex(point + offset * half_width, style.color);
+		mesh.colored_vertex(point - offset * half_width, style.color);
+		mesh.colored_vertex(point - offset * (half_width + FEATHER), Color32::TRANSPARENT);
+	}
+
+	let segments = if closed { count } else { count - 1 };
+	for segment in 0..segments {
+		let a = (segment * 4) as u32;
+		let b = (((segment + 1) % count) * 4) as u32;
+		// Outer feather, core and the other feather, two triangles each
+		for lane in 0..3 {
+			mesh.add_triangle(a + lane, b + lane, a + lane + 1);
+			mesh.add_triangle(a + lane + 1, b + lane, b + lane + 1);
+		}
+	}
+
+	mesh
+}
+
+/// Offset of a corner vertex for a half width of 1.0, between the normals of the two segments meeting
+/// at the corner and long enough to keep both segments at their width
+fn miter(previous: Vec2, next: Vec2) -> Vec2 {
+	let sum = previous + next;
+	if sum.length() < MIN_SEGMENT {
+		// Line turns straight back, the corner is cut square
+		return previous;
+	}
+
+	let direction = sum.normalized();
+	let cos = direction.x * previous.x + direction.y * previous.y;
+	direction * (1.0 / cos.max(1.0 / MITER_LIMIT))
+}
@@ -1,23 +1,192 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_mut)]
 
-use dotrix::assets::Mesh;
+mod audio;
+mod base;
+mod bench;
+mod bookmarks;
+mod config;
+#[cfg(feature = "dev")]
+mod console;
+mod economy;
+mod effects;
+mod enemies;
+mod gamepad;
+mod history;
+#[cfg(feature = "dev")]
+mod hot_reload;
+mod input_log;
+mod level;
+mod lighting;
+mod lines;
+mod mesh_gen;
+mod pathfinding;
+mod picking;
+mod replay;
+mod rng;
+mod save;
+mod sculpt;
+mod settings;
+mod shake;
+mod skyboxes;
+mod spatial;
+mod stats;
+mod terrain;
+mod theme;
+mod towers;
+mod waves;
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use dotrix::assets::{Mesh, Texture};
 use dotrix::camera;
+use dotrix::ecs::Entity;
 use dotrix::egui::{self, Egui};
 use dotrix::input::{ActionMapper, Button, KeyCode, Mapper};
-use dotrix::math::{Point3, Vec3};
+use dotrix::math::{InnerSpace, Point3, Vec2, Vec2u, Vec3};
 use dotrix::overlay::{self, Overlay};
 use dotrix::pbr::{self, Light};
 use dotrix::prelude::*;
 use dotrix::sky::{skybox, SkyBox};
-use dotrix::{Animator, Assets, Camera, Color, CubeMap, Frame, Input, Pipeline, State, Transform, Window, World};
+use dotrix::{Animator, Assets, Camera, Color, CubeMap, Frame, Id, Input, Pipeline, State, Transform, Window, World};
+use serde::{Deserialize, Serialize};
 
+use audio::Audio;
+use base::BaseHealth;
+use bench::{Benchmark, TerrainBenchmark};
+use bookmarks::{CameraBookmark, CameraBookmarks};
+use config::{Config, WindowConfig};
+use economy::Economy;
+use input_log::{InputEntry, InputLog};
+use level::{Level, LevelBrush};
+use enemies::{Enemy, EnemyKind, Path, PathMarker};
+use gamepad::{Gamepad, GamepadAxes};
+use history::BuildOperation;
+use lighting::{LightSwitch, TimeOfDay};
+use lines::LineStyle;
+use pathfinding::NavGrid;
+use picking::{BoxDrag, Ray, Selection};
+use replay::{ActionState, InputFrame, InputSource, ModifierKeys, Recording, ReplayInput};
+use rng::Rng;
+use save::{SaveGame, SavedTower};
+use sculpt::{BrushFalloff, SculptBrush};
+use settings::{FrameRateCap, Settings};
+use shake::CameraShake;
+use skyboxes::{SkyBoxSet, SkyBoxes};
+use spatial::SpatialGrid;
+use stats::{CullingStats, FrameStats};
+use terrain::{CostMap, Heightmap, TerrainChunk, TerrainConfig, TerrainGrid};
+use theme::{ThemeKind, UiTheme};
+use towers::{BuildState, Projectile, ProjectilePool, SplashRing, TargetPriority, Tower, TowerKind};
+use waves::{WavePhase, WaveSpawner};
+
+// Color of the debug drawings over the scene, labels take theirs from the `UiTheme`
 const DEBUG_YELLOW: egui::Rgba = egui::Rgba::from_rgb(255.0, 255.0, 0.0);
-const PAN_SPEED: f32 = 30.0;
-const SCROLL_SPEED: f32 = 60.0;
+const NOTIFICATION_SECONDS: f32 = 2.0;
+// Gameplay ticks per second of `GameClock`
+const GAMEPLAY_TICK_RATE: f32 = 60.0;
+// Longest frame time the gameplay advances by, so a stalled frame does not make enemies and projectiles jump
+const MAX_FRAME_DELTA: f32 = 0.1;
+// Part of the frame interval `limit_frame_rate` spins through instead of sleeping, sleeps overshoot
+const FRAME_LIMIT_SPIN: Duration = Duration::from_millis(1);
+// Seconds to wait for the imported assets before offering to continue without them
+const LOADING_TIMEOUT: f32 = 10.0;
+// Shortest time between two autosaves, so pausing repeatedly does not write the file every time
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+// Pan acceleration in units per second squared and the damping rate applied once the input is released
+const PAN_ACCEL: f32 = 120.0;
+const PAN_DAMPING: f32 = 8.0;
+// Camera yaw speed in radians per second
+const ROTATE_SPEED: f32 = 1.5;
+const EDGE_MARGIN: f32 = 12.0;
+const TERRAIN_BASE_HEIGHT: f32 = 0.0;
+// Camera view set at startup, `Action::ResetCamera` restores the target and the angle of the settings
+const DEFAULT_CAMERA_TARGET: (f32, f32, f32) = (0.0, -8.5, 0.0);
+const DEFAULT_CAMERA_XZ_ANGLE: f32 = 1.2;
+const CAMERA_RESET_SECONDS: f32 = 0.3;
+// Rate at which the camera closes in on the focused enemy, the share of the distance left after a second is exp(-rate)
+const FOCUS_FOLLOW_RATE: f32 = 6.0;
+// World units the screen center may go past the terrain edges, see `CameraBounds`
+const CAMERA_BOUNDS_MARGIN: f32 = 4.0;
+// Free camera movement speed in units per second, mouse look speed in radians per pixel and the pitch limit
+const FREE_CAM_SPEED: f32 = 10.0;
+const FREE_CAM_SENSITIVITY: f32 = 0.003;
+const FREE_CAM_MAX_PITCH: f32 = 1.5;
+// Bounds of the camera eye height above the terrain base, see `camera_zoom`
+const MIN_ZOOM: f32 = 2.0;
+const MAX_ZOOM: f32 = 60.0;
+// Tints drawn over the tiles a tower can and can not be built on, and over the walls of the level
+const TILE_BUILDABLE: [f32; 4] = [0.4, 1.0, 0.4, 0.35];
+const TILE_BLOCKED: [f32; 4] = [1.0, 0.4, 0.4, 0.35];
+const TILE_WALL: [f32; 4] = [0.35, 0.35, 0.4, 0.8];
+const TILE_TINTS: [[f32; 4]; 3] = [TILE_BUILDABLE, TILE_BLOCKED, TILE_WALL];
+// Tints of the ghost tower where it can and can not be placed
+const GHOST_VALID: [f32; 4] = [0.3, 1.0, 0.3, 0.5];
+const GHOST_BLOCKED: [f32; 4] = [1.0, 0.3, 0.3, 0.5];
+// Height of the selection highlight above the terrain, enough to avoid z-fighting
+const HIGHLIGHT_OFFSET: f32 = 0.01;
+// Height of the tile tints above the terrain, below the highlight
+const TINT_OFFSET: f32 = 0.005;
+// Side of the minimap in egui points
+const MINIMAP_SIZE: f32 = 160.0;
+// Distance from the picked point within which terrain vertices are counted
+const VERTEX_PICK_RADIUS: f32 = 1.0;
+// Number of segments of the tower range ring and the splash ring meshes
+const RANGE_RING_SEGMENTS: usize = 48;
+// Colors of the range ring and the splash rings, the splash rings fade out from the alpha
+const RANGE_RING_COLOR: [f32; 4] = [0.4, 0.7, 1.0, 0.8];
+const SPLASH_RING_COLOR: [f32; 4] = [1.0, 0.63, 0.16, 1.0];
+// Width in egui points of the outlines of the selected towers
+const OUTLINE_WIDTH: f32 = 2.0;
+// Width in egui points and color of the enemy path line, the color of the spawn marker
+const ENEMY_PATH_WIDTH: f32 = 3.0;
+const ENEMY_PATH_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 80, 200);
+// Tints of `ui_tile_states` for the cells with a tower, on the enemy path and the ones a tower can be built on
+const TILE_STATE_TOWER: egui::Color32 = egui::Color32::from_rgba_premultiplied(90, 0, 0, 90);
+const TILE_STATE_PATH: egui::Color32 = egui::Color32::from_rgba_premultiplied(0, 30, 90, 90);
+const TILE_STATE_BUILDABLE: egui::Color32 = egui::Color32::from_rgba_premultiplied(0, 70, 0, 90);
+// Health bar size in egui points and its height in world units above the enemy base
+const HEALTH_BAR_SIZE: (f32, f32) = (24.0, 4.0);
+// Frames of the input log listed by `ui_input_log`, the dump has all of them
+const INPUT_LOG_LINES: usize = 20;
+const HEALTH_BAR_ELEVATION: f32 = 0.6;
+// Height of the path end markers above the terrain base, and how far and how fast they bob around it
+const PATH_MARKER_ELEVATION: f32 = 0.5;
+const PATH_MARKER_BOB_HEIGHT: f32 = 0.1;
+const PATH_MARKER_BOB_SPEED: f32 = 2.0;
+// Size in world units of the path end markers and the number of sides of their cylinders
+const PATH_MARKER_RADIUS: f32 = 0.1;
+const PATH_MARKER_HEIGHT: f32 = 0.2;
+const PATH_MARKER_SEGMENTS: usize = 12;
+// Side in world units of the cells enemies are bucketed in for tower targeting. Smaller cells skip more
+// enemies out of range but a tower visits more of them, about a tile to a tower range is reasonable.
+const TARGETING_CELL_SIZE: f32 = 2.0;
+// Output lines shown by the console and the width of its input in egui points
+#[cfg(feature = "dev")]
+const CONSOLE_LINES: usize = 12;
+#[cfg(feature = "dev")]
+const CONSOLE_WIDTH: f32 = 360.0;
 
 struct MainState {
 	name: String,
 	positions: Vec<[f32; 3]>,
+	uvs: Vec<[f32; 2]>,
+	/// Tints of the tiles, one of the `TILE_TINTS` or `terrain::TILE_WHITE` per vertex, see `update_tile_colors`
+	colors: Vec<[f32; 4]>,
+	/// Terrain chunks and their meshes, the vertex arrays above are those of the whole terrain
+	chunks: Vec<ChunkMesh>,
+	texture: Id<Texture>,
+	/// Heightmap texture waiting to be loaded and applied to the terrain
+	heightmap: Option<Id<Texture>>,
+	/// Cost map texture waiting to be loaded and applied to the navigation grid, see `apply_cost_map`
+	cost_map: Option<Id<Texture>>,
+	/// Time the game was last saved by `autosave`
+	last_autosave: Option<Instant>,
+}
+
+/// Main menu shown at startup before the game world is spawned, see `ui_menu`
+struct MenuState {
+	name: String,
 }
 
 struct PauseState {
@@ -25,9 +194,364 @@ struct PauseState {
 	handled: bool,
 }
 
+/// Settings screen opened from the pause menu, returns to it on "Back"
+struct SettingsState {
+	name: String,
+	/// Action waiting for a key press to be bound to
+	rebinding: Option<Action>,
+	rebind_warning: Option<String>,
+	/// Text of the seed field, filled from the settings when the window opens and applied once it parses
+	seed_text: Option<String>,
+}
+
+/// Pushed over the main state by `start_game` until the imported textures are loaded, see `ui_loading`
+struct LoadingState {
+	name: String,
+	/// Textures that are not loaded yet
+	pending: Vec<Id<Texture>>,
+	total: usize,
+	/// Seconds since the game was started
+	elapsed: f32,
+}
+
+/// Asks to confirm exiting the game, pushed on top of any other state
+struct ConfirmExitState {
+	name: String,
+	/// Cursor grab to restore when the player decides to stay
+	resume_grab: bool,
+}
+
+/// Shown once the base has lost all of its lives, `ui_game_over` restarts from it
+struct GameOverState {
+	name: String,
+}
+
+/// Shown once the last wave is defeated, `ui_victory` starts a new game from it
+struct VictoryState {
+	name: String,
+}
+
 struct Player {}
 
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+/// Terrain chunk entity or one of its tint overlays, `chunk` is the index of the chunk in `MainState::chunks`
+struct Terrain {
+	chunk: usize,
+}
+
+/// Terrain chunk spawned by `init_terrain`
+struct ChunkMesh {
+	chunk: TerrainChunk,
+	mesh: Id<Mesh>,
+	/// Meshes of the tiles of each of the `TILE_TINTS`, drawn over the chunk in the tint color
+	tints: Vec<Id<Mesh>>,
+	/// World space bounding box of the chunk, updated whenever its mesh is rebuilt
+	bounds: (Vec3, Vec3),
+	/// Entities of the chunk are in the world, `cull_terrain_chunks` exiles them while the chunk is out of view
+	spawned: bool,
+}
+
+/// Marker of the quad highlighting the selected terrain cell
+struct Highlight {}
+
+/// Ring around the tower under the cursor showing its range, its mesh is rebuilt when the range changes
+/// so the band keeps its width
+struct RangeRing {
+	mesh: Id<Mesh>,
+	range: f32,
+}
+
+/// Translucent tower of the selected kind previewing a placement in build mode, see `update_ghost_tower`
+struct GhostTower {
+	kind: TowerKind,
+}
+
+/// How `player_control` moves the camera
+enum CameraMode {
+	/// Target panning over the terrain
+	Isometric,
+	/// Mouse look and movement along the view direction, keeps the isometric view to return to
+	Free {
+		target: Point3,
+		y_angle: f32,
+		xz_angle: f32,
+	},
+}
+
+/// Area of the ground the screen center is kept over by `player_control`, the terrain and a margin around it
+struct CameraBounds {
+	min: (f32, f32),
+	max: (f32, f32),
+}
+
+impl CameraBounds {
+	fn from_grid(grid: &TerrainGrid, margin: f32) -> Self {
+		let (extent_x, extent_z) = grid.extent();
+		let margin = margin.max(0.0);
+		Self {
+			min: (grid.origin.x - margin, grid.origin.z - margin),
+			max: (grid.origin.x + extent_x + margin, grid.origin.z + extent_z + margin),
+		}
+	}
+
+	fn clamp(&self, x: f32, z: f32) -> (f32, f32) {
+		(x.clamp(self.min.0, self.max.0), z.clamp(self.min.1, self.max.1))
+	}
+}
+
+/// Camera panning velocity, integrated into `camera.target` by `player_control`
+struct CameraMotion {
+	velocity: Vec3,
+	mode: CameraMode,
+	/// Transition back to the default view, the player has no control until it is finished
+	reset: Option<CameraReset>,
+	/// `Settings::camera_angle` last set on the camera, a changed setting is applied once so bookmarks keep their angle
+	angle: Option<f32>,
+	/// Enemy the camera follows, picked with `Action::FocusEnemy` and dropped on panning
+	focus: Option<Entity>,
+}
+
+struct CameraReset {
+	target: Point3,
+	xz_angle: f32,
+	elapsed: f32,
+}
+
+impl Default for CameraMotion {
+	fn default() -> Self {
+		Self {
+			velocity: Vec3::new(0.0, 0.0, 0.0),
+			mode: CameraMode::Isometric,
+			reset: None,
+			angle: None,
+			focus: None,
+		}
+	}
+}
+
+impl CameraMotion {
+	/// Starts the transition from the current view of the camera
+	fn start_reset(&mut self, camera: &Camera) {
+		self.velocity = Vec3::new(0.0, 0.0, 0.0);
+		self.mode = CameraMode::Isometric;
+		self.focus = None;
+		self.reset = Some(CameraReset {
+			target: camera.target,
+			xz_angle: camera.xz_angle,
+			elapsed: 0.0,
+		});
+	}
+
+	/// Moves the camera towards the default target and the `xz_angle` of the settings over `CAMERA_RESET_SECONDS`
+	fn step_reset(&mut self, camera: &mut Camera, xz_angle: f32, delta: f32) {
+		let reset = match self.reset.as_mut() {
+			Some(reset) => reset,
+			None => return,
+		};
+
+		reset.elapsed += delta;
+		let t = (reset.elapsed / CAMERA_RESET_SECONDS).min(1.0);
+		// Smoothstep eases the camera in and out
+		let t = t * t * (3.0 - 2.0 * t);
+
+		let (x, y, z) = DEFAULT_CAMERA_TARGET;
+		camera.target = Point3::new(
+			reset.target.x + (x - reset.target.x) * t,
+			reset.target.y + (y - reset.target.y) * t,
+			reset.target.z + (z - reset.target.z) * t,
+		);
+		camera.xz_angle = reset.xz_angle + (xz_angle - reset.xz_angle) * t;
+
+		if reset.elapsed >= CAMERA_RESET_SECONDS {
+			self.reset = None;
+		}
+	}
+
+	/// Accelerates towards `direction` at `PAN_ACCEL` up to `max_speed`, or damps the velocity if there is no input
+	fn update(&mut self, direction: Vec3, max_speed: f32, delta: f32) {
+		if direction.x == 0.0 && direction.z == 0.0 {
+			// Exponential decay keeps the stopping distance independent from the frame rate
+			self.velocity *= (-PAN_DAMPING * delta).exp();
+			if self.velocity.magnitude2() < 0.0001 {
+				self.velocity = Vec3::new(0.0, 0.0, 0.0);
+			}
+			return;
+		}
+
+		self.velocity += direction * (PAN_ACCEL * delta);
+
+		let max_speed = max_speed * direction.magnitude();
+		let speed = self.velocity.magnitude();
+		if speed > max_speed {
+			self.velocity *= max_speed / speed;
+		}
+	}
+}
+
+/// Debug rendering switches, all off by default
+#[derive(Default)]
+struct DebugOptions {
+	wireframe: bool,
+	/// Entity counters of `ui_debug_stats`
+	stats: bool,
+	/// Cells tinted by their pathfinding cost, see `ui_path_costs`
+	costs: bool,
+	/// Terrain chunks outside of the view are hidden, see `cull_terrain_chunks`
+	culling: bool,
+	/// Input of every frame is recorded and shown by `ui_input_log`
+	input_log: bool,
+	/// Route of the enemies from the spawn to the goal, see `ui_enemy_path`
+	path: bool,
+	/// Side panel of `ui_main`, toggled from the status bar
+	info_panel: bool,
+	/// Cells tinted by whether a tower stands on them, the path crosses them or a tower can be built there,
+	/// see `ui_tile_states`
+	tiles: bool,
+}
+
+/// Speed of the gameplay time, the camera and UI keep using the real frame time
+struct TimeScale {
+	factor: f32,
+}
+
+impl TimeScale {
+	/// Factors offered in the pause menu, 0.0 stops the gameplay but keeps rendering
+	const PRESETS: [f32; 4] = [0.0, 0.5, 1.0, 2.0];
+}
+
+impl Default for TimeScale {
+	fn default() -> Self {
+		Self {
+			factor: 1.0,
+		}
+	}
+}
+
+/// Gameplay time advanced by `tick_game_clock`, which runs only in the main state. The simulation
+/// advances in ticks of `timestep` seconds, the scaled frame time is accumulated and spent on
+/// as many whole ticks as it covers, so the gameplay steps are the same at any frame rate.
+struct GameClock {
+	/// Gameplay seconds of a tick
+	timestep: f32,
+	/// Ticks the gameplay systems run this frame, zero or more
+	ticks: u32,
+	/// Gameplay seconds not spent on a tick yet, always less than `timestep`
+	accumulator: f32,
+	/// Gameplay seconds since startup
+	elapsed: f32,
+}
+
+impl GameClock {
+	fn with_tick_rate(ticks_per_second: f32) -> Self {
+		Self {
+			timestep: 1.0 / ticks_per_second,
+			ticks: 0,
+			accumulator: 0.0,
+			elapsed: 0.0,
+		}
+	}
+
+	/// Gameplay seconds simulated this frame
+	fn frame_time(&self) -> f32 {
+		self.ticks as f32 * self.timestep
+	}
+
+	/// Share of the next tick already accumulated, renders `Motion` between the last two ticks
+	fn alpha(&self) -> f32 {
+		self.accumulator / self.timestep
+	}
+}
+
+impl Default for GameClock {
+	fn default() -> Self {
+		Self::with_tick_rate(GAMEPLAY_TICK_RATE)
+	}
+}
+
+/// Simulated position of a moving entity, its `Transform` is interpolated between the positions
+/// of the last two ticks by `interpolate_motion`. Gameplay reads the position from here.
+struct Motion {
+	previous: Vec3,
+	current: Vec3,
+}
+
+impl Motion {
+	fn at(position: Vec3) -> Self {
+		Self {
+			previous: position,
+			current: position,
+		}
+	}
+
+	/// Keeps the current position as the previous one, called before every tick moving the entity
+	fn start_tick(&mut self) {
+		self.previous = self.current;
+	}
+}
+
+#[derive(Default)]
+struct PathMarkersContext {
+	/// Seconds the markers have been bobbing for
+	elapsed: f32,
+}
+
+#[derive(Default)]
+struct TileColorsContext {
+	/// `NavGrid::revision` the colors were last calculated for, `None` before the first update
+	revision: Option<u64>,
+}
+
+#[derive(Default)]
+struct TileStatesContext {
+	/// `NavGrid::revision` `buildable` was calculated for, `None` before the first update
+	revision: Option<u64>,
+	/// Cells a tower can be built on without cutting the path off
+	buildable: HashSet<(i32, i32)>,
+}
+
+#[derive(Default)]
+struct ThemeContext {
+	/// Theme whose visuals egui has, `None` before the first frame
+	applied: Option<ThemeKind>,
+}
+
+#[derive(Default)]
+struct WindowConfigContext {
+	/// Config is applied once, on the first frame
+	applied: bool,
+}
+
+#[derive(Default)]
+struct WavesContext {
+	/// Waves file is read once, on the first frame
+	loaded: bool,
+}
+
+#[derive(Default)]
+struct FrameLimitContext {
+	/// End of the previous frame, `None` before the first one
+	last_frame: Option<Instant>,
+}
+
+#[derive(Default)]
+struct SaveContext {
+	/// Save file is restored once, on the first frame of the main state
+	restored_on_startup: bool,
+}
+
+/// Transient messages shown by `ui_notifications`, each with its remaining seconds
+#[derive(Default)]
+struct Notifications {
+	queue: Vec<(String, f32)>,
+}
+
+impl Notifications {
+	/// Shows the message for `NOTIFICATION_SECONDS`
+	fn notify(&mut self, message: impl Into<String>) {
+		self.queue.push((message.into(), NOTIFICATION_SECONDS));
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 enum Action {
 	TogglePause,
 	Exit,
@@ -35,6 +559,72 @@ enum Action {
 	PanDown,
 	PanLeft,
 	PanRight,
+	PlaceTower,
+	Save,
+	Load,
+	RotateLeft,
+	RotateRight,
+	CycleSky,
+	ToggleWireframe,
+	ToggleDebug,
+	ToggleCosts,
+	ResetCamera,
+	ToggleFreeCam,
+	Sell,
+	ToggleSculpt,
+	ToggleCulling,
+	ToggleInputLog,
+	Undo,
+	SaveReplay,
+	Bookmark1,
+	Bookmark2,
+	Bookmark3,
+	Bookmark4,
+	FocusEnemy,
+	TogglePath,
+	ToggleConsole,
+	ToggleTiles,
+	ToggleEditor,
+}
+
+impl Action {
+	const ALL: [Action; 32] = [
+		Action::TogglePause,
+		Action::Exit,
+		Action::PanUp,
+		Action::PanDown,
+		Action::PanLeft,
+		Action::PanRight,
+		Action::PlaceTower,
+		Action::Save,
+		Action::Load,
+		Action::RotateLeft,
+		Action::RotateRight,
+		Action::CycleSky,
+		Action::ToggleWireframe,
+		Action::ToggleDebug,
+		Action::ToggleCosts,
+		Action::ResetCamera,
+		Action::ToggleFreeCam,
+		Action::Sell,
+		Action::ToggleSculpt,
+		Action::ToggleCulling,
+		Action::ToggleInputLog,
+		Action::Undo,
+		Action::SaveReplay,
+		Action::Bookmark1,
+		Action::Bookmark2,
+		Action::Bookmark3,
+		Action::Bookmark4,
+		Action::FocusEnemy,
+		Action::TogglePath,
+		Action::ToggleConsole,
+		Action::ToggleTiles,
+		Action::ToggleEditor,
+	];
+
+	/// Camera bookmark actions in the order of `CameraBookmarks::slots`
+	const BOOKMARKS: [Action; bookmarks::BOOKMARK_SLOTS] = [Action::Bookmark1, Action::Bookmark2, Action::Bookmark3, Action::Bookmark4];
 }
 
 impl ActionMapper<Action> for Input {
@@ -44,29 +634,576 @@ impl ActionMapper<Action> for Input {
 	}
 }
 
+/// Click on a widget changing the game, given with `GameInput::command` and recorded with the frame.
+/// Towers are named by their cell, the entities differ from session to session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum UiCommand {
+	/// Build bar button, the next click on the terrain places the tower
+	SelectTower(TowerKind),
+	/// Button of the status bar starting the next wave early
+	StartWave,
+	TargetPriority {
+		cells: Vec<(i32, i32)>,
+		priority: TargetPriority,
+	},
+	Upgrade {
+		cells: Vec<(i32, i32)>,
+	},
+	/// Click on the minimap moving the camera target to the X,Z point
+	FocusCamera(f32, f32),
+	/// Game speed preset of the pause menu
+	GameSpeed(f32),
+	OpenSettings,
+	CloseSettings,
+	/// "No" in the quit dialog
+	CancelExit,
+	/// Restart from the pause menu or "Restart" and "Play Again" once the game is over
+	Restart,
+}
+
+/// Input the gameplay, camera and menu hotkeys read instead of `Input`, taken once a frame by `capture_input`
+/// from the live devices or a replay. Widgets changing the game go through `UiCommand`s, so they are recorded
+/// and replayed too. The main menu is not part of recorded sessions, nor are the sculpt and level editor brushes.
+struct GameInput {
+	source: Box<dyn InputSource<Action, UiCommand>>,
+	frame: InputFrame<Action, UiCommand>,
+	modifiers: dotrix::input::Modifiers,
+	/// Session being recorded and the file F12 writes it to
+	recording: Option<(String, Recording<Action, UiCommand>)>,
+	replaying: bool,
+	/// Start a new game instead of restoring the save file, set for benchmarks and by "New Game" in the menu
+	new_game: bool,
+	/// Only `Action::ToggleConsole` is taken while the console is open, the typing would move the camera
+	/// and fire the hotkeys otherwise
+	console_open: bool,
+}
+
+impl GameInput {
+	fn live() -> Self {
+		Self {
+			source: Box::new(LiveInput::default()),
+			frame: InputFrame::default(),
+			modifiers: dotrix::input::Modifiers::empty(),
+			recording: None,
+			replaying: false,
+			new_game: false,
+			console_open: false,
+		}
+	}
+
+	/// Live input recorded from the first frame, the gameplay randomness starts from `seed`
+	fn recorded(path: String, seed: u64) -> Self {
+		Self {
+			recording: Some((
+				path,
+				Recording {
+					seed,
+					frames: Vec::new(),
+				},
+			)),
+			..Self::live()
+		}
+	}
+
+	fn replay(recording: Recording<Action, UiCommand>) -> Self {
+		Self {
+			source: Box::new(ReplayInput::new(recording)),
+			replaying: true,
+			..Self::live()
+		}
+	}
+
+	/// Recorded and replayed sessions, benchmarks and "New Game" start a new game instead of restoring the save file
+	fn fresh_start(&self) -> bool {
+		self.new_game || self.replaying || self.recording.is_some()
+	}
+
+	/// Takes the input of the next frame, returns `true` on the frame a replay runs out and the live input takes over
+	fn capture(&mut self, live: &Input, gamepad: GamepadAxes, delta: f32) -> bool {
+		let mut finished = false;
+		let mut frame = match self.source.next_frame(live, gamepad, delta) {
+			Some(frame) => frame,
+			None => {
+				self.source = Box::new(LiveInput::default());
+				self.replaying = false;
+				finished = true;
+				self.source
+					.next_frame(live, gamepad, delta)
+					.unwrap_or_default()
+			}
+		};
+		if self.console_open {
+			frame
+				.actions
+				.retain(|state| state.action == Action::ToggleConsole);
+		}
+
+		let mut modifiers = dotrix::input::Modifiers::empty();
+		for (held, modifier) in [
+			(frame.modifiers.shift, dotrix::input::Modifiers::SHIFT),
+			(frame.modifiers.ctrl, dotrix::input::Modifiers::CTRL),
+			(frame.modifiers.alt, dotrix::input::Modifiers::ALT),
+		] {
+			if held {
+				modifiers |= modifier;
+			}
+		}
+		self.modifiers = modifiers;
+
+		if let Some((_, recording)) = self.recording.as_mut() {
+			recording
+				.frames
+				.push(frame.clone());
+		}
+		self.frame = frame;
+		finished
+	}
+
+	/// Gives a command on this frame, the system giving it applies `commands` once its widgets are shown.
+	/// Clicks are ignored while replaying, the recorded commands are given instead.
+	fn command(&mut self, command: UiCommand) {
+		if self.replaying {
+			return;
+		}
+
+		// The frame is recorded by `capture`, before the widgets are clicked
+		if let Some(frame) = self
+			.recording
+			.as_mut()
+			.and_then(|(_, recording)| recording.frames.last_mut())
+		{
+			frame
+				.commands
+				.push(command.clone());
+		}
+		self.frame
+			.commands
+			.push(command);
+	}
+
+	fn commands(&self) -> &[UiCommand] {
+		&self.frame.commands
+	}
+
+	/// Real duration of the frame, recorded one while replaying
+	fn delta(&self) -> f32 {
+		self.frame.delta
+	}
+
+	fn is_action_activated(&self, action: Action) -> bool {
+		self.frame
+			.state(action)
+			.map(|state| state.activated)
+			.unwrap_or(false)
+	}
+
+	fn is_action_hold(&self, action: Action) -> bool {
+		self.frame
+			.state(action)
+			.map(|state| state.hold)
+			.unwrap_or(false)
+	}
+
+	fn is_action_deactivated(&self, action: Action) -> bool {
+		self.frame
+			.state(action)
+			.map(|state| state.deactivated)
+			.unwrap_or(false)
+	}
+
+	fn mouse_position(&self) -> Option<Vec2> {
+		self.frame
+			.mouse_position
+			.map(|[x, y]| Vec2::new(x, y))
+	}
+
+	fn mouse_delta(&self) -> Vec2 {
+		let [x, y] = self.frame.mouse_delta;
+		Vec2::new(x, y)
+	}
+
+	fn mouse_scroll(&self) -> f32 {
+		self.frame.mouse_scroll
+	}
+
+	fn gamepad(&self) -> GamepadAxes {
+		self.frame.gamepad
+	}
+}
+
+/// Reads the keyboard and mouse through `Input` and the controller through `Gamepad`
+#[derive(Default)]
+struct LiveInput {
+	/// Seconds since the first frame
+	elapsed: f32,
+}
+
+impl InputSource<Action, UiCommand> for LiveInput {
+	fn next_frame(&mut self, live: &Input, gamepad: GamepadAxes, delta: f32) -> Option<InputFrame<Action, UiCommand>> {
+		self.elapsed += delta;
+
+		let actions = Action::ALL
+			.iter()
+			.map(|action| ActionState {
+				action: *action,
+				activated: live.is_action_activated(*action),
+				hold: live.is_action_hold(*action),
+				deactivated: live.is_action_deactivated(*action),
+			})
+			.filter(|state| state.activated || state.hold || state.deactivated)
+			.collect();
+		let mouse_delta = live.mouse_delta();
+
+		Some(InputFrame {
+			time: self.elapsed,
+			delta,
+			actions,
+			mouse_position: live
+				.mouse_position()
+				.map(|position| [position.x, position.y]),
+			mouse_delta: [mouse_delta.x, mouse_delta.y],
+			mouse_scroll: live.mouse_scroll(),
+			modifiers: ModifierKeys {
+				shift: live.modifiers.shift(),
+				ctrl: live.modifiers.ctrl(),
+				alt: live.modifiers.alt(),
+			},
+			gamepad,
+			commands: Vec::new(),
+		})
+	}
+}
+
+/// Input source chosen on the command line, `--record [path]` records the session and `--replay path`
+/// plays one back. Both start a new game with the seed of the recording.
+fn game_input() -> (GameInput, u64) {
+	if let Some(path) = command_line_value("--replay") {
+		let path = path.unwrap_or_else(|| String::from(replay::REPLAY_PATH));
+		match replay::load_recording::<Action, UiCommand>(&path) {
+			Ok(recording) => {
+				log::info!("Replaying {} frames from {}", recording.frames.len(), path);
+				let seed = recording.seed;
+				return (GameInput::replay(recording), seed);
+			}
+			Err(err) => log::error!("Unable to load the replay {}, playing live: {}", path, err),
+		}
+	}
+
+	if let Some(path) = command_line_value("--record") {
+		let path = path.unwrap_or_else(|| String::from(replay::REPLAY_PATH));
+		log::info!("Recording the session, F12 writes it to {}", path);
+		return (GameInput::recorded(path, rng::DEFAULT_SEED), rng::DEFAULT_SEED);
+	}
+
+	(GameInput::live(), rng::DEFAULT_SEED)
+}
+
+/// Benchmark asked for with `--bench [count]`, it keeps `count` enemies and projectiles alive
+fn benchmark() -> Option<Benchmark> {
+	command_line_value("--bench").map(|count| {
+		let count = match count.map(|count| count.parse::<usize>()) {
+			Some(Ok(count)) => count,
+			Some(Err(err)) => {
+				log::error!("Invalid benchmark count, using {}: {}", bench::DEFAULT_COUNT, err);
+				bench::DEFAULT_COUNT
+			}
+			None => bench::DEFAULT_COUNT,
+		};
+		Benchmark::new(count)
+	})
+}
+
+/// Terrain benchmark asked for with `--bench-terrain [size]`, on a terrain of `size` cells along each side
+fn terrain_benchmark(chunk_size: usize) -> Option<TerrainBenchmark> {
+	command_line_value("--bench-terrain").map(|size| {
+		let size = match size.map(|size| size.parse::<usize>()) {
+			Some(Ok(size)) => size.max(2),
+			Some(Err(err)) => {
+				log::error!("Invalid terrain benchmark size, using {}: {}", bench::DEFAULT_TERRAIN_SIZE, err);
+				bench::DEFAULT_TERRAIN_SIZE
+			}
+			None => bench::DEFAULT_TERRAIN_SIZE,
+		};
+		TerrainBenchmark::new(size, chunk_size)
+	})
+}
+
+/// Terrain of the config file resized by `--terrain WIDTHxDEPTH` and rechunked by `--chunk-size N`
+fn terrain_config(mut config: TerrainConfig) -> TerrainConfig {
+	if let Some(size) = command_line_value("--terrain") {
+		let parsed = size.as_deref().and_then(|size| {
+			let (width, depth) = size.split_once('x')?;
+			Some((width.parse::<usize>().ok()?, depth.parse::<usize>().ok()?))
+		});
+		match parsed {
+			Some((width, depth)) => {
+				config.width = width;
+				config.depth = depth;
+			}
+			None => log::error!("Terrain size must be given as WIDTHxDEPTH, keeping {}x{}", config.width, config.depth),
+		}
+	}
+
+	if let Some(chunk_size) = command_line_value("--chunk-size") {
+		match chunk_size.map(|chunk_size| chunk_size.parse::<usize>()) {
+			Some(Ok(chunk_size)) => config.chunk_size = chunk_size,
+			Some(Err(err)) => log::error!("Invalid chunk size, keeping {}: {}", config.chunk_size, err),
+			None => log::error!("Chunk size is missing after --chunk-size, keeping {}", config.chunk_size),
+		}
+	}
+
+	config.clamped()
+}
+
+/// `None` without the flag, otherwise the argument following it unless that is another flag
+fn command_line_value(flag: &str) -> Option<Option<String>> {
+	let args = std::env::args().collect::<Vec<_>>();
+	args.iter()
+		.position(|arg| arg == flag)
+		.map(|index| {
+			args.get(index + 1)
+				.filter(|value| !value.starts_with("--"))
+				.cloned()
+		})
+}
+
 fn main() {
-	Dotrix::application("Isometric TD Tech Demo")
+	let (mut game_input, seed) = game_input();
+	let benchmark = benchmark();
+	if benchmark.is_some() {
+		game_input.new_game = true;
+	}
+	let config = match config::load_config(config::CONFIG_PATH) {
+		Ok(config) => {
+			log::info!("Loaded the config from {}", config::CONFIG_PATH);
+			config
+		}
+		Err(err) if err.is_missing() => Config::default(),
+		Err(err) => {
+			log::error!("Unable to load {}, using the default window and terrain: {}", config::CONFIG_PATH, err);
+			Config::default()
+		}
+	};
+	let window_config = config.window;
+	let mut terrain_config = terrain_config(config.terrain);
+	let terrain_benchmark = terrain_benchmark(terrain_config.chunk_size);
+	if let Some(terrain_benchmark) = terrain_benchmark.as_ref() {
+		game_input.new_game = true;
+		terrain_config.width = terrain_benchmark.size;
+		terrain_config.depth = terrain_benchmark.size;
+	}
+	let terrain_grid = TerrainGrid::from_config(&terrain_config);
+	let goal = (terrain_config.width as i32 - 1, terrain_config.depth as i32 - 1);
+	let mut nav_grid = NavGrid::new(terrain_grid, (0, 0), goal);
+	match level::load_level(level::LEVEL_PATH, &terrain_grid) {
+		Ok(layout) => {
+			log::info!("Loaded the level from {}", level::LEVEL_PATH);
+			layout.apply(&mut nav_grid);
+		}
+		Err(err) if err.is_missing() => {}
+		Err(err) => log::error!("Unable to load {}, using the default level: {}", level::LEVEL_PATH, err),
+	}
+	let path = Path::from_cells(
+		&terrain_grid,
+		&nav_grid
+			.find_path(nav_grid.spawn, nav_grid.goal)
+			.unwrap_or_default(),
+	);
+
+	let app = Dotrix::application("Isometric TD Tech Demo")
+		.with(Service::from(window_config))
+		.with(Service::from(terrain_config))
+		.with(Service::from(CameraBounds::from_grid(&terrain_grid, CAMERA_BOUNDS_MARGIN)))
+		.with(Service::from(terrain_grid))
+		.with(Service::from(nav_grid))
+		.with(Service::from(path))
+		.with(Service::from(WaveSpawner::default()))
+		.with(Service::from(Economy::default()))
+		.with(Service::from(BaseHealth::default()))
+		.with(Service::from(Notifications::default()))
+		.with(Service::from(Selection::default()))
+		.with(Service::from(FrameStats::default()))
+		.with(Service::from(CullingStats::default()))
+		.with(Service::from(TimeOfDay::default()))
+		.with(Service::from(SkyBoxes::default()))
+		.with(Service::from(DebugOptions::default()))
+		.with(Service::from(InputLog::default()))
+		.with(Service::from(Settings::default()))
+		.with(Service::from(UiTheme::default()))
+		.with(Service::from(BuildState::default()))
+		.with(Service::from(SculptBrush::default()))
+		.with(Service::from(LevelBrush::default()))
+		.with(Service::from(TimeScale::default()))
+		.with(Service::from(GameClock::default()))
+		.with(Service::from(CameraBookmarks::default()))
+		.with(Service::from(CameraShake::default()))
+		.with(Service::from(ProjectilePool::default()))
+		.with(Service::from(Audio::load(&audio::SOUNDS)))
+		.with(Service::from(Gamepad::start()))
+		.with(Service::from(Rng::new(seed)))
+		.with(Service::from(game_input))
+		.with(System::from(capture_input))
+		.with(System::from(settle_camera))
 		.with(System::from(startup))
-		.with(System::from(ui_main).with(State::off::<PauseState>()))
+		.with(System::from(apply_window_config))
+		.with(System::from(load_waves))
+		.with(System::from(apply_ui_theme))
+		.with(System::from(tick_game_clock).with(State::on::<MainState>()))
+		.with(System::from(autosave).with(State::on::<MainState>()))
+		// Panels come first, the windows anchored later are placed in the space they leave
+		.with(System::from(ui_hud).with(State::on::<MainState>()))
+		.with(System::from(ui_build_bar).with(State::on::<MainState>()))
+		.with(System::from(ui_main).with(State::on::<MainState>()))
+		.with(System::from(ui_menu).with(State::on::<MenuState>()))
 		.with(System::from(ui_paused).with(State::on::<PauseState>()))
+		.with(System::from(ui_settings).with(State::on::<SettingsState>()))
+		.with(System::from(ui_notifications))
+		.with(System::from(ui_confirm_exit).with(State::on::<ConfirmExitState>()))
+		.with(System::from(ui_loading).with(State::on::<LoadingState>()))
+		.with(System::from(ui_game_over).with(State::on::<GameOverState>()))
+		.with(System::from(ui_victory).with(State::on::<VictoryState>()))
+		.with(System::from(ui_wireframe).with(State::on::<MainState>()))
+		.with(System::from(ui_debug_stats).with(State::on::<MainState>()))
+		.with(System::from(ui_input_log))
+		.with(System::from(ui_path_costs).with(State::on::<MainState>()))
+		.with(System::from(ui_tile_states).with(State::on::<MainState>()))
+		.with(System::from(ui_enemy_path).with(State::on::<MainState>()))
+		.with(System::from(ui_minimap).with(State::on::<MainState>()))
+		.with(System::from(ui_health_bars).with(State::on::<MainState>()))
+		.with(System::from(ui_sculpt).with(State::on::<MainState>()))
+		.with(System::from(ui_level_editor).with(State::on::<MainState>()))
+		.with(System::from(ui_tower_tooltip).with(State::on::<MainState>()))
+		.with(System::from(ui_box_select).with(State::on::<MainState>()))
+		.with(System::from(ui_selected_towers).with(State::on::<MainState>()))
+		.with(System::from(ui_tower_priority).with(State::on::<MainState>()))
+		.with(System::from(apply_tower_commands).with(State::on::<MainState>()))
 		.with(System::from(player_control).with(State::on::<MainState>()))
+		.with(System::from(camera_bookmarks).with(State::on::<MainState>()))
+		.with(System::from(apply_heightmap).with(State::on::<MainState>()))
+		.with(System::from(apply_cost_map).with(State::on::<MainState>()))
+		.with(System::from(pick_terrain_cell).with(State::on::<MainState>()))
+		.with(System::from(update_highlight).with(State::on::<MainState>()))
+		.with(System::from(update_range_ring).with(State::on::<MainState>()))
+		.with(System::from(update_ghost_tower).with(State::on::<MainState>()))
+		.with(System::from(place_tower).with(State::on::<MainState>()))
+		.with(System::from(remove_tower).with(State::on::<MainState>()))
+		.with(System::from(undo_build).with(State::on::<MainState>()))
+		.with(System::from(sculpt_terrain).with(State::on::<MainState>()))
+		.with(System::from(edit_level).with(State::on::<MainState>()))
+		.with(System::from(update_navigation).with(State::on::<MainState>()))
+		.with(System::from(update_tile_colors).with(State::on::<MainState>()))
+		.with(System::from(animate_path_markers).with(State::on::<MainState>()))
+		.with(System::from(move_enemies).with(State::on::<MainState>()))
+		.with(System::from(spawn_waves).with(State::on::<MainState>()))
+		.with(System::from(tower_targeting).with(State::on::<MainState>()))
+		.with(System::from(move_projectiles).with(State::on::<MainState>()))
+		.with(System::from(tick_status_effects).with(State::on::<MainState>()))
+		.with(System::from(expire_splash_rings).with(State::on::<MainState>()))
+		.with(System::from(interpolate_motion).with(State::on::<MainState>()))
+		.with(System::from(load_control).with(State::on::<MainState>()))
+		.with(System::from(load_settings).with(State::on::<MainState>()))
+		.with(System::from(save_control).with(State::on::<MainState>()))
+		.with(System::from(day_night_cycle).with(State::on::<MainState>()))
+		.with(System::from(cycle_sky).with(State::on::<MainState>()))
 		.with(System::from(global_control))
+		.with(System::from(debug_control).with(State::on::<MainState>()))
+		.with(System::from(record_input))
+		.with(System::from(save_replay))
+		.with(System::from(shake_camera))
+		.with(System::from(cull_terrain_chunks).with(State::on::<MainState>()))
+		.with(System::from(update_frame_stats))
+		.with(System::from(limit_frame_rate))
 		.with(overlay::extension)
 		.with(egui::extension)
 		.with(skybox::extension)
-		.with(pbr::extension)
-		.run();
+		.with(pbr::extension);
+
+	// Kept out of the app unless asked for, so the normal game never runs the benchmark
+	let app = match benchmark {
+		Some(benchmark) => app
+			.with(Service::from(benchmark))
+			.with(System::from(run_benchmark).with(State::on::<MainState>())),
+		None => app,
+	};
+	let app = match terrain_benchmark {
+		Some(terrain_benchmark) => app
+			.with(Service::from(terrain_benchmark))
+			.with(System::from(run_terrain_benchmark).with(State::on::<MainState>())),
+		None => app,
+	};
+
+	#[cfg(feature = "dev")]
+	let app = app
+		.with(Service::from(hot_reload::AssetWatcher::start()))
+		.with(Service::from(console::Console::default()))
+		.with(System::from(hot_reload::reload_textures))
+		.with(System::from(ui_console).with(State::on::<MainState>()));
+
+	app.run();
 }
 
-fn startup(mut assets: Mut<Assets>, mut input: Mut<Input>, mut state: Mut<State>, mut world: Mut<World>, mut window: Mut<Window>, mut camera: Mut<Camera>) {
-	window.set_cursor_grab(true);
-	camera.target.y = -8.5;
-	camera.xz_angle = 1.2;
+#[allow(clippy::too_many_arguments)]
+fn startup(
+	mut assets: Mut<Assets>,
+	mut input: Mut<Input>,
+	mut state: Mut<State>,
+	mut world: Mut<World>,
+	mut window: Mut<Window>,
+	mut camera: Mut<Camera>,
+	mut skyboxes: Mut<SkyBoxes>,
+	mut settings: Mut<Settings>,
+	mut bookmarks: Mut<CameraBookmarks>,
+	mut rng: Mut<Rng>,
+	game_input: Const<GameInput>,
+	terrain_config: Const<TerrainConfig>,
+) {
+	let (x, y, z) = DEFAULT_CAMERA_TARGET;
+	camera.target = Point3::new(x, y, z);
+	camera.xz_angle = DEFAULT_CAMERA_XZ_ANGLE;
 
 	init_input(&mut input);
-	init_skybox(&mut assets, &mut world);
-	init_terrain(&mut assets, &mut world, &mut state);
-	init_lights(&mut world);
+	init_tower_meshes(&mut assets);
+	init_enemy_meshes(&mut assets);
+	assets.store_as(mesh_gen::box_mesh([0.1, 0.1, 0.1]), towers::PROJECTILE_MESH);
+	assets.store_as(mesh_gen::ring(1.0, RANGE_RING_SEGMENTS), towers::SPLASH_RING_MESH);
+
+	// Recorded, replayed and benchmark sessions start right away, clicks on the menu are not part of their input
+	if game_input.fresh_start() {
+		window.set_cursor_grab(true);
+		start_game(&mut assets, &mut world, &mut state, &mut skyboxes, &terrain_config);
+	} else {
+		restore_settings(&mut settings, &mut bookmarks, &mut rng);
+		window.set_cursor_grab(false);
+		state.push(MenuState {
+			name: String::from("Menu State"),
+		});
+	}
+}
+
+/// Spawns the sky, terrain, lights and path markers and pushes the main state with the loading state over it,
+/// the menu is shown over an empty scene
+fn start_game(assets: &mut Assets, world: &mut World, state: &mut State, skyboxes: &mut SkyBoxes, terrain_config: &TerrainConfig) {
+	init_skybox(assets, world, skyboxes);
+	init_terrain(assets, world, state, terrain_config);
+	init_lights(world);
+	init_path_markers(assets, world);
+
+	let main_state = state
+		.get::<MainState>()
+		.expect("Unable to get main state");
+	let mut pending = vec![main_state.texture];
+	pending.extend(main_state.heightmap);
+	pending.extend(main_state.cost_map);
+	for set in skyboxes.sets.iter() {
+		pending.extend_from_slice(set.textures());
+	}
+
+	state.push(LoadingState {
+		name: String::from("Loading State"),
+		total: pending.len(),
+		pending,
+		elapsed: 0.0,
+	});
 }
 
 fn init_input(input: &mut Input) {
@@ -80,10 +1217,36 @@ fn init_input(input: &mut Input) {
 			(Action::PanDown, Button::Key(KeyCode::S)),
 			(Action::PanLeft, Button::Key(KeyCode::A)),
 			(Action::PanRight, Button::Key(KeyCode::D)),
+			(Action::PlaceTower, Button::MouseLeft),
+			(Action::Save, Button::Key(KeyCode::F5)),
+			(Action::Load, Button::Key(KeyCode::F9)),
+			(Action::RotateLeft, Button::Key(KeyCode::Q)),
+			(Action::RotateRight, Button::Key(KeyCode::E)),
+			(Action::CycleSky, Button::Key(KeyCode::F2)),
+			(Action::ToggleWireframe, Button::Key(KeyCode::F3)),
+			(Action::ToggleDebug, Button::Key(KeyCode::F4)),
+			(Action::ToggleCosts, Button::Key(KeyCode::F6)),
+			(Action::ResetCamera, Button::Key(KeyCode::Home)),
+			(Action::ToggleFreeCam, Button::Key(KeyCode::F7)),
+			(Action::Sell, Button::MouseRight),
+			(Action::ToggleSculpt, Button::Key(KeyCode::F8)),
+			(Action::ToggleCulling, Button::Key(KeyCode::F10)),
+			(Action::ToggleInputLog, Button::Key(KeyCode::F11)),
+			(Action::Undo, Button::Key(KeyCode::Z)),
+			(Action::SaveReplay, Button::Key(KeyCode::F12)),
+			(Action::Bookmark1, Button::Key(KeyCode::Key1)),
+			(Action::Bookmark2, Button::Key(KeyCode::Key2)),
+			(Action::Bookmark3, Button::Key(KeyCode::Key3)),
+			(Action::Bookmark4, Button::Key(KeyCode::Key4)),
+			(Action::FocusEnemy, Button::Key(KeyCode::F)),
+			(Action::TogglePath, Button::Key(KeyCode::P)),
+			(Action::ToggleConsole, Button::Key(KeyCode::Grave)),
+			(Action::ToggleTiles, Button::Key(KeyCode::T)),
+			(Action::ToggleEditor, Button::Key(KeyCode::F1)),
 		]);
 }
 
-fn init_terrain(assets: &mut Assets, world: &mut World, state: &mut State) {
+fn init_terrain(assets: &mut Assets, world: &mut World, state: &mut State, config: &TerrainConfig) {
 	// Generate terrain mesh like this:
 	//   0   1
 	// 0 +---+---+---> x
@@ -94,201 +1257,3792 @@ fn init_terrain(assets: &mut Assets, world: &mut World, state: &mut State) {
 	//   |
 	//   z
 
-	let size = 5;
-	let mut positions = Vec::with_capacity(3 * 2 * size * size);
-	let mut uvs = Vec::new();
-	for x in 0..size {
-		let x0 = x as f32;
-		let x1 = x0 + 1.0;
-		for z in 0..size {
-			let z0 = z as f32;
-			let z1 = z0 + 1.0;
-			// Add vertices
-			positions.push([x0, 0.0, z0]);
-			positions.push([x0, 0.0, z1]);
-			positions.push([x1, 0.0, z0]);
-			positions.push([x1, 0.0, z0]);
-			positions.push([x0, 0.0, z1]);
-			positions.push([x1, 0.0, z1]);
-			// Add texture vertices
-			uvs.push([0.0, 0.0]);
-			uvs.push([0.0, 1.0]);
-			uvs.push([1.0, 0.0]);
-			uvs.push([1.0, 0.0]);
-			uvs.push([0.0, 1.0]);
-			uvs.push([1.0, 1.0]);
-		}
-	}
-
-	let normals = Mesh::calculate_normals(&positions, None);
-
-	let mut mesh = Mesh::default();
-
-	mesh.with_vertices(&positions);
-	mesh.with_vertices(&normals);
-	mesh.with_vertices(&uvs);
-
-	// Store mesh and get its ID
-	let mesh = assets.store_as(mesh, "terrain");
+	let (positions, uvs) = mesh_gen::grid_plane_vertices(config.width, config.depth, config.tile_scale);
+
+	let colors = vec![terrain::TILE_WHITE; positions.len()];
+
+	// Heightmap and cost map are imported asynchronously, `apply_heightmap` displaces the mesh once the
+	// heightmap is loaded and `apply_cost_map` sets the cell costs once the cost map is
+	let import = |assets: &mut Assets, path: &str| {
+		assets.import(path);
+		let name = std::path::Path::new(path)
+			.file_stem()
+			.and_then(|stem| stem.to_str())
+			.unwrap_or_default();
+		assets.register(name)
+	};
+	let heightmap = config
+		.heightmap
+		.as_ref()
+		.map(|path| import(assets, path));
+	let cost_map = config
+		.cost_map
+		.as_ref()
+		.map(|path| import(assets, path));
 
 	// import terrain texture and get its ID
 	assets.import("assets/terrain.png");
 	let texture = assets.register("terrain");
 
-	// Center terrain tile at coordinate system center (0.0, 0.0, 0.0) by moving the tile on a
-	// half of its size by X and Z axis
-	let shift = (size / 2) as f32;
+	let highlight = assets.store_as(mesh_gen::grid_plane(1, 1, config.tile_scale), "highlight");
+	if let Some((model, material, mut transform, pipeline)) = (pbr::solid::Entity {
+		mesh: highlight,
+		albedo: Color::rgba(0.2, 0.8, 1.0, 0.5),
+		..Default::default()
+	})
+	.some()
+	{
+		// Hidden until a cell is selected
+		transform.scale = Vec3::new(0.0, 0.0, 0.0);
+		world.spawn(Some((model, material, transform, pipeline, Highlight {})));
+	}
 
-	world.spawn(
-		(pbr::solid::Entity {
-			mesh,
-			texture,
-			translate: Vec3::new(-shift, 0.0, -shift),
-			..Default::default()
-		})
-		.some(),
-	);
+	let range_ring = assets.store_as(mesh_gen::ring(1.0, RANGE_RING_SEGMENTS), "range_ring");
+	let [r, g, b, a] = RANGE_RING_COLOR;
+	if let Some((model, material, mut transform, pipeline)) = (pbr::solid::Entity {
+		mesh: range_ring,
+		albedo: Color::rgba(r, g, b, a),
+		..Default::default()
+	})
+	.some()
+	{
+		// Hidden until a cell with a tower in range is selected, see `update_range_ring`
+		transform.scale = Vec3::new(0.0, 0.0, 0.0);
+		world.spawn(Some((
+			model,
+			material,
+			transform,
+			pipeline,
+			RangeRing {
+				mesh: range_ring,
+				range: 1.0,
+			},
+		)));
+	}
 
-	state.push(MainState {
+	let mut main_state = MainState {
 		name: String::from("Main State"),
-		positions: positions,
-	});
+		positions,
+		uvs,
+		colors,
+		chunks: Vec::new(),
+		texture,
+		heightmap,
+		cost_map,
+		last_autosave: None,
+	};
+	spawn_chunks(assets, world, &mut main_state, &TerrainGrid::from_config(config), config.chunk_size);
+	state.push(main_state);
 }
 
-fn init_lights(world: &mut World) {
-	// spawn source of white light at (0.0, 100.0, 0.0)
-	world.spawn(Some((Light::Simple {
-		// direction: Vec3::new(0.3, -0.5, -0.6),
-		position: Vec3::new(0.0, 1000.0, 0.0),
-		color: Color::white(),
-		intensity: 0.5,
-		enabled: true,
-	},)));
-	// spawn source of white light at (0.0, 100.0, 0.0)
-	world.spawn(Some((Light::Ambient {
-		color: Color::white(),
-		intensity: 0.5,
-	},)));
-}
-
-fn init_skybox(assets: &mut Assets, world: &mut World) {
-	let asset_list = &[
-		"assets/skybox_right.png",
-		"assets/skybox_left.png",
-		"assets/skybox_top.png",
-		"assets/skybox_bottom.png",
-		"assets/skybox_back.png",
-		"assets/skybox_front.png",
-	];
+fn apply_heightmap(mut state: Mut<State>, mut assets: Mut<Assets>, nav_grid: Const<NavGrid>, config: Const<TerrainConfig>) {
+	let main_state = state
+		.get_mut::<MainState>()
+		.expect("Unable to get main state");
 
-	asset_list
-		.into_iter()
-		.for_each(|asset| {
-			assets.import(asset);
-		});
-	world.spawn(Some((
-		SkyBox {
-			view_range: 500.0,
-			..Default::default()
-		},
-		CubeMap {
-			right: assets.register("skybox_right"),
-			left: assets.register("skybox_left"),
-			top: assets.register("skybox_top"),
-			bottom: assets.register("skybox_bottom"),
-			back: assets.register("skybox_back"),
-			front: assets.register("skybox_front"),
-			..Default::default()
-		},
-		Pipeline::default(),
-	)));
-}
+	let texture_id = match main_state.heightmap {
+		Some(id) => id,
+		None => return,
+	};
+
+	let heightmap = match assets.get(texture_id) {
+		Some(texture) => Heightmap::from_texture(texture, config.width + 1, config.depth + 1),
+		None => return,
+	};
+	main_state.heightmap = None;
+
+	let heightmap = match heightmap {
+		Some(heightmap) => heightmap,
+		None => {
+			log::warn!(
+				"Heightmap must be {}x{} pixels for terrain size {}x{}, keeping the flat plane",
+				config.width + 1,
+				config.depth + 1,
+				config.width,
+				config.depth
+			);
+			return;
+		}
+	};
+
+	heightmap.displace(&mut main_state.positions, config.tile_scale, config.height_scale);
+	update_terrain_normals(&mut assets, main_state, &nav_grid.grid);
+}
+
+/// Sets the cell costs from the roughness of the cost map once it is loaded, the cells cost
+/// `pathfinding::BASE_COST` without one
+fn apply_cost_map(mut state: Mut<State>, mut nav_grid: Mut<NavGrid>, assets: Const<Assets>, config: Const<TerrainConfig>) {
+	let main_state = state
+		.get_mut::<MainState>()
+		.expect("Unable to get main state");
+
+	let texture_id = match main_state.cost_map {
+		Some(id) => id,
+		None => return,
+	};
+
+	let cost_map = match assets.get(texture_id) {
+		Some(texture) => CostMap::from_texture(texture, config.width + 1, config.depth + 1),
+		None => return,
+	};
+	main_state.cost_map = None;
+
+	match cost_map {
+		Some(cost_map) => nav_grid.set_costs(
+			cost_map
+				.cell_roughness()
+				.into_iter()
+				.map(pathfinding::cost_from_roughness)
+				.collect(),
+		),
+		None => log::warn!(
+			"Cost map must be {}x{} pixels for terrain size {}x{}, keeping the base cost",
+			config.width + 1,
+			config.depth + 1,
+			config.width,
+			config.depth
+		),
+	}
+}
+
+/// In the sculpt mode the left mouse button raises the terrain under the cursor and the right one lowers it
+fn sculpt_terrain(
+	mut state: Mut<State>,
+	mut assets: Mut<Assets>,
+	brush: Const<SculptBrush>,
+	build: Const<BuildState>,
+	selection: Const<Selection>,
+	grid: Const<TerrainGrid>,
+	input: Const<GameInput>,
+) {
+	if !build.sculpt {
+		return;
+	}
+
+	// Tower actions keep their mouse buttons, `place_tower` and `remove_tower` ignore them while sculpting
+	let direction = if input.is_action_hold(Action::PlaceTower) {
+		1.0
+	} else if input.is_action_hold(Action::Sell) {
+		-1.0
+	} else {
+		return;
+	};
+
+	// Brush centers off the terrain are ignored, a brush partly over the edge only moves the terrain vertices
+	let point = match (selection.point, selection.cell) {
+		(Some(point), Some(_)) => point,
+		_ => return,
+	};
+
+	let main_state = state
+		.get_mut::<MainState>()
+		.expect("Unable to get main state");
+
+	let amount = direction * brush.strength * input.delta();
+	if brush.apply(&mut main_state.positions, grid.origin, point, amount) {
+		let (min, max) = grid.cell_bounds(point, brush.radius);
+		update_terrain_chunks(&mut assets, main_state, &grid, |chunk| chunk.overlaps(min, max));
+	}
+}
+
+/// In the level editor the left mouse button paints the selected `LevelBrush` on the cell under the cursor,
+/// `update_navigation` reroutes the enemies once the level changes
+fn edit_level(
+	mut nav_grid: Mut<NavGrid>,
+	brush: Const<LevelBrush>,
+	build: Const<BuildState>,
+	selection: Const<Selection>,
+	input: Const<GameInput>,
+) {
+	if !build.level_editor || !input.is_action_hold(Action::PlaceTower) {
+		return;
+	}
+
+	let cell = match selection.cell {
+		Some(cell) => cell,
+		None => return,
+	};
+
+	// Edits that would take the path away from the enemies are refused, the brush is held over many cells
+	match *brush {
+		LevelBrush::Spawn => nav_grid.move_spawn(cell),
+		LevelBrush::Goal => nav_grid.move_goal(cell),
+		LevelBrush::Wall => nav_grid.add_wall(cell),
+		LevelBrush::Eraser => nav_grid.remove_wall(cell),
+	};
+}
+
+/// Rebuilds the terrain meshes with normals recalculated from the current positions by `mesh_gen::flat_normals`, must be called
+/// whenever the positions change or the slopes keep the lighting of their previous shape
+fn update_terrain_normals(assets: &mut Assets, main_state: &mut MainState, grid: &TerrainGrid) {
+	update_terrain_chunks(assets, main_state, grid, |_| true);
+}
+
+/// Splits the terrain of the main state into chunks of `chunk_size` tiles and spawns them, the entities of
+/// the chunks it had before are exiled. Every chunk is an entity of its own, so a change only reuploads the
+/// chunks it touches. The grid origin centers the terrain at coordinate system center (0.0, 0.0, 0.0).
+fn spawn_chunks(assets: &mut Assets, world: &mut World, main_state: &mut MainState, grid: &TerrainGrid, chunk_size: usize) {
+	let spawned = world
+		.query::<(&Entity, &Terrain)>()
+		.map(|(entity, _)| *entity)
+		.collect::<Vec<_>>();
+	for entity in spawned {
+		world.exile(entity);
+	}
+
+	main_state.chunks = grid
+		.chunks(chunk_size)
+		.into_iter()
+		.enumerate()
+		.map(|(index, chunk)| {
+			let mesh = chunk.build_mesh(grid, &main_state.positions, &main_state.uvs);
+			let mesh = assets.store_as(mesh, &format!("terrain_chunk_{}", index));
+
+			// The standard PBR pipeline does not read vertex colors, so every tint is a mesh of its own
+			let tints = TILE_TINTS
+				.iter()
+				.enumerate()
+				.map(|(tint_index, tint)| {
+					let tint_mesh = chunk.build_tint_mesh(grid, &main_state.positions, &main_state.colors, *tint, TINT_OFFSET);
+					assets.store_as(tint_mesh, &format!("terrain_tint_{}_{}", index, tint_index))
+				})
+				.collect();
+
+			let bounds = chunk.bounds(grid, &main_state.positions);
+			let chunk_mesh = ChunkMesh {
+				chunk,
+				mesh,
+				tints,
+				bounds,
+				spawned: true,
+			};
+			spawn_chunk(world, &chunk_mesh, index, main_state.texture, grid.origin);
+			chunk_mesh
+		})
+		.collect();
+}
+
+/// Spawns the entity of the chunk mesh and those of its tints at `translate`, the origin of the terrain grid
+fn spawn_chunk(world: &mut World, chunk_mesh: &ChunkMesh, index: usize, texture: Id<Texture>, translate: Vec3) {
+	if let Some((model, material, transform, pipeline)) = (pbr::solid::Entity {
+		mesh: chunk_mesh.mesh,
+		texture,
+		translate,
+		..Default::default()
+	})
+	.some()
+	{
+		world.spawn(Some((
+			model,
+			material,
+			transform,
+			pipeline,
+			Terrain {
+				chunk: index,
+			},
+		)));
+	}
+
+	for (tint, tint_mesh) in TILE_TINTS
+		.iter()
+		.zip(chunk_mesh.tints.iter())
+	{
+		let [r, g, b, a] = *tint;
+		if let Some((model, material, transform, pipeline)) = (pbr::solid::Entity {
+			mesh: *tint_mesh,
+			albedo: Color::rgba(r, g, b, a),
+			translate,
+			..Default::default()
+		})
+		.some()
+		{
+			world.spawn(Some((
+				model,
+				material,
+				transform,
+				pipeline,
+				Terrain {
+					chunk: index,
+				},
+			)));
+		}
+	}
+}
+
+/// Rebuilds the meshes and the tint meshes of the chunks `dirty` returns `true` for
+fn update_terrain_chunks(assets: &mut Assets, main_state: &mut MainState, grid: &TerrainGrid, dirty: impl Fn(&TerrainChunk) -> bool) {
+	for chunk_mesh in main_state.chunks.iter_mut() {
+		let chunk = &chunk_mesh.chunk;
+		if !dirty(chunk) {
+			continue;
+		}
+		if let Some(mesh) = assets.get_mut(chunk_mesh.mesh) {
+			*mesh = chunk.build_mesh(grid, &main_state.positions, &main_state.uvs);
+		}
+		for (tint, tint_mesh) in TILE_TINTS
+			.iter()
+			.zip(chunk_mesh.tints.iter())
+		{
+			if let Some(mesh) = assets.get_mut(*tint_mesh) {
+				*mesh = chunk.build_tint_mesh(grid, &main_state.positions, &main_state.colors, *tint, TINT_OFFSET);
+			}
+		}
+		chunk_mesh.bounds = chunk.bounds(grid, &main_state.positions);
+	}
+}
+
+/// Stores a mesh for every tower kind and level, upgraded towers are taller
+fn init_tower_meshes(assets: &mut Assets) {
+	for kind in TowerKind::ALL {
+		for level in 1..=towers::MAX_LEVEL {
+			let tower = Tower {
+				level,
+				..Tower::new(kind, (0, 0))
+			};
+			assets.store_as(mesh_gen::box_mesh(tower.dimensions()), &tower.mesh_name());
+		}
+	}
+}
+
+fn init_enemy_meshes(assets: &mut Assets) {
+	for kind in EnemyKind::ALL {
+		assets.store_as(mesh_gen::box_mesh(kind.dimensions()), kind.mesh_name());
+	}
+}
+
+fn init_path_markers(assets: &mut Assets, world: &mut World) {
+	let mesh = assets.store_as(
+		mesh_gen::cylinder(PATH_MARKER_RADIUS, PATH_MARKER_HEIGHT, PATH_MARKER_SEGMENTS),
+		enemies::PATH_MARKER_MESH,
+	);
+
+	for (marker, albedo) in [(PathMarker::Spawn, Color::rgb(1.0, 0.3, 0.8)), (PathMarker::Goal, Color::rgb(0.3, 1.0, 1.0))] {
+		if let Some((model, material, mut transform, pipeline)) = (pbr::solid::Entity {
+			mesh,
+			albedo,
+			..Default::default()
+		})
+		.some()
+		{
+			// Hidden until `update_navigation` finds the path
+			transform.scale = Vec3::new(0.0, 0.0, 0.0);
+			world.spawn(Some((model, material, transform, pipeline, marker)));
+		}
+	}
+}
+
+fn init_lights(world: &mut World) {
+	// spawn source of white light at (0.0, 100.0, 0.0)
+	world.spawn(Some((
+		Light::Simple {
+			// direction: Vec3::new(0.3, -0.5, -0.6),
+			position: Vec3::new(0.0, 1000.0, 0.0),
+			color: Color::white(),
+			intensity: 0.5,
+			enabled: true,
+		},
+		LightSwitch::new("Sun"),
+	)));
+	// spawn source of white light at (0.0, 100.0, 0.0)
+	world.spawn(Some((
+		Light::Ambient {
+			color: Color::white(),
+			intensity: 0.5,
+		},
+		LightSwitch::new("Ambient"),
+	)));
+}
+
+fn init_skybox(assets: &mut Assets, world: &mut World, skyboxes: &mut SkyBoxes) {
+	// Preload every set so switching is instant
+	skyboxes.sets = skyboxes::SKYBOX_SETS
+		.iter()
+		.filter_map(|prefix| SkyBoxSet::load(assets, prefix))
+		.collect();
+	skyboxes.current = 0;
+
+	let cubemap = match skyboxes.sets.first() {
+		Some(set) => set.cubemap(),
+		None => {
+			log::error!("No skybox set could be loaded");
+			return;
+		}
+	};
+
+	world.spawn(Some((
+		SkyBox {
+			view_range: 500.0,
+			..Default::default()
+		},
+		cubemap,
+		Pipeline::default(),
+	)));
+}
+
+fn cycle_sky(mut world: Mut<World>, mut skyboxes: Mut<SkyBoxes>, input: Const<GameInput>) {
+	if !input.is_action_activated(Action::CycleSky) {
+		return;
+	}
+
+	if let Some(set) = skyboxes.cycle() {
+		// Replacing the whole component drops the loaded cube texture, so the new faces are uploaded
+		for (cubemap,) in world.query::<(&mut CubeMap,)>() {
+			*cubemap = set.cubemap();
+		}
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn player_control(
+	mut world: Mut<World>,
+	mut motion: Context<CameraMotion>,
+	input: Const<GameInput>,
+	window: Const<Window>,
+	settings: Const<Settings>,
+	grid: Const<TerrainGrid>,
+	bounds: Const<CameraBounds>,
+	mut camera: Mut<Camera>,
+	mut notifications: Mut<Notifications>,
+) {
+	let delta = input.delta();
+
+	if input.is_action_activated(Action::ResetCamera) {
+		motion.start_reset(&camera);
+	}
+	if motion.reset.is_some() {
+		motion.step_reset(&mut camera, settings.camera_angle, delta);
+		return;
+	}
+
+	let toggle_free_cam = input.is_action_activated(Action::ToggleFreeCam);
+	match motion.mode {
+		CameraMode::Isometric if toggle_free_cam && window.cursor_grab() => {
+			motion.velocity = Vec3::new(0.0, 0.0, 0.0);
+			motion.focus = None;
+			motion.mode = CameraMode::Free {
+				target: camera.target,
+				y_angle: camera.y_angle,
+				xz_angle: camera.xz_angle,
+			};
+		}
+		CameraMode::Free {
+			target,
+			y_angle,
+			xz_angle,
+		} if toggle_free_cam || !window.cursor_grab() => {
+			camera.target = target;
+			camera.y_angle = y_angle;
+			camera.xz_angle = xz_angle;
+			motion.mode = CameraMode::Isometric;
+		}
+		_ => {}
+	}
+
+	if let CameraMode::Free { .. } = motion.mode {
+		free_camera_control(&input, &mut camera, settings.mouse_sensitivity, delta);
+		return;
+	}
+
+	// Camera orbits its target, so the terrain at the screen center stays in place and panning,
+	// which follows the yaw only, stays aligned with the screen
+	if motion.angle != Some(settings.camera_angle) {
+		motion.angle = Some(settings.camera_angle);
+		camera.xz_angle = settings.camera_angle;
+	}
+
+	// Scaled edge panning of zero is the same as no edge input, `CameraMotion::update` damps the velocity then
+	let (edge_x, edge_z) = if window.cursor_grab() {
+		let (x, z) = edge_pan_direction(&input, &window);
+		(x * settings.edge_pan_sensitivity, z * settings.edge_pan_sensitivity)
+	} else {
+		(0.0, 0.0)
+	};
 
-fn player_control(mut world: Mut<World>, input: Const<Input>, frame: Const<Frame>, mut camera: Mut<Camera>) {
 	let dz = if input.is_action_hold(Action::PanUp) {
-		-(PAN_SPEED * frame.delta().as_secs_f32())
+		-1.0
 	} else if input.is_action_hold(Action::PanDown) {
-		PAN_SPEED * frame.delta().as_secs_f32()
+		1.0
 	} else {
 		0.0
 	};
 
-	let dx = if input.is_action_hold(Action::PanRight) {
-		PAN_SPEED * frame.delta().as_secs_f32()
-	} else if input.is_action_hold(Action::PanLeft) {
-		-(PAN_SPEED * frame.delta().as_secs_f32())
-	} else {
-		0.0
-	};
+	let dx = if input.is_action_hold(Action::PanRight) {
+		1.0
+	} else if input.is_action_hold(Action::PanLeft) {
+		-1.0
+	} else {
+		0.0
+	};
+
+	// Left stick pans in proportion to how far it is pushed, the keyboard and the screen edges take over
+	// while it rests or without a gamepad
+	let gamepad = input.gamepad();
+	let (pan_x, pan_z) = if gamepad.is_panning() {
+		let [x, z] = gamepad.pan;
+		(x, z)
+	} else {
+		(dx + edge_x, dz + edge_z)
+	};
+
+	// Panning takes the camera back from the focused enemy
+	if pan_x != 0.0 || pan_z != 0.0 {
+		motion.focus = None;
+	}
+	if input.is_action_activated(Action::FocusEnemy) {
+		motion.focus = next_enemy(&world, motion.focus);
+		if motion.focus.is_none() {
+			notifications.notify("No enemies to focus");
+		}
+	}
+
+	motion.update(Vec3::new(pan_x, 0.0, pan_z), settings.pan_speed, delta);
+
+	if input.is_action_hold(Action::RotateLeft) {
+		camera.y_angle -= ROTATE_SPEED * delta;
+	} else if input.is_action_hold(Action::RotateRight) {
+		camera.y_angle += ROTATE_SPEED * delta;
+	}
+
+	let dy = if input.mouse_scroll() > 0.0 {
+		settings.scroll_speed * delta
+	} else if input.mouse_scroll() < 0.0 {
+		-(settings.scroll_speed * delta)
+	} else {
+		// Right stick and triggers zoom as fast as the wheel when pushed all the way
+		settings.scroll_speed * gamepad.zoom * delta
+	};
+
+	// Clamp the target height instead of the delta, so the camera rests exactly on the limit
+	// while the wheel keeps scrolling into it
+	let (min_y, max_y) = zoom_limits(&camera);
+
+	// Velocity is in screen space, X to the right and Z down
+	let (right, forward) = ground_axes(&camera);
+	let pan = (right * motion.velocity.x - forward * motion.velocity.z) * delta;
+
+	let mut pos_x = camera.target.x + pan.x;
+	let mut pos_z = camera.target.z + pan.z;
+	let pos_y = (camera.target.y - dy).clamp(min_y, max_y);
+
+	// Focus moves on to the next enemy once the focused one is gone, and is cleared when none are left
+	let focused = |world: &World, focus: Entity| {
+		world
+			.query::<(&Entity, &Enemy, &Transform)>()
+			.find(|(entity, enemy, _)| **entity == focus && enemy.health > 0.0)
+			.map(|(_, _, transform)| transform.translate)
+	};
+	let followed = match motion.focus {
+		Some(focus) => match focused(&world, focus) {
+			Some(position) => Some(position),
+			None => {
+				motion.focus = next_enemy(&world, Some(focus));
+				motion
+					.focus
+					.and_then(|focus| focused(&world, focus))
+			}
+		},
+		None => None,
+	};
+	if let Some(position) = followed {
+		// Exponential approach keeps the glide the same at any frame rate
+		let t = 1.0 - (-FOCUS_FOLLOW_RATE * delta).exp();
+		pos_x += (position.x - pos_x) * t;
+		pos_z += (position.z - pos_z) * t;
+	}
+
+	let viewport = window_viewport(&window);
+	let cursor_point = |camera: &Camera| {
+		input
+			.mouse_position()
+			.zip(viewport)
+			.and_then(|(cursor, viewport)| Ray::from_cursor(camera, &cursor, viewport).intersect_plane_y(TERRAIN_BASE_HEIGHT))
+	};
+
+	// Zoom towards the terrain point under the cursor by shifting the target, so that point stays
+	// under the cursor. Off the terrain the camera zooms towards the screen center.
+	let anchor = if pos_y != camera.target.y {
+		camera.target = Point3::new(pos_x, camera.target.y, pos_z);
+		cursor_point(&camera).filter(|point| grid.world_to_cell(*point).is_some())
+	} else {
+		None
+	};
+
+	camera.target = Point3::new(pos_x, pos_y, pos_z);
+
+	if let Some((anchor, zoomed)) = anchor.and_then(|anchor| cursor_point(&camera).map(|zoomed| (anchor, zoomed))) {
+		camera.target.x += anchor.x - zoomed.x;
+		camera.target.z += anchor.z - zoomed.z;
+	}
+
+	// The ground point at the screen center is kept in bounds rather than the target, which lies beside it
+	// depending on the pitch and yaw, so the bounds stay the same on the screen however the camera is turned
+	if settings.camera_bounds {
+		let center = viewport.and_then(|(width, height)| {
+			Ray::from_cursor(&camera, &Vec2::new(width / 2.0, height / 2.0), (width, height)).intersect_plane_y(TERRAIN_BASE_HEIGHT)
+		});
+		let (x, z) = match center {
+			Some(center) => (center.x, center.z),
+			None => (camera.target.x, camera.target.z),
+		};
+		let (clamped_x, clamped_z) = bounds.clamp(x, z);
+		camera.target.x += clamped_x - x;
+		camera.target.z += clamped_z - z;
+	}
+}
+
+/// Living enemy after `current` in the order of the world, wrapping around to the first one. Starts from
+/// the first enemy if `current` is `None` or gone, `None` if no enemy is alive.
+fn next_enemy(world: &World, current: Option<Entity>) -> Option<Entity> {
+	let living = world
+		.query::<(&Entity, &Enemy)>()
+		.filter(|(_, enemy)| enemy.health > 0.0)
+		.map(|(entity, _)| *entity)
+		.collect::<Vec<_>>();
+
+	let next = current
+		.and_then(|current| living.iter().position(|entity| *entity == current))
+		.map(|index| (index + 1) % living.len())
+		.unwrap_or(0);
+	living.get(next).copied()
+}
+
+/// SHIFT and a number key save the camera view, the number key alone jumps back to it
+fn camera_bookmarks(mut bookmarks: Mut<CameraBookmarks>, mut camera: Mut<Camera>, mut notifications: Mut<Notifications>, input: Const<GameInput>) {
+	let slot = match Action::BOOKMARKS
+		.iter()
+		.position(|action| input.is_action_activated(*action))
+	{
+		Some(slot) => slot,
+		None => return,
+	};
+
+	if input.modifiers == dotrix::input::Modifiers::SHIFT {
+		bookmarks.slots[slot] = Some(CameraBookmark::from_camera(&camera));
+		notifications.notify(format!("Camera bookmark {} saved", slot + 1));
+	} else if let Some(bookmark) = bookmarks.slots[slot] {
+		bookmark.apply(&mut camera);
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn place_tower(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut economy: Mut<Economy>,
+	mut notifications: Mut<Notifications>,
+	mut build: Mut<BuildState>,
+	input: Const<GameInput>,
+	nav_grid: Const<NavGrid>,
+	selection: Const<Selection>,
+) {
+	let shift = input.modifiers == dotrix::input::Modifiers::SHIFT;
+	if build.repeating && !shift {
+		build.placing = false;
+		build.repeating = false;
+	}
+
+	// Towers are placed on release, so the press may start a box selection instead
+	if build.editing() || !build.placing || !input.is_action_deactivated(Action::PlaceTower) {
+		return;
+	}
+
+	let dragged = match selection.drag {
+		Some(drag) => drag.is_box(),
+		None => false,
+	};
+	if dragged {
+		return;
+	}
+
+	let cell = match selection.cell {
+		Some(cell) => cell,
+		None => return,
+	};
+
+	let occupied = world
+		.query::<(&Tower,)>()
+		.any(|(tower,)| tower.cell == cell);
+	if occupied {
+		return;
+	}
+
+	if !nav_grid.can_block(cell) {
+		notifications.notify(String::from("This would block the path"));
+		return;
+	}
+
+	let kind = build.selected_kind;
+	if !economy.spend(kind.cost()) {
+		notifications.notify(format!("Not enough gold: {} needed", kind.cost()));
+		build.placing = false;
+		build.repeating = false;
+		return;
+	}
+
+	economy.towers_built += 1;
+	spawn_tower(&mut world, &mut assets, &nav_grid.grid, Tower::new(kind, cell));
+	build
+		.history
+		.record(BuildOperation::Placed { cell }, tower_entities(&world));
+
+	// SHIFT keeps building the same kind as long as there is gold for it
+	build.repeating = shift;
+	build.placing = shift && economy.gold >= kind.cost();
+}
+
+fn remove_tower(
+	mut world: Mut<World>,
+	mut economy: Mut<Economy>,
+	mut notifications: Mut<Notifications>,
+	mut selection: Mut<Selection>,
+	mut build: Mut<BuildState>,
+	input: Const<GameInput>,
+) {
+	if build.editing() || !input.is_action_activated(Action::Sell) {
+		return;
+	}
+
+	let cell = match selection.cell {
+		Some(cell) => cell,
+		None => return,
+	};
+
+	let sold = world
+		.query::<(&Entity, &Tower)>()
+		.find(|(_, tower)| tower.cell == cell)
+		.map(|(entity, tower)| {
+			let operation = BuildOperation::Sold {
+				kind: tower.kind,
+				cell,
+				level: tower.level,
+				priority: tower.priority,
+				kills: tower.kills,
+				refund: tower.refund(),
+			};
+			(*entity, tower.kind, tower.refund(), operation)
+		});
+
+	// `update_navigation` frees the cell for the enemies once the tower is gone
+	if let Some((entity, kind, refund, operation)) = sold {
+		world.exile(entity);
+		economy.earn(refund);
+		notifications.notify(format!("Sold {:?} tower for {} gold", kind, refund));
+		build
+			.history
+			.record(operation, tower_entities(&world));
+		selection.cell = None;
+		selection.point = None;
+	}
+}
+
+/// CTRL+Z reverses the last placement or sale. An undone placement refunds everything paid for the tower,
+/// an undone sale takes the refund back. `update_navigation` reroutes the enemies afterwards.
+fn undo_build(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut economy: Mut<Economy>,
+	mut notifications: Mut<Notifications>,
+	mut build: Mut<BuildState>,
+	input: Const<GameInput>,
+	nav_grid: Const<NavGrid>,
+) {
+	if !input.is_action_activated(Action::Undo) || input.modifiers != dotrix::input::Modifiers::CTRL {
+		return;
+	}
+
+	let operation = match build
+		.history
+		.pop(&tower_entities(&world))
+	{
+		Some(operation) => operation,
+		None => {
+			notifications.notify("Nothing to undo");
+			return;
+		}
+	};
+
+	match operation {
+		BuildOperation::Placed {
+			cell,
+		} => {
+			let placed = world
+				.query::<(&Entity, &Tower)>()
+				.find(|(_, tower)| tower.cell == cell)
+				.map(|(entity, tower)| (*entity, tower.kind, tower.value()));
+			if let Some((entity, kind, value)) = placed {
+				world.exile(entity);
+				economy.reimburse(value);
+				economy.towers_built = economy.towers_built.saturating_sub(1);
+				notifications.notify(format!("Undid placing a {:?} tower, {} gold refunded", kind, value));
+			}
+		}
+		BuildOperation::Sold {
+			kind,
+			cell,
+			level,
+			priority,
+			kills,
+			refund,
+		} => {
+			let occupied = world
+				.query::<(&Tower,)>()
+				.any(|(tower,)| tower.cell == cell);
+			if occupied || !nav_grid.can_block(cell) {
+				notifications.notify("Can not undo the sale, the cell is taken or needed for the path");
+				build
+					.history
+					.restore(operation);
+				return;
+			}
+			if !economy.take_back(refund) {
+				notifications.notify(format!("Not enough gold to undo the sale: {} needed", refund));
+				build
+					.history
+					.restore(operation);
+				return;
+			}
+
+			let tower = Tower {
+				level,
+				priority,
+				kills,
+				..Tower::new(kind, cell)
+			};
+			spawn_tower(&mut world, &mut assets, &nav_grid.grid, tower);
+			notifications.notify(format!("Undid selling a {:?} tower", kind));
+		}
+	}
+
+	build
+		.history
+		.sync(tower_entities(&world));
+}
+
+fn tower_entities(world: &World) -> HashSet<Entity> {
+	world
+		.query::<(&Entity, &Tower)>()
+		.map(|(entity, _)| *entity)
+		.collect()
+}
+
+fn spawn_tower(world: &mut World, assets: &mut Assets, grid: &TerrainGrid, tower: Tower) {
+	let mesh = assets.register(&tower.mesh_name());
+
+	if let Some((model, material, transform, pipeline)) = (pbr::solid::Entity {
+		mesh,
+		albedo: tower.color(),
+		translate: grid.cell_to_world_center(tower.cell),
+		..Default::default()
+	})
+	.some()
+	{
+		world.spawn(Some((model, material, transform, pipeline, tower)));
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_control(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut state: Mut<State>,
+	mut camera: Mut<Camera>,
+	mut economy: Mut<Economy>,
+	mut context: Context<SaveContext>,
+	grid: Const<TerrainGrid>,
+	input: Const<GameInput>,
+) {
+	let load = if !context.restored_on_startup {
+		context.restored_on_startup = true;
+		!input.fresh_start()
+	} else {
+		input.is_action_activated(Action::Load)
+	};
+
+	if !load {
+		return;
+	}
+
+	let main_state = state
+		.get_mut::<MainState>()
+		.expect("Unable to get main state");
+
+	match save::load_game(save::SAVE_PATH) {
+		Ok(save) => restore_game(&save, &mut world, &mut assets, main_state, &mut camera, &mut economy, &grid),
+		Err(err) if err.is_missing() => log::info!("No save file at {}, starting a new game", save::SAVE_PATH),
+		Err(err) => log::error!("Unable to load {}, starting a new game: {}", save::SAVE_PATH, err),
+	}
+}
+
+/// Restores the settings and camera bookmarks on the same key as `load_control`, errors are already reported there
+fn load_settings(mut settings: Mut<Settings>, mut bookmarks: Mut<CameraBookmarks>, mut rng: Mut<Rng>, input: Const<GameInput>) {
+	if input.is_action_activated(Action::Load) {
+		restore_settings(&mut settings, &mut bookmarks, &mut rng);
+	}
+}
+
+/// Takes the settings and camera bookmarks from the save file if there is one, on startup before the menu
+/// is shown and on every load
+fn restore_settings(settings: &mut Settings, bookmarks: &mut CameraBookmarks, rng: &mut Rng) {
+	if let Ok(save) = save::load_game(save::SAVE_PATH) {
+		*settings = save.settings.clamped();
+		*bookmarks = save.bookmarks;
+		rng.reseed(settings.seed);
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_control(
+	world: Const<World>,
+	state: Const<State>,
+	camera: Const<Camera>,
+	economy: Const<Economy>,
+	settings: Const<Settings>,
+	bookmarks: Const<CameraBookmarks>,
+	input: Const<GameInput>,
+	mut notifications: Mut<Notifications>,
+) {
+	if !input.is_action_activated(Action::Save) {
+		return;
+	}
+
+	let main_state = state
+		.get::<MainState>()
+		.expect("Unable to get main state");
+	let save = snapshot(&world, main_state, &camera, &economy, &settings, &bookmarks);
+
+	match save::save_game(save::SAVE_PATH, &save) {
+		Ok(()) => notifications.notify("Game saved"),
+		Err(err) => {
+			log::error!("Unable to save the game to {}: {}", save::SAVE_PATH, err);
+			notifications.notify("Unable to save the game");
+		}
+	}
+}
+
+/// Saves the game to `save::AUTOSAVE_PATH` when it gets paused, at most once per `AUTOSAVE_INTERVAL`.
+/// Runs before `ui_main` pushes the pause state, as the main state is out of reach afterwards.
+#[allow(clippy::too_many_arguments)]
+fn autosave(
+	world: Const<World>,
+	mut state: Mut<State>,
+	camera: Const<Camera>,
+	economy: Const<Economy>,
+	settings: Const<Settings>,
+	bookmarks: Const<CameraBookmarks>,
+	input: Const<GameInput>,
+	mut notifications: Mut<Notifications>,
+) {
+	if !input.is_action_activated(Action::TogglePause) {
+		return;
+	}
+
+	let main_state = state
+		.get_mut::<MainState>()
+		.expect("Unable to get main state");
+
+	let throttled = match main_state.last_autosave {
+		Some(last) => last.elapsed() < AUTOSAVE_INTERVAL,
+		None => false,
+	};
+	if throttled {
+		return;
+	}
+	main_state.last_autosave = Some(Instant::now());
+
+	let save = snapshot(&world, main_state, &camera, &economy, &settings, &bookmarks);
+	match save::save_game(save::AUTOSAVE_PATH, &save) {
+		Ok(()) => notifications.notify("Autosaved"),
+		Err(err) => log::error!("Unable to autosave the game to {}: {}", save::AUTOSAVE_PATH, err),
+	}
+}
+
+/// Current game as it is written to a save file
+fn snapshot(
+	world: &World,
+	main_state: &MainState,
+	camera: &Camera,
+	economy: &Economy,
+	settings: &Settings,
+	bookmarks: &CameraBookmarks,
+) -> SaveGame {
+	SaveGame {
+		positions: main_state.positions.clone(),
+		camera_target: [camera.target.x, camera.target.y, camera.target.z],
+		gold: economy.gold,
+		towers: world
+			.query::<(&Tower,)>()
+			.map(|(tower,)| SavedTower {
+				kind: tower.kind,
+				cell: tower.cell,
+				level: tower.level,
+				priority: tower.priority,
+			})
+			.collect(),
+		settings: *settings,
+		bookmarks: *bookmarks,
+	}
+}
+
+fn restore_game(
+	save: &SaveGame,
+	world: &mut World,
+	assets: &mut Assets,
+	main_state: &mut MainState,
+	camera: &mut Camera,
+	economy: &mut Economy,
+	grid: &TerrainGrid,
+) {
+	let placed = world
+		.query::<(&Entity, &Tower)>()
+		.map(|(entity, _)| *entity)
+		.collect::<Vec<_>>();
+	for entity in placed {
+		world.exile(entity);
+	}
+	for tower in save.towers.iter() {
+		let restored = Tower {
+			level: tower.level.clamp(1, towers::MAX_LEVEL),
+			priority: tower.priority,
+			..Tower::new(tower.kind, tower.cell)
+		};
+		spawn_tower(world, assets, grid, restored);
+	}
+
+	economy.gold = save.gold;
+	camera.target = Point3::new(save.camera_target[0], save.camera_target[1], save.camera_target[2]);
+
+	// Terrain of another size can not be applied to the current mesh
+	if save.positions.len() == main_state.positions.len() {
+		main_state.positions = save.positions.clone();
+		update_terrain_normals(assets, main_state, grid);
+	} else {
+		log::warn!("Saved terrain does not match the current terrain size, keeping the generated one");
+	}
+}
+
+/// Blocks the cells of placed or restored towers and reroutes enemies once they or the level change
+fn update_navigation(mut world: Mut<World>, mut nav_grid: Mut<NavGrid>, mut path: Mut<Path>, grid: Const<TerrainGrid>) {
+	let blocked = world
+		.query::<(&Tower,)>()
+		.map(|(tower,)| tower.cell)
+		.collect::<HashSet<_>>();
+	if blocked == nav_grid.blocked && !nav_grid.changed {
+		return;
+	}
+	nav_grid.blocked = blocked;
+	nav_grid.changed = false;
+	nav_grid.revision += 1;
+
+	match nav_grid.find_path(nav_grid.spawn, nav_grid.goal) {
+		Some(cells) => *path = Path::from_cells(&grid, &cells),
+		None => log::warn!("Enemy goal is unreachable, keeping the previous path"),
+	}
+
+	for (enemy, motion) in world.query::<(&mut Enemy, &Motion)>() {
+		// Enemies walled in by towers keep walking their previous route
+		let route = grid
+			.world_to_cell(motion.current)
+			.and_then(|cell| nav_grid.find_path(cell, nav_grid.goal));
+		if let Some(cells) = route {
+			enemy.waypoints = Path::from_cells(&grid, &cells).waypoints;
+			enemy.waypoint_index = 0;
+		}
+	}
+}
+
+/// Keeps the markers above the first and last waypoints of the path and bobs them up and down
+fn animate_path_markers(mut world: Mut<World>, mut context: Context<PathMarkersContext>, path: Const<Path>, frame: Const<Frame>) {
+	context.elapsed += frame.delta().as_secs_f32();
+	let bob = (context.elapsed * PATH_MARKER_BOB_SPEED).sin() * PATH_MARKER_BOB_HEIGHT;
+
+	for (marker, transform) in world.query::<(&PathMarker, &mut Transform)>() {
+		let waypoint = match marker {
+			PathMarker::Spawn => path.waypoints.first(),
+			PathMarker::Goal => path.waypoints.last(),
+		};
+		if let Some(waypoint) = waypoint {
+			transform.translate = *waypoint + Vec3::new(0.0, PATH_MARKER_ELEVATION + bob, 0.0);
+			transform.scale = Vec3::new(1.0, 1.0, 1.0);
+		}
+	}
+}
+
+/// Tints the tiles green where a tower can be built, red where it can not and gray on the walls
+fn update_tile_colors(mut state: Mut<State>, mut assets: Mut<Assets>, mut context: Context<TileColorsContext>, nav_grid: Const<NavGrid>) {
+	if context.revision == Some(nav_grid.revision) {
+		return;
+	}
+	context.revision = Some(nav_grid.revision);
+
+	let main_state = state
+		.get_mut::<MainState>()
+		.expect("Unable to get main state");
+
+	let walls = nav_grid.walls();
+	for x in 0..nav_grid.grid.width as i32 {
+		for z in 0..nav_grid.grid.depth as i32 {
+			let cell = (x, z);
+			let color = if walls.contains(&cell) {
+				TILE_WALL
+			} else if !nav_grid.blocked.contains(&cell) && nav_grid.can_block(cell) {
+				TILE_BUILDABLE
+			} else {
+				TILE_BLOCKED
+			};
+			for vertex in nav_grid.grid.cell_vertices(cell) {
+				main_state.colors[vertex] = color;
+			}
+		}
+	}
+
+	update_terrain_normals(&mut assets, main_state, &nav_grid.grid);
+}
+
+fn move_enemies(
+	mut world: Mut<World>,
+	mut state: Mut<State>,
+	mut window: Mut<Window>,
+	mut base_health: Mut<BaseHealth>,
+	mut shake: Mut<CameraShake>,
+	clock: Const<GameClock>,
+) {
+	let mut arrived = Vec::new();
+
+	// Enemies push each other apart from where they stood at the start of the frame
+	let mut neighbors = SpatialGrid::new(enemies::SEPARATION_RADIUS);
+	for (entity, _, motion) in world.query::<(&Entity, &Enemy, &Motion)>() {
+		neighbors.insert(motion.current, *entity);
+	}
+
+	for (entity, enemy, motion) in world.query::<(&Entity, &mut Enemy, &mut Motion)>() {
+		let speed = enemy.speed * enemy.effects.speed_factor();
+		let push = enemies::separation(
+			motion.current,
+			*entity,
+			neighbors.query(motion.current, enemies::SEPARATION_RADIUS),
+			speed * enemies::SEPARATION_MAX_SHARE,
+		);
+
+		for _ in 0..clock.ticks {
+			motion.start_tick();
+			motion.current += push * clock.timestep;
+			let mut step = speed * clock.timestep;
+
+			// Spend the whole step even if it overshoots a waypoint, so the speed does not depend on the tick length
+			while let Some(waypoint) = enemy.waypoints.get(enemy.waypoint_index).copied() {
+				let before = motion.current;
+				if !enemies::step_towards(&mut motion.current, waypoint, step) {
+					break;
+				}
+				step -= (motion.current - before).magnitude();
+				enemy.waypoint_index += 1;
+			}
+
+			if enemy.waypoint_index >= enemy.waypoints.len() {
+				arrived.push(*entity);
+				break;
+			}
+		}
+	}
+
+	let mut destroyed = false;
+	for entity in arrived {
+		destroyed |= base_health.lose();
+		shake.start(shake::SHAKE_MAGNITUDE);
+		world.exile(entity);
+	}
+
+	if destroyed {
+		window.set_cursor_grab(false);
+		state.push(GameOverState {
+			name: String::from("Game Over State"),
+		});
+	}
+}
+
+/// Takes the shake offset of the last frame off `camera.target`, runs before any system reads or moves the camera
+fn settle_camera(mut camera: Mut<Camera>, mut shake: Mut<CameraShake>) {
+	camera.target -= shake.take_offset();
+}
+
+/// Offsets `camera.target` by the `CameraShake` of this frame, runs after every system that moves the camera
+fn shake_camera(mut camera: Mut<Camera>, mut shake: Mut<CameraShake>, settings: Const<Settings>, frame: Const<Frame>) {
+	if !settings.camera_shake {
+		shake.stop();
+		return;
+	}
+	let offset = shake.step(frame.delta().as_secs_f32());
+	camera.target += offset;
+}
+
+/// Sizes and titles the window as `config::CONFIG_PATH` asks, the application is created with the default title
+fn apply_window_config(mut window: Mut<Window>, config: Const<WindowConfig>, mut context: Context<WindowConfigContext>) {
+	if context.applied {
+		return;
+	}
+	context.applied = true;
+
+	window.set_title(&config.title);
+	if config.fullscreen {
+		window.set_full_screen(true);
+	} else {
+		window.set_inner_size(Vec2u::new(config.width, config.height));
+	}
+}
+
+/// Replaces the default waves with the ones from `waves::WAVES_PATH`, malformed files are logged
+/// and the default waves kept
+fn load_waves(mut spawner: Mut<WaveSpawner>, mut context: Context<WavesContext>) {
+	if context.loaded {
+		return;
+	}
+	context.loaded = true;
+
+	match waves::load_waves(waves::WAVES_PATH) {
+		Ok(definitions) => {
+			log::info!("Loaded {} waves from {}", definitions.len(), waves::WAVES_PATH);
+			*spawner = WaveSpawner::new(definitions);
+		}
+		Err(err) if err.is_missing() => log::info!("No waves file at {}, using the default waves", waves::WAVES_PATH),
+		Err(err) => log::error!("Unable to load {}, using the default waves: {}", waves::WAVES_PATH, err),
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_waves(
+	mut state: Mut<State>,
+	mut world: Mut<World>,
+	mut window: Mut<Window>,
+	mut assets: Mut<Assets>,
+	mut spawner: Mut<WaveSpawner>,
+	mut rng: Mut<Rng>,
+	path: Const<Path>,
+	clock: Const<GameClock>,
+) {
+	let spawn_point = match path.waypoints.first() {
+		Some(point) => *point,
+		None => return,
+	};
+
+	let mut kinds = Vec::new();
+	for _ in 0..clock.ticks {
+		match spawner.phase {
+			WavePhase::Building => {
+				spawner.countdown -= clock.timestep;
+				if spawner.countdown <= 0.0 {
+					spawner.start_wave();
+				}
+				continue;
+			}
+			WavePhase::Cleared => break,
+			WavePhase::Spawning => {}
+		}
+
+		if spawner.remaining() == 0 {
+			// Wave is over once every spawned enemy is killed or has reached the goal
+			if kinds.is_empty()
+				&& world
+					.query::<(&Enemy,)>()
+					.next()
+					.is_none()
+				&& spawner.finish_wave()
+			{
+				// Pushed on the transition to cleared only, the spawner stays cleared until a restart
+				window.set_cursor_grab(false);
+				state.push(VictoryState {
+					name: String::from("Victory State"),
+				});
+			}
+			continue;
+		}
+
+		spawner.elapsed += clock.timestep;
+		if spawner.elapsed < spawner.interval {
+			continue;
+		}
+		// Spawn is held until the last enemy has walked far enough, slow enemies would stack otherwise.
+		// The interval is counted from the held spawn.
+		let spacing_squared = spawner.spacing * spawner.spacing;
+		let crowded = !kinds.is_empty()
+			|| world
+				.query::<(&Enemy, &Motion)>()
+				.any(|(_, motion)| {
+					let offset = motion.current - spawn_point;
+					offset.x * offset.x + offset.z * offset.z < spacing_squared
+				});
+		if crowded {
+			spawner.elapsed = spawner.interval;
+			continue;
+		}
+		spawner.elapsed -= spawner.interval;
+		if let Some(kind) = spawner.pop() {
+			kinds.push(kind);
+		}
+	}
+
+	for kind in kinds {
+		let offset = enemies::lateral_offset(&path.waypoints, rng.range(-spawner.jitter, spawner.jitter));
+		let health_factor = rng.range(1.0 - enemies::HEALTH_VARIANCE, 1.0 + enemies::HEALTH_VARIANCE);
+		let enemy = Enemy::new(kind, health_factor, path.waypoints.clone()).leaving_spawn();
+
+		spawn_enemy(&mut world, &mut assets, enemy, spawn_point + offset);
+	}
+}
+
+fn spawn_enemy(world: &mut World, assets: &mut Assets, enemy: Enemy, position: Vec3) {
+	let mesh = assets.register(enemy.kind.mesh_name());
+	if let Some((model, material, transform, pipeline)) = (pbr::solid::Entity {
+		mesh,
+		albedo: enemy.kind.color(),
+		translate: position,
+		..Default::default()
+	})
+	.some()
+	{
+		world.spawn(Some((model, material, transform, pipeline, enemy, Motion::at(position))));
+	}
+}
+
+fn tower_targeting(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut pool: Mut<ProjectilePool>,
+	audio: Const<Audio>,
+	settings: Const<Settings>,
+	clock: Const<GameClock>,
+) {
+	if clock.ticks == 0 {
+		return;
+	}
+
+	let mut enemies = SpatialGrid::new(TARGETING_CELL_SIZE);
+	for (enemy, motion) in world.query::<(&Enemy, &Motion)>() {
+		if enemy.health > 0.0 {
+			enemies.insert(motion.current, (enemy.health, enemy.remaining_distance(motion.current)));
+		}
+	}
+
+	let mut shots = Vec::new();
+	for (entity, tower, transform) in world.query::<(&Entity, &mut Tower, &Transform)>() {
+		// A tower fires at most once a frame, its interval is longer than a frame at any playable rate
+		tower.cooldown = (tower.cooldown - clock.frame_time()).max(0.0);
+		if tower.cooldown > 0.0 {
+			continue;
+		}
+
+		let muzzle = transform.translate + tower.muzzle();
+		let stats = tower.stats();
+		let range_squared = stats.range * stats.range;
+		// The target has the lowest key of the enemies in range
+		let target = enemies
+			.query(muzzle, stats.range)
+			.map(|(position, (health, remaining))| (*position, *health, *remaining, (*position - muzzle).magnitude2()))
+			.filter(|(_, _, _, distance)| *distance <= range_squared)
+			.map(|(position, health, remaining, distance)| {
+				let key = match tower.priority {
+					TargetPriority::First => remaining,
+					TargetPriority::Last => -remaining,
+					TargetPriority::Closest => distance,
+					TargetPriority::Strongest => -health,
+				};
+				(position, key)
+			})
+			.min_by(|a, b| a.1.total_cmp(&b.1));
+
+		if let Some((target, _)) = target {
+			tower.cooldown = stats.fire_interval;
+			tower.last_fired = Some(clock.elapsed);
+			let velocity = (target - muzzle).normalize() * towers::PROJECTILE_SPEED;
+			shots.push((*entity, muzzle, velocity, stats.damage, tower.kind, stats.range / towers::PROJECTILE_SPEED));
+		}
+	}
+
+	if shots.is_empty() {
+		return;
+	}
+
+	let mesh = assets.register(towers::PROJECTILE_MESH);
+	let projectiles = shots
+		.into_iter()
+		.map(|(source, muzzle, velocity, damage, kind, lifetime)| {
+			audio.play_sound(audio::FIRE_SOUND, settings.master_volume);
+			let projectile = Projectile {
+				velocity,
+				damage,
+				effect: kind.effect(),
+				splash: kind.splash(),
+				lifetime,
+				source,
+				active: true,
+			};
+			(projectile, muzzle)
+		})
+		.collect();
+	spawn_projectiles(&mut world, &mut pool, mesh, projectiles);
+}
+
+/// Fires the projectiles from their positions. Inactive projectiles of the pool are taken over first,
+/// the whole component is replaced so nothing of the last shot is left, new entities are spawned for the rest.
+fn spawn_projectiles(world: &mut World, pool: &mut ProjectilePool, mesh: Id<Mesh>, projectiles: Vec<(Projectile, Vec3)>) {
+	let mut projectiles = projectiles.into_iter();
+
+	for (pooled, motion, transform) in world.query::<(&mut Projectile, &mut Motion, &mut Transform)>() {
+		if pooled.active {
+			continue;
+		}
+		let (projectile, position) = match projectiles.next() {
+			Some(next) => next,
+			None => return,
+		};
+		*pooled = projectile;
+		*motion = Motion::at(position);
+		transform.translate = position;
+		transform.scale = Vec3::new(1.0, 1.0, 1.0);
+		pool.reused += 1;
+	}
+
+	for (projectile, position) in projectiles {
+		if let Some((model, material, transform, pipeline)) = (pbr::solid::Entity {
+			mesh,
+			albedo: Color::rgb(1.0, 0.9, 0.3),
+			translate: position,
+			..Default::default()
+		})
+		.some()
+		{
+			world.spawn(Some((model, material, transform, pipeline, projectile, Motion::at(position))));
+			pool.spawned += 1;
+		}
+	}
+}
+
+fn move_projectiles(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut economy: Mut<Economy>,
+	audio: Const<Audio>,
+	settings: Const<Settings>,
+	clock: Const<GameClock>,
+) {
+	let hit_radius_squared = towers::PROJECTILE_HIT_RADIUS * towers::PROJECTILE_HIT_RADIUS;
+	let mut despawned = Vec::new();
+	let mut kills = Vec::new();
+	let mut rings = Vec::new();
+
+	for (projectile, motion, transform) in world.query::<(&mut Projectile, &mut Motion, &mut Transform)>() {
+		if !projectile.active {
+			continue;
+		}
+		for _ in 0..clock.ticks {
+			motion.start_tick();
+			motion.current += projectile.velocity * clock.timestep;
+			projectile.lifetime -= clock.timestep;
+
+			let position = motion.current;
+			let hit = world
+				.query::<(&Entity, &Enemy, &Motion)>()
+				.find(|(_, enemy, enemy_motion)| enemy.health > 0.0 && (enemy_motion.current - position).magnitude2() <= hit_radius_squared)
+				.map(|(enemy_entity, _, enemy_motion)| (*enemy_entity, enemy_motion.current));
+
+			if let Some((target, impact)) = hit {
+				// A splash damages the hit enemy in full and the ones around it by the distance to it
+				for (enemy_entity, enemy, enemy_motion) in world.query::<(&Entity, &mut Enemy, &Motion)>() {
+					let factor = match projectile.splash {
+						Some(splash) => splash.factor((enemy_motion.current - impact).magnitude()),
+						None if *enemy_entity == target => 1.0,
+						None => 0.0,
+					};
+					if factor <= 0.0 || enemy.health <= 0.0 {
+						continue;
+					}
+
+					enemy.health -= projectile.damage * factor;
+					if let Some(effect) = projectile.effect {
+						enemy.effects.apply(effect, projectile.source);
+					}
+					if enemy.health <= 0.0 {
+						economy.earn(enemy.kind.reward());
+						audio.play_sound(audio::EXPLOSION_SOUND, settings.master_volume);
+						kills.push(projectile.source);
+						despawned.push(*enemy_entity);
+					}
+				}
+				if let Some(splash) = projectile.splash {
+					rings.push((impact, splash.radius));
+				}
+				deactivate_projectile(projectile, transform);
+				break;
+			} else if projectile.lifetime <= 0.0 {
+				deactivate_projectile(projectile, transform);
+				break;
+			}
+		}
+	}
+
+	// The tower may have been sold while its projectile was flying
+	for (entity, tower) in world.query::<(&Entity, &mut Tower)>() {
+		tower.kills += kills.iter().filter(|source| *source == entity).count() as u32;
+	}
+
+	for entity in despawned {
+		world.exile(entity);
+	}
+
+	// The ring mesh has a radius of 1.0 and is scaled to the splash radius
+	let mesh = assets.register(towers::SPLASH_RING_MESH);
+	let [r, g, b, a] = SPLASH_RING_COLOR;
+	for (impact, radius) in rings {
+		if let Some((model, material, mut transform, pipeline)) = (pbr::solid::Entity {
+			mesh,
+			albedo: Color::rgba(r, g, b, a),
+			translate: impact,
+			..Default::default()
+		})
+		.some()
+		{
+			transform.scale = Vec3::new(radius, 1.0, radius);
+			world.spawn(Some((
+				model,
+				material,
+				transform,
+				pipeline,
+				SplashRing {
+					remaining: towers::SPLASH_RING_SECONDS,
+				},
+			)));
+		}
+	}
+}
+
+/// Loads the game for `--bench`: towers on every cell that keeps the path open, and the enemy and projectile
+/// counts of the `Benchmark` topped up every frame. The base can not fall meanwhile. Prints the frame times
+/// to stdout and exits once `bench::MEASURED_FRAMES` are measured.
+#[allow(clippy::too_many_arguments)]
+fn run_benchmark(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut bench: Mut<Benchmark>,
+	mut pool: Mut<ProjectilePool>,
+	mut nav_grid: Mut<NavGrid>,
+	mut base_health: Mut<BaseHealth>,
+	mut rng: Mut<Rng>,
+	path: Const<Path>,
+	frame: Const<Frame>,
+) {
+	let grid = nav_grid.grid;
+
+	if !bench.towers_placed {
+		bench.towers_placed = true;
+		// Cells are blocked one by one so the next ones are tested against the path left open,
+		// `update_navigation` blocks them for good once it sees the towers
+		let blocked = nav_grid.blocked.clone();
+		for x in 0..grid.width as i32 {
+			for z in 0..grid.depth as i32 {
+				let cell = (x, z);
+				if nav_grid.blocked.contains(&cell) || !nav_grid.can_block(cell) {
+					continue;
+				}
+				nav_grid.blocked.insert(cell);
+				let kind = TowerKind::ALL[rng.next_u64() as usize % TowerKind::ALL.len()];
+				spawn_tower(&mut world, &mut assets, &grid, Tower::new(kind, cell));
+			}
+		}
+		nav_grid.blocked = blocked;
+	}
+
+	base_health.lives = base::STARTING_LIVES;
+
+	if let Some(spawn_point) = path.waypoints.first().copied() {
+		let alive = world
+			.query::<(&Enemy,)>()
+			.count();
+		for i in alive..bench.enemies {
+			let kind = EnemyKind::ALL[i % EnemyKind::ALL.len()];
+			let offset = enemies::lateral_offset(&path.waypoints, rng.range(-enemies::SPAWN_JITTER, enemies::SPAWN_JITTER));
+			let enemy = Enemy::new(kind, 1.0, path.waypoints.clone()).leaving_spawn();
+			spawn_enemy(&mut world, &mut assets, enemy, spawn_point + offset);
+		}
+	}
+
+	// Harmless projectiles crossing the terrain in random directions, a tower or an enemy stands in as their source
+	let source = world
+		.query::<(&Entity, &Tower)>()
+		.map(|(entity, _)| *entity)
+		.next()
+		.or_else(|| {
+			world
+				.query::<(&Entity, &Enemy)>()
+				.map(|(entity, _)| *entity)
+				.next()
+		});
+	if let Some(source) = source {
+		let flying = world
+			.query::<(&Projectile,)>()
+			.filter(|(projectile,)| projectile.active)
+			.count();
+		let mesh = assets.register(towers::PROJECTILE_MESH);
+		let (extent_x, extent_z) = grid.extent();
+		let mut projectiles = Vec::new();
+		for _ in flying..bench.projectiles {
+			let position = grid.origin + Vec3::new(rng.range(0.0, extent_x), bench::PROJECTILE_HEIGHT, rng.range(0.0, extent_z));
+			let angle = rng.range(0.0, std::f32::consts::TAU);
+			let projectile = Projectile {
+				velocity: Vec3::new(angle.cos(), 0.0, angle.sin()) * towers::PROJECTILE_SPEED,
+				damage: 0.0,
+				effect: None,
+				splash: None,
+				lifetime: bench::PROJECTILE_LIFETIME,
+				source,
+				active: true,
+			};
+			projectiles.push((projectile, position));
+		}
+		spawn_projectiles(&mut world, &mut pool, mesh, projectiles);
+	}
+
+	if bench.record(frame.delta().as_secs_f32()) {
+		println!("{}", bench.report(&pool));
+		std::process::exit(0);
+	}
+}
+
+/// Measures the terrain layouts for `--bench-terrain`: spawns the terrain in each of them in turn, raises
+/// a cell every frame and times rebuilding the meshes the edit touches. Prints the frame and rebuild times
+/// to stdout and exits once every layout is measured.
+fn run_terrain_benchmark(
+	mut world: Mut<World>,
+	mut state: Mut<State>,
+	mut assets: Mut<Assets>,
+	mut bench: Mut<TerrainBenchmark>,
+	grid: Const<TerrainGrid>,
+	frame: Const<Frame>,
+) {
+	let main_state = match state.get_mut::<MainState>() {
+		Some(main_state) => main_state,
+		None => return,
+	};
+	let layout = match bench.layout() {
+		Some(layout) => layout,
+		None => return,
+	};
+
+	if bench.frames.is_none() {
+		spawn_chunks(&mut assets, &mut world, main_state, &grid, bench.chunk_size(layout));
+	}
+
+	// Cells are edited row by row, so the edits spread over every chunk
+	let frames = bench.frames.unwrap_or(0);
+	let cell = ((frames % grid.width) as i32, (frames / grid.width % grid.depth) as i32);
+	for vertex in grid.cell_vertices(cell) {
+		main_state.positions[vertex][1] += bench::TERRAIN_EDIT_HEIGHT;
+	}
+	let start = Instant::now();
+	update_terrain_chunks(&mut assets, main_state, &grid, |chunk| chunk.overlaps(cell, cell));
+	let rebuild = start.elapsed().as_secs_f32();
+
+	bench.record(frame.delta().as_secs_f32(), rebuild);
+	if bench.layout().is_none() {
+		println!("{}", bench.report());
+		std::process::exit(0);
+	}
+}
+
+/// Returns the projectile to the pool, scaled down to nothing until `spawn_projectiles` reuses it
+fn deactivate_projectile(projectile: &mut Projectile, transform: &mut Transform) {
+	projectile.active = false;
+	transform.scale = Vec3::new(0.0, 0.0, 0.0);
+}
+
+/// Fades the splash rings out and despawns them once `towers::SPLASH_RING_SECONDS` are over
+fn expire_splash_rings(mut world: Mut<World>, clock: Const<GameClock>) {
+	let mut expired = Vec::new();
+	for (entity, ring, material) in world.query::<(&Entity, &mut SplashRing, &mut pbr::Material)>() {
+		ring.remaining -= clock.frame_time();
+		if ring.remaining <= 0.0 {
+			expired.push(*entity);
+		}
+		let [r, g, b, a] = SPLASH_RING_COLOR;
+		let fade = (ring.remaining / towers::SPLASH_RING_SECONDS).clamp(0.0, 1.0);
+		material.albedo = Color::rgba(r, g, b, a * fade);
+	}
+
+	for entity in expired {
+		world.exile(entity);
+	}
+}
+
+/// Deals the poison damage and expires the slows and poisons of the enemies
+fn tick_status_effects(
+	mut world: Mut<World>,
+	mut economy: Mut<Economy>,
+	audio: Const<Audio>,
+	settings: Const<Settings>,
+	clock: Const<GameClock>,
+) {
+	let mut despawned = Vec::new();
+	let mut kills = Vec::new();
+
+	for (entity, enemy) in world.query::<(&Entity, &mut Enemy)>() {
+		// Killed by a projectile and rewarded by `move_projectiles` already
+		if enemy.health <= 0.0 {
+			continue;
+		}
+		for _ in 0..clock.ticks {
+			let (damage, source) = match enemy.effects.tick(clock.timestep) {
+				Some(poison) => poison,
+				None => continue,
+			};
+			enemy.health -= damage;
+			if enemy.health <= 0.0 {
+				economy.earn(enemy.kind.reward());
+				audio.play_sound(audio::EXPLOSION_SOUND, settings.master_volume);
+				kills.push(source);
+				despawned.push(*entity);
+				break;
+			}
+		}
+	}
+
+	for (entity, tower) in world.query::<(&Entity, &mut Tower)>() {
+		tower.kills += kills.iter().filter(|source| *source == entity).count() as u32;
+	}
+
+	for entity in despawned {
+		world.exile(entity);
+	}
+}
+
+fn day_night_cycle(mut world: Mut<World>, mut time_of_day: Mut<TimeOfDay>, clock: Const<GameClock>) {
+	time_of_day.advance(clock.frame_time());
+
+	let (sun_color, sun_intensity) = time_of_day.sun();
+	for (light, switch) in world.query::<(&mut Light, &LightSwitch)>() {
+		match light {
+			Light::Simple {
+				position,
+				color,
+				intensity,
+				..
+			} => {
+				*position = time_of_day.sun_position();
+				*color = sun_color;
+				*intensity = sun_intensity;
+			}
+			Light::Ambient {
+				intensity,
+				..
+			} => {
+				*intensity = ambient_intensity(switch, &time_of_day);
+			}
+			_ => {}
+		}
+	}
+}
+
+/// Ambient light has no `enabled` flag, so a disabled one is zeroed
+fn ambient_intensity(switch: &LightSwitch, time_of_day: &TimeOfDay) -> f32 {
+	if switch.enabled {
+		time_of_day.ambient_intensity()
+	} else {
+		0.0
+	}
+}
+
+fn pick_terrain_cell(
+	world: Const<World>,
+	input: Const<GameInput>,
+	window: Const<Window>,
+	camera: Const<Camera>,
+	grid: Const<TerrainGrid>,
+	overlay: Const<Overlay>,
+	mut selection: Mut<Selection>,
+) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	// Clicks on the minimap and other windows must not select the terrain behind them
+	if egui_overlay.ctx.is_pointer_over_area() {
+		selection.point = None;
+		selection.cell = None;
+		selection.tower = None;
+		return;
+	}
+
+	let ray = input
+		.mouse_position()
+		.zip(window_viewport(&window))
+		.map(|(cursor, viewport)| Ray::from_cursor(&camera, &cursor, viewport));
+	let point = ray
+		.as_ref()
+		.and_then(|ray| ray.intersect_plane_y(TERRAIN_BASE_HEIGHT));
+
+	selection.point = point;
+	selection.cell = point.and_then(|point| grid.world_to_cell(point));
+
+	// Towers are tested against their boxes, the nearest one wins where several overlap on screen
+	selection.tower = ray.and_then(|ray| {
+		world
+			.query::<(&Entity, &Tower, &Transform)>()
+			.filter_map(|(entity, tower, transform)| {
+				let (min, max) = tower.bounds(transform.translate);
+				ray.intersect_box(min, max)
+					.map(|distance| (*entity, distance))
+			})
+			.min_by(|a, b| a.1.total_cmp(&b.1))
+			.map(|(entity, _)| entity)
+	});
+}
+
+fn update_highlight(mut world: Mut<World>, grid: Const<TerrainGrid>, selection: Const<Selection>) {
+	for (transform, _) in world.query::<(&mut Transform, &Highlight)>() {
+		match selection.cell {
+			Some(cell) => {
+				// Highlight mesh spreads from its corner along X and Z, like the cell from its own
+				transform.translate = grid.origin + Vec3::new(cell.0 as f32 * grid.tile_scale, HIGHLIGHT_OFFSET, cell.1 as f32 * grid.tile_scale);
+				transform.scale = Vec3::new(1.0, 1.0, 1.0);
+			}
+			None => transform.scale = Vec3::new(0.0, 0.0, 0.0),
+		}
+	}
+}
+
+/// Spawns the ghost tower on entering build mode and despawns it on leaving. The ghost stands on the
+/// selected cell, tinted by whether `place_tower` would build there.
+fn update_ghost_tower(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	build: Const<BuildState>,
+	selection: Const<Selection>,
+	economy: Const<Economy>,
+	nav_grid: Const<NavGrid>,
+) {
+	let ghosts = world
+		.query::<(&Entity, &GhostTower)>()
+		.map(|(entity, _)| *entity)
+		.collect::<Vec<_>>();
+
+	if !build.placing || build.editing() {
+		for entity in ghosts {
+			world.exile(entity);
+		}
+		return;
+	}
+
+	let kind = build.selected_kind;
+	if ghosts.is_empty() {
+		if let Some((model, material, mut transform, pipeline)) = (pbr::solid::Entity {
+			mesh: assets.register(&Tower::new(kind, (0, 0)).mesh_name()),
+			albedo: ghost_color(true),
+			..Default::default()
+		})
+		.some()
+		{
+			// Hidden until a cell is selected
+			transform.scale = Vec3::new(0.0, 0.0, 0.0);
+			world.spawn(Some((model, material, transform, pipeline, GhostTower { kind })));
+		}
+	}
+
+	let valid = selection.cell.map(|cell| {
+		let occupied = world
+			.query::<(&Tower,)>()
+			.any(|(tower,)| tower.cell == cell);
+		!occupied && economy.gold >= kind.cost() && nav_grid.can_block(cell)
+	});
+
+	for (ghost, model, material, transform) in world.query::<(&mut GhostTower, &mut pbr::Model, &mut pbr::Material, &mut Transform)>() {
+		if ghost.kind != kind {
+			ghost.kind = kind;
+			model.mesh = assets.register(&Tower::new(kind, (0, 0)).mesh_name());
+		}
+		match (selection.cell, valid) {
+			(Some(cell), Some(valid)) => {
+				transform.translate = nav_grid.grid.cell_to_world_center(cell);
+				transform.scale = Vec3::new(1.0, 1.0, 1.0);
+				material.albedo = ghost_color(valid);
+			}
+			_ => transform.scale = Vec3::new(0.0, 0.0, 0.0),
+		}
+	}
+}
+
+fn ghost_color(valid: bool) -> Color {
+	let [r, g, b, a] = if valid { GHOST_VALID } else { GHOST_BLOCKED };
+	Color::rgba(r, g, b, a)
+}
+
+/// Mouse look around the eye, scaled by `sensitivity`, and WASD movement along the view direction
+fn free_camera_control(input: &GameInput, camera: &mut Camera, sensitivity: f32, delta: f32) {
+	let eye = picking::eye_position(camera);
+
+	let mouse = input.mouse_delta() * (FREE_CAM_SENSITIVITY * sensitivity);
+	camera.y_angle += mouse.x;
+	camera.xz_angle = (camera.xz_angle + mouse.y).clamp(-FREE_CAM_MAX_PITCH, FREE_CAM_MAX_PITCH);
+
+	// Camera orbits the target, so keep the eye in place by moving the target instead
+	let target = Vec3::new(camera.target.x, camera.target.y, camera.target.z);
+	let offset = picking::eye_position(camera) - target;
+	let forward = -offset.normalize();
+	let right = forward
+		.cross(Vec3::unit_y())
+		.normalize();
+
+	let mut direction = Vec3::new(0.0, 0.0, 0.0);
+	if input.is_action_hold(Action::PanUp) {
+		direction += forward;
+	} else if input.is_action_hold(Action::PanDown) {
+		direction -= forward;
+	}
+	if input.is_action_hold(Action::PanRight) {
+		direction += right;
+	} else if input.is_action_hold(Action::PanLeft) {
+		direction -= right;
+	}
+
+	let eye = eye + direction * (FREE_CAM_SPEED * delta);
+	let target = eye - offset;
+	camera.target = Point3::new(target.x, target.y, target.z);
+}
+
+/// Direction (-1.0, 0.0 or 1.0 by X and Z) to pan when the cursor touches the window border
+fn edge_pan_direction(input: &GameInput, window: &Window) -> (f32, f32) {
+	let pos = match input.mouse_position() {
+		Some(pos) => pos,
+		None => return (0.0, 0.0),
+	};
+	let (width, height) = match window_viewport(window) {
+		Some(viewport) => viewport,
+		None => return (0.0, 0.0),
+	};
+
+	let x = if pos.x <= EDGE_MARGIN {
+		-1.0
+	} else if pos.x >= width - EDGE_MARGIN {
+		1.0
+	} else {
+		0.0
+	};
+
+	let z = if pos.y <= EDGE_MARGIN {
+		-1.0
+	} else if pos.y >= height - EDGE_MARGIN {
+		1.0
+	} else {
+		0.0
+	};
+
+	(x, z)
+}
+
+/// Current size of the window in pixels, the space of `Input::mouse_position`. Read every frame, so
+/// picking and edge panning follow resizes, and `None` while the window is minimized to zero size.
+fn window_viewport(window: &Window) -> Option<(f32, f32)> {
+	let size = window.inner_size();
+	let (width, height) = (size.x as f32, size.y as f32);
+	if width < 1.0 || height < 1.0 {
+		return None;
+	}
+	Some((width, height))
+}
+
+/// Screen right and screen up directions projected on the ground plane
+fn ground_axes(camera: &Camera) -> (Vec3, Vec3) {
+	let (sin, cos) = camera.y_angle.sin_cos();
+	let forward = Vec3::new(-cos, 0.0, -sin);
+	let right = Vec3::new(sin, 0.0, -cos);
+
+	(right, forward)
+}
+
+/// Height of the camera eye above the terrain base
+fn camera_zoom(camera: &Camera) -> f32 {
+	camera.target.y + camera.distance * camera.xz_angle.sin() - TERRAIN_BASE_HEIGHT
+}
+
+/// Range of `camera.target.y` that keeps the zoom within `MIN_ZOOM..=MAX_ZOOM` and the eye above the ground
+fn zoom_limits(camera: &Camera) -> (f32, f32) {
+	let eye_offset = camera.distance * camera.xz_angle.sin();
+	let min_zoom = MIN_ZOOM.max(0.0);
+	let max_zoom = MAX_ZOOM.max(min_zoom);
+
+	(TERRAIN_BASE_HEIGHT + min_zoom - eye_offset, TERRAIN_BASE_HEIGHT + max_zoom - eye_offset)
+}
+
+fn debug_control(mut debug: Mut<DebugOptions>, input: Const<GameInput>) {
+	if input.is_action_activated(Action::ToggleWireframe) {
+		debug.wireframe = !debug.wireframe;
+	}
+	if input.is_action_activated(Action::ToggleDebug) {
+		debug.stats = !debug.stats;
+	}
+	if input.is_action_activated(Action::ToggleCosts) {
+		debug.costs = !debug.costs;
+	}
+	if input.is_action_activated(Action::ToggleCulling) {
+		debug.culling = !debug.culling;
+	}
+	if input.is_action_activated(Action::ToggleInputLog) {
+		debug.input_log = !debug.input_log;
+	}
+	if input.is_action_activated(Action::TogglePath) {
+		debug.path = !debug.path;
+	}
+	if input.is_action_activated(Action::ToggleTiles) {
+		debug.tiles = !debug.tiles;
+	}
+}
+
+/// Takes the input of the frame for the other systems, registered first
+fn capture_input(
+	mut game_input: Mut<GameInput>,
+	mut notifications: Mut<Notifications>,
+	input: Const<Input>,
+	gamepad: Const<Gamepad>,
+	frame: Const<Frame>,
+) {
+	if game_input.capture(&input, gamepad.axes(), frame.delta().as_secs_f32()) {
+		log::info!("Replay finished, back to the live input");
+		notifications.notify("Replay finished");
+	}
+}
+
+/// F12 writes the session recorded since startup, recording goes on afterwards
+fn save_replay(game_input: Const<GameInput>, mut notifications: Mut<Notifications>) {
+	if !game_input.is_action_activated(Action::SaveReplay) {
+		return;
+	}
+
+	let (path, recording) = match game_input.recording.as_ref() {
+		Some(recording) => recording,
+		None => {
+			notifications.notify("Not recording, start the game with --record to record a replay");
+			return;
+		}
+	};
+
+	match replay::save_recording(path, recording) {
+		Ok(()) => notifications.notify(format!("Replay of {} frames written to {}", recording.frames.len(), path)),
+		Err(err) => {
+			log::error!("Unable to write the replay to {}: {}", path, err);
+			notifications.notify("Unable to write the replay");
+		}
+	}
+}
+
+/// Records the actions, mouse and modifiers of the frame while the input log is on
+fn record_input(mut log: Mut<InputLog>, input: Const<GameInput>, debug: Const<DebugOptions>) {
+	if !debug.input_log {
+		return;
+	}
+
+	let actions = Action::ALL
+		.iter()
+		.filter_map(|action| {
+			let phase = if input.is_action_activated(*action) {
+				"pressed"
+			} else if input.is_action_deactivated(*action) {
+				"released"
+			} else if input.is_action_hold(*action) {
+				"held"
+			} else {
+				return None;
+			};
+			Some(format!("{:?} {}", action, phase))
+		})
+		.collect();
+
+	log.push(InputEntry {
+		frame: 0,
+		actions,
+		mouse: input.mouse_position(),
+		scroll: input.mouse_scroll(),
+		modifiers: input.modifiers,
+	});
+}
+
+/// Exiles the entities of the terrain chunks outside of the camera frustum while culling is on, and spawns
+/// them again once the chunks are back in view. Only the rendering is affected, the terrain data and the
+/// gameplay keep covering every chunk.
+fn cull_terrain_chunks(
+	mut world: Mut<World>,
+	mut state: Mut<State>,
+	mut stats: Mut<CullingStats>,
+	camera: Const<Camera>,
+	window: Const<Window>,
+	grid: Const<TerrainGrid>,
+	debug: Const<DebugOptions>,
+) {
+	let main_state = match state.get_mut::<MainState>() {
+		Some(main_state) => main_state,
+		None => return,
+	};
+
+	// Frustum follows the camera every frame, so rotating or zooming culls the right chunks
+	let frustum = match window_viewport(&window) {
+		Some(viewport) if debug.culling => Some(picking::Frustum::from_camera(&camera, viewport)),
+		_ => None,
+	};
+	let shown = main_state
+		.chunks
+		.iter()
+		.map(|chunk| match &frustum {
+			Some(frustum) => frustum.intersects_box(chunk.bounds.0, chunk.bounds.1),
+			None => true,
+		})
+		.collect::<Vec<_>>();
+
+	let leaving = world
+		.query::<(&Entity, &Terrain)>()
+		.filter(|(_, terrain)| !shown.get(terrain.chunk).copied().unwrap_or(true))
+		.map(|(entity, _)| *entity)
+		.collect::<Vec<_>>();
+	for entity in leaving {
+		world.exile(entity);
+	}
+
+	let texture = main_state.texture;
+	let (mut visible, mut culled) = (0, 0);
+	for (index, (chunk_mesh, shown)) in main_state
+		.chunks
+		.iter_mut()
+		.zip(shown)
+		.enumerate()
+	{
+		if shown && !chunk_mesh.spawned {
+			spawn_chunk(&mut world, chunk_mesh, index, texture, grid.origin);
+		}
+		chunk_mesh.spawned = shown;
+		if shown {
+			visible += 1;
+		} else {
+			culled += 1;
+		}
+	}
+
+	stats.visible = visible;
+	stats.culled = culled;
+}
+
+/// Advances the gameplay clock by the clamped and scaled frame time, must run before the gameplay systems
+/// Follows the frame time of `GameInput`, so a replay steps the gameplay as the recorded session did
+fn tick_game_clock(mut clock: Mut<GameClock>, time_scale: Const<TimeScale>, input: Const<GameInput>) {
+	let delta = input
+		.delta()
+		.min(MAX_FRAME_DELTA)
+		* time_scale.factor;
+
+	clock.accumulator += delta;
+	clock.ticks = (clock.accumulator / clock.timestep).floor() as u32;
+	clock.accumulator -= clock.frame_time();
+	clock.elapsed += clock.frame_time();
+}
+
+/// Moves the rendered entities between their last two simulated positions, must run after the gameplay systems
+fn interpolate_motion(mut world: Mut<World>, clock: Const<GameClock>) {
+	let alpha = clock.alpha();
+	for (motion, transform) in world.query::<(&Motion, &mut Transform)>() {
+		transform.translate = motion.previous + (motion.current - motion.previous) * alpha;
+	}
+}
+
+fn update_frame_stats(mut stats: Mut<FrameStats>, frame: Const<Frame>) {
+	stats.push(frame.delta().as_secs_f32());
+}
+
+/// Waits out the rest of the frame interval of the frame rate cap, registered after the other systems.
+/// Gameplay follows `GameClock`, which measures the real frame time, so the cap changes how many ticks
+/// a frame steps but not the speed of the game.
+fn limit_frame_rate(settings: Const<Settings>, mut context: Context<FrameLimitContext>) {
+	if let (Some(interval), Some(last_frame)) = (settings.frame_rate_cap.interval(), context.last_frame) {
+		let deadline = last_frame + interval;
+		let now = Instant::now();
+		if deadline > now + FRAME_LIMIT_SPIN {
+			std::thread::sleep(deadline - now - FRAME_LIMIT_SPIN);
+		}
+		while Instant::now() < deadline {
+			std::hint::spin_loop();
+		}
+	}
+
+	context.last_frame = Some(Instant::now());
+}
+
+fn global_control(mut state: Mut<State>, mut window: Mut<Window>, input: Const<GameInput>) {
+	if !input.is_action_activated(Action::Exit) || input.modifiers != dotrix::input::Modifiers::CTRL {
+		return;
+	}
+
+	if state
+		.get::<ConfirmExitState>()
+		.is_none()
+	{
+		state.push(ConfirmExitState {
+			name: String::from("Confirm Exit State"),
+			resume_grab: window.cursor_grab(),
+		});
+		window.set_cursor_grab(false);
+	}
+}
+
+/// Shows the loading progress and pops the loading state once every texture is loaded, or when the
+/// player decides to continue after `LOADING_TIMEOUT`
+fn ui_loading(mut state: Mut<State>, assets: Const<Assets>, overlay: Const<Overlay>, frame: Const<Frame>, theme: Const<UiTheme>) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let loading_state = state
+		.get_mut::<LoadingState>()
+		.expect("Cannot find loading state");
+
+	loading_state.elapsed += frame.delta().as_secs_f32();
+	loading_state
+		.pending
+		.retain(|id| assets.get(*id).is_none());
+
+	let mut done = loading_state.pending.is_empty();
+	let loaded = loading_state.total - loading_state.pending.len();
+	let timed_out = loading_state.elapsed >= LOADING_TIMEOUT;
+
+	egui::containers::Window::new("Loading")
+		.resizable(false)
+		.collapsible(false)
+		.anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.add(
+				egui::ProgressBar::new(loaded as f32 / loading_state.total.max(1) as f32)
+					.text(format!("{} of {} textures", loaded, loading_state.total))
+					.animate(true),
+			);
+			if timed_out {
+				ui.colored_label(theme.warning, format!("{} textures failed to load", loading_state.pending.len()));
+				done |= ui.button("Continue anyway").clicked();
+			}
+		});
+
+	if done {
+		if !loading_state.pending.is_empty() {
+			log::error!("Continuing with {} textures not loaded", loading_state.pending.len());
+		}
+		state.pop_any();
+	}
+}
+
+fn ui_confirm_exit(mut state: Mut<State>, mut window: Mut<Window>, mut input: Mut<GameInput>, overlay: Const<Overlay>) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let mut quit = false;
+	let mut cancel = false;
+
+	egui::containers::Window::new("Quit?")
+		.resizable(false)
+		.collapsible(false)
+		.anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.horizontal(|ui| {
+				quit = ui.button("Yes").clicked();
+				cancel = ui.button("No").clicked();
+			});
+		});
+
+	if quit {
+		std::process::exit(0);
+	}
+
+	if cancel {
+		input.command(UiCommand::CancelExit);
+	}
+
+	if input.is_action_activated(Action::TogglePause) || input.commands().contains(&UiCommand::CancelExit) {
+		let confirm_state = state
+			.get::<ConfirmExitState>()
+			.expect("Cannot find confirm exit state");
+		window.set_cursor_grab(confirm_state.resume_grab);
+		state.pop_any();
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ui_game_over(
+	mut state: Mut<State>,
+	mut world: Mut<World>,
+	mut window: Mut<Window>,
+	mut spawner: Mut<WaveSpawner>,
+	mut economy: Mut<Economy>,
+	mut base_health: Mut<BaseHealth>,
+	mut rng: Mut<Rng>,
+	mut camera: Mut<Camera>,
+	mut selection: Mut<Selection>,
+	mut build: Mut<BuildState>,
+	mut input: Mut<GameInput>,
+	overlay: Const<Overlay>,
+) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let mut restart = false;
+
+	egui::containers::Window::new("Game over")
+		.resizable(false)
+		.collapsible(false)
+		.anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.label("The base has fallen");
+			ui.label(format!("Wave reached: {}", spawner.wave));
+			ui.label(format!("Score: {}", economy.earned));
+			restart = ui.button("Restart").clicked();
+		});
+
+	if restart {
+		input.command(UiCommand::Restart);
+	}
+
+	if !input.commands().contains(&UiCommand::Restart) {
+		return;
+	}
+
+	restart_game(&mut world, &mut spawner, &mut economy, &mut base_health, &mut rng, &mut camera, &mut selection, &mut build);
+	window.set_cursor_grab(true);
+	state.pop_any();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ui_victory(
+	mut state: Mut<State>,
+	mut world: Mut<World>,
+	mut window: Mut<Window>,
+	mut spawner: Mut<WaveSpawner>,
+	mut economy: Mut<Economy>,
+	mut base_health: Mut<BaseHealth>,
+	mut rng: Mut<Rng>,
+	mut camera: Mut<Camera>,
+	mut selection: Mut<Selection>,
+	mut build: Mut<BuildState>,
+	mut input: Mut<GameInput>,
+	overlay: Const<Overlay>,
+) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let mut play_again = false;
+
+	egui::containers::Window::new("Victory")
+		.resizable(false)
+		.collapsible(false)
+		.anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.label("Every wave has been defeated");
+			ui.label(format!("Waves survived: {}", spawner.wave_count()));
+			ui.label(format!("Towers built: {}", economy.towers_built));
+			ui.label(format!("Lives left: {}", base_health.lives));
+			ui.label(format!("Score: {}", economy.victory_score(base_health.lives)));
+			play_again = ui.button("Play Again").clicked();
+		});
+
+	if play_again {
+		input.command(UiCommand::Restart);
+	}
+
+	if !input.commands().contains(&UiCommand::Restart) {
+		return;
+	}
+
+	restart_game(&mut world, &mut spawner, &mut economy, &mut base_health, &mut rng, &mut camera, &mut selection, &mut build);
+	window.set_cursor_grab(true);
+	state.pop_any();
+}
+
+/// Starts the game over from the first wave, terrain, lights and path markers stay as they are and
+/// everything spawned during the game is removed. The camera goes back to the default target and
+/// nothing removed is left selected or in the undo history.
+#[allow(clippy::too_many_arguments)]
+fn restart_game(
+	world: &mut World,
+	spawner: &mut WaveSpawner,
+	economy: &mut Economy,
+	base_health: &mut BaseHealth,
+	rng: &mut Rng,
+	camera: &mut Camera,
+	selection: &mut Selection,
+	build: &mut BuildState,
+) {
+	// Inactive pooled projectiles are queried as well, the pool starts over with the next shots
+	let spawned = world
+		.query::<(&Entity, &Enemy)>()
+		.map(|(entity, _)| *entity)
+		.chain(world.query::<(&Entity, &Tower)>().map(|(entity, _)| *entity))
+		.chain(world.query::<(&Entity, &Projectile)>().map(|(entity, _)| *entity))
+		.chain(world.query::<(&Entity, &SplashRing)>().map(|(entity, _)| *entity))
+		.chain(world.query::<(&Entity, &GhostTower)>().map(|(entity, _)| *entity))
+		.collect::<Vec<_>>();
+	for entity in spawned {
+		world.exile(entity);
+	}
+
+	*selection = Selection::default();
+	*build = BuildState {
+		selected_kind: build.selected_kind,
+		..Default::default()
+	};
+	// The camera stops following an enemy in `player_control` once the enemy is gone
+	let (x, y, z) = DEFAULT_CAMERA_TARGET;
+	camera.target = Point3::new(x, y, z);
+
+	*spawner = spawner.restarted();
+	*economy = Economy::default();
+	*base_health = BaseHealth::default();
+	// The same seed replays the same waves
+	let seed = rng.seed();
+	rng.reseed(seed);
+}
+
+/// Pause hotkey and the side panel with the camera, mouse and selection details, shown while
+/// `DebugOptions::info_panel` is on
+#[allow(clippy::too_many_arguments)]
+fn ui_main(
+	mut state: Mut<State>,
+	input: Const<GameInput>,
+	overlay: Const<Overlay>,
+	debug: Const<DebugOptions>,
+	camera: Const<Camera>,
+	selection: Const<Selection>,
+	grid: Const<TerrainGrid>,
+	theme: Const<UiTheme>,
+) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let main_state = state
+		.get::<MainState>()
+		.expect("Unable to get main state");
+
+	if input.is_action_activated(Action::TogglePause) {
+		state.push(PauseState {
+			name: String::from("Paused State"),
+			handled: false,
+		});
+
+		return;
+	}
+
+	if !debug.info_panel {
+		return;
+	}
+
+	// Terrain vertices around the picked point, `positions` are relative to the grid origin
+	let nearby = selection.point.map(|point| {
+		let local = point - grid.origin;
+		main_state
+			.positions
+			.iter()
+			.map(|p| (p[0] - local.x).hypot(p[2] - local.z))
+			.filter(|distance| *distance <= VERTEX_PICK_RADIUS)
+			.fold((0, f32::MAX), |(count, nearest), distance| (count + 1, nearest.min(distance)))
+	});
+
+	egui::SidePanel::left("Information")
+		.resizable(false)
+		.show(&egui_overlay.ctx, |ui| {
+			ui.colored_label(theme.label, format!("Camera X,Y,Z: [{:.1},{:.1},{:.1}]", camera.target.x, camera.target.y, camera.target.z));
+			match input.mouse_position() {
+				Some(pos) => ui.colored_label(theme.label, format!("Mouse X,Y: [{:.1},{:.1}]", pos.x, pos.y)),
+				None => ui.colored_label(theme.label, "Mouse X,Y: none"),
+			};
+			match nearby {
+				Some((count, nearest)) if count > 0 => ui.colored_label(theme.label, format!("{} vertices nearby, nearest {:.2}", count, nearest)),
+				_ => ui.colored_label(theme.label, "No vertices nearby"),
+			};
+			match selection.cell {
+				Some((x, z)) => ui.colored_label(theme.label, format!("Selected cell X,Z: [{},{}]", x, z)),
+				None => ui.colored_label(theme.label, "Selected cell: none"),
+			};
+		});
+}
+
+/// Status bar along the top of the window with the wave, gold, lives, clock, FPS, camera zoom and the key hint,
+/// and the button starting the next wave before the build countdown runs out, for an early start bonus
+#[allow(clippy::too_many_arguments)]
+fn ui_hud(
+	mut spawner: Mut<WaveSpawner>,
+	mut economy: Mut<Economy>,
+	mut notifications: Mut<Notifications>,
+	mut debug: Mut<DebugOptions>,
+	mut input: Mut<GameInput>,
+	world: Const<World>,
+	time_of_day: Const<TimeOfDay>,
+	base_health: Const<BaseHealth>,
+	overlay: Const<Overlay>,
+	frame: Const<Frame>,
+	camera: Const<Camera>,
+	theme: Const<UiTheme>,
+) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let alive = world
+		.query::<(&Enemy,)>()
+		.count() as u32;
+	let bonus = spawner.early_start_bonus();
+	let (hours, minutes) = time_of_day.clock();
+	let mut start = false;
+
+	egui::TopBottomPanel::top("Status").show(&egui_overlay.ctx, |ui| {
+		ui.horizontal_wrapped(|ui| {
+			ui.colored_label(theme.label, format!("Wave: {} / {}", spawner.wave, spawner.wave_count()));
+			match spawner.phase {
+				WavePhase::Building => {
+					ui.colored_label(theme.label, format!("Next wave in {:.0}s", spawner.countdown.ceil()));
+					start = ui
+						.button(format!("Start Next Wave (+{} gold)", bonus))
+						.clicked();
+				}
+				WavePhase::Spawning => {
+					ui.colored_label(theme.label, format!("Enemies remaining: {}", spawner.remaining() + alive));
+				}
+				WavePhase::Cleared => {
+					ui.colored_label(theme.label, "All waves cleared, victory!");
+				}
+			}
+			ui.separator();
+			ui.colored_label(theme.label, format!("Gold: {}", economy.gold));
+			ui.separator();
+			ui.colored_label(theme.label, format!("Lives: {}", base_health.lives));
+			ui.separator();
+			ui.colored_label(theme.label, format!("Time: {:02}:{:02}", hours, minutes));
+			ui.separator();
+			ui.colored_label(theme.label, format!("FPS: {:.1}", frame.fps()));
+			ui.separator();
+			ui.colored_label(theme.label, format!("Zoom: {:.1} [{:.1}..{:.1}]", camera_zoom(&camera), MIN_ZOOM, MAX_ZOOM));
+			ui.separator();
+			if ui
+				.selectable_label(debug.info_panel, "Info")
+				.clicked()
+			{
+				debug.info_panel = !debug.info_panel;
+			}
+			ui.separator();
+			ui.colored_label(theme.label, "Press ESC to pause and CTRL+C to exit.");
+		});
+	});
+
+	if start {
+		input.command(UiCommand::StartWave);
+	}
+
+	if input.commands().contains(&UiCommand::StartWave) {
+		economy.earn(bonus);
+		spawner.start_wave();
+		if bonus > 0 {
+			notifications.notify(format!("Early start bonus: {} gold", bonus));
+		}
+	}
+}
+
+/// Sets the egui visuals of the theme chosen in the settings, on the first frame and whenever it changes
+fn apply_ui_theme(mut theme: Mut<UiTheme>, mut context: Context<ThemeContext>, settings: Const<Settings>, overlay: Const<Overlay>) {
+	if context.applied == Some(settings.theme) {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	*theme = UiTheme::preset(settings.theme);
+	theme.apply(&egui_overlay.ctx);
+	context.applied = Some(settings.theme);
+}
+
+fn ui_wireframe(state: Const<State>, overlay: Const<Overlay>, camera: Const<Camera>, grid: Const<TerrainGrid>, debug: Const<DebugOptions>) {
+	if !debug.wireframe {
+		return;
+	}
+
+	let main_state = match state.get::<MainState>() {
+		Some(main_state) => main_state,
+		None => return,
+	};
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	// Project straight into egui points, which keeps the overlay correct on scaled displays
+	let screen = egui_overlay
+		.ctx
+		.input()
+		.screen_rect;
+	let viewport = (screen.width(), screen.height());
+	let painter = egui_overlay
+		.ctx
+		.layer_painter(egui::LayerId::background());
+	let stroke = egui::Stroke::new(1.0, DEBUG_YELLOW);
+
+	for triangle in main_state.positions.chunks(3) {
+		let points = triangle
+			.iter()
+			.map(|p| picking::world_to_screen(&camera, Vec3::new(p[0], p[1], p[2]) + grid.origin, viewport))
+			.collect::<Option<Vec<_>>>();
+
+		if let Some(points) = points {
+			for i in 0..points.len() {
+				let (ax, ay) = points[i];
+				let (bx, by) = points[(i + 1) % points.len()];
+				painter.line_segment([egui::pos2(ax, ay), egui::pos2(bx, by)], stroke);
+			}
+		}
+	}
+}
+
+fn ui_build_bar(mut build: Mut<BuildState>, mut input: Mut<GameInput>, economy: Const<Economy>, overlay: Const<Overlay>) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let mut buttons = Vec::new();
+	let mut clicked = None;
+	egui::TopBottomPanel::bottom("Build").show(&egui_overlay.ctx, |ui| {
+		ui.horizontal_wrapped(|ui| {
+			for kind in TowerKind::ALL {
+				ui.vertical(|ui| {
+					let affordable = economy.gold >= kind.cost();
+					ui.set_enabled(affordable);
+					let label = format!("{:?} ({} gold)", kind, kind.cost());
+					let response = ui.selectable_label(build.placing && build.selected_kind == kind, label);
+					if response.clicked() {
+						clicked = Some(kind);
+					}
+					// Towers the player can not afford are skipped by the keyboard too
+					if affordable {
+						buttons.push((kind, response));
+					}
+				});
+			}
+		});
+	});
+
+	let responses = buttons
+		.iter()
+		.map(|(_, response)| response)
+		.collect::<Vec<_>>();
+	if let Some(index) = keyboard_navigation(&egui_overlay.ctx, &responses) {
+		clicked = Some(buttons[index].0);
+	}
+
+	if let Some(kind) = clicked {
+		input.command(UiCommand::SelectTower(kind));
+	}
+
+	for command in input.commands() {
+		if let UiCommand::SelectTower(kind) = command {
+			build.selected_kind = *kind;
+			build.placing = true;
+		}
+	}
+}
+
+/// Moves the keyboard focus between the widgets in their order with the arrow keys, the first arrow
+/// press focuses the first widget. Returns the index of the focused widget if Enter is pressed.
+///
+/// Only the arrow keys and Enter are read, so the menus never respond to the camera keys.
+fn keyboard_navigation(ctx: &egui::CtxRef, widgets: &[&egui::Response]) -> Option<usize> {
+	if widgets.is_empty() {
+		return None;
+	}
+
+	let input = ctx.input();
+	let next = input.key_pressed(egui::Key::ArrowDown) || input.key_pressed(egui::Key::ArrowRight);
+	let previous = input.key_pressed(egui::Key::ArrowUp) || input.key_pressed(egui::Key::ArrowLeft);
+	let enter = input.key_pressed(egui::Key::Enter);
+
+	let focused = widgets
+		.iter()
+		.position(|widget| widget.has_focus());
+	let last = widgets.len() - 1;
+	let target = match focused {
+		Some(index) if next => Some(if index == last { 0 } else { index + 1 }),
+		Some(index) if previous => Some(if index == 0 { last } else { index - 1 }),
+		None if next || previous => Some(0),
+		_ => None,
+	};
+	if let Some(index) = target {
+		widgets[index].request_focus();
+	}
+
+	if enter {
+		focused
+	} else {
+		None
+	}
+}
+
+/// Toggles the sculpt mode and shows the brush sliders while it is on
+fn ui_sculpt(
+	mut build: Mut<BuildState>,
+	mut brush: Mut<SculptBrush>,
+	mut notifications: Mut<Notifications>,
+	input: Const<GameInput>,
+	overlay: Const<Overlay>,
+) {
+	if input.is_action_activated(Action::ToggleSculpt) {
+		build.sculpt = !build.sculpt;
+		build.level_editor = false;
+		notifications.notify(if build.sculpt { "Sculpt mode on" } else { "Sculpt mode off" });
+	}
+
+	if !build.sculpt {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	egui::containers::Window::new("Sculpt")
+		.resizable(false)
+		.anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(16.0, -16.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.label("Left mouse button raises, right one lowers");
+			ui.add(egui::Slider::new(&mut brush.radius, sculpt::RADIUS_RANGE).text("Brush radius"));
+			ui.add(egui::Slider::new(&mut brush.strength, sculpt::STRENGTH_RANGE).text("Strength"));
+			ui.horizontal(|ui| {
+				ui.label("Falloff:");
+				for falloff in BrushFalloff::ALL {
+					ui.radio_value(&mut brush.falloff, falloff, format!("{:?}", falloff));
+				}
+			});
+		});
+}
+
+/// Toggles the level editor and shows the brushes and the save button while it is on
+fn ui_level_editor(
+	mut build: Mut<BuildState>,
+	mut brush: Mut<LevelBrush>,
+	mut notifications: Mut<Notifications>,
+	nav_grid: Const<NavGrid>,
+	input: Const<GameInput>,
+	overlay: Const<Overlay>,
+) {
+	if input.is_action_activated(Action::ToggleEditor) {
+		build.level_editor = !build.level_editor;
+		build.sculpt = false;
+		build.placing = false;
+		notifications.notify(if build.level_editor { "Level editor on" } else { "Level editor off" });
+	}
+
+	if !build.level_editor {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let mut save = false;
+
+	egui::containers::Window::new("Level editor")
+		.resizable(false)
+		.anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(16.0, -16.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.label("Left mouse button paints the brush");
+			ui.horizontal(|ui| {
+				ui.label("Brush:");
+				for kind in LevelBrush::ALL {
+					ui.radio_value(&mut *brush, kind, format!("{:?}", kind));
+				}
+			});
+			ui.label(format!("Spawn {:?}, goal {:?}, walls: {}", nav_grid.spawn, nav_grid.goal, nav_grid.walls().len()));
+			save = ui.button("Save level").clicked();
+		});
+
+	if save {
+		match level::save_level(level::LEVEL_PATH, &Level::from_nav_grid(&nav_grid)) {
+			Ok(()) => notifications.notify("Level saved"),
+			Err(err) => {
+				log::error!("Unable to save the level to {}: {}", level::LEVEL_PATH, err);
+				notifications.notify("Unable to save the level");
+			}
+		}
+	}
+}
+
+/// Stats and reload progress of the tower under the cursor next to the mouse pointer
+fn ui_tower_tooltip(world: Const<World>, overlay: Const<Overlay>, selection: Const<Selection>, clock: Const<GameClock>) {
+	let hovered = match selection.tower {
+		Some(hovered) => hovered,
+		None => return,
+	};
+
+	let tower = match world
+		.query::<(&Entity, &Tower)>()
+		.find(|(entity, _)| **entity == hovered)
+	{
+		Some((_, tower)) => tower,
+		None => return,
+	};
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let pointer = match egui_overlay
+		.ctx
+		.input()
+		.pointer
+		.hover_pos()
+	{
+		Some(pointer) => pointer,
+		None => return,
+	};
+
+	// Not interactable, so the tooltip does not count as a window hiding the terrain from picking
+	egui::Area::new("Tower tooltip")
+		.fixed_pos(pointer + egui::vec2(16.0, 16.0))
+		.order(egui::Order::Tooltip)
+		.interactable(false)
+		.show(&egui_overlay.ctx, |ui| {
+			egui::Frame::popup(ui.style()).show(ui, |ui| {
+				let stats = tower.stats();
+				ui.label(format!("{:?} tower, level {}/{}", tower.kind, tower.level, towers::MAX_LEVEL));
+				match tower.upgraded_stats() {
+					Some(upgraded) => {
+						ui.label(format!("Range: {:.1} (+{:.1})", stats.range, upgraded.range - stats.range));
+						ui.label(format!("Damage: {:.0} (+{:.0})", stats.damage, upgraded.damage - stats.damage));
+						ui.label(format!(
+							"Fire interval: {:.2}s (-{:.2}s)",
+							stats.fire_interval,
+							stats.fire_interval - upgraded.fire_interval
+						));
+					}
+					None => {
+						ui.label(format!("Range: {:.1}", stats.range));
+						ui.label(format!("Damage: {:.0}", stats.damage));
+						ui.label(format!("Fire interval: {:.2}s", stats.fire_interval));
+					}
+				}
+				ui.label(format!("Kills: {}", tower.kills));
+				// Cooldown runs on the game clock, so the bar stops while the game is paused or at 0x speed
+				let reload = if tower.cooldown > 0.0 {
+					format!("Next shot in {:.2}s", tower.cooldown)
+				} else {
+					String::from("Ready to fire")
+				};
+				ui.add(
+					egui::ProgressBar::new(tower.reload_fraction())
+						.desired_width(120.0)
+						.text(reload),
+				);
+				if let Some(last_fired) = tower.last_fired {
+					ui.label(format!("Last shot {:.1}s ago", (clock.elapsed - last_fired).max(0.0)));
+				}
+				match tower.upgrade_cost() {
+					Some(cost) => ui.label(format!("Upgrade: {} gold", cost)),
+					None => ui.label("Max level"),
+				};
+			});
+		});
+}
+
+/// Selects the towers within the rectangle dragged with the left mouse button, SHIFT adds them to the selection.
+/// Runs before `place_tower`, which ignores the release of a drag.
+fn ui_box_select(
+	world: Const<World>,
+	input: Const<GameInput>,
+	overlay: Const<Overlay>,
+	camera: Const<Camera>,
+	build: Const<BuildState>,
+	mut selection: Mut<Selection>,
+) {
+	if build.editing() {
+		selection.drag = None;
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let pointer = egui_overlay
+		.ctx
+		.input()
+		.pointer
+		.hover_pos()
+		.map(|pointer| Vec2::new(pointer.x, pointer.y));
+
+	if input.is_action_activated(Action::PlaceTower) {
+		// Presses on the windows must not start a drag over the terrain behind them
+		selection.drag = match pointer {
+			Some(start) if !egui_overlay.ctx.is_pointer_over_area() => Some(BoxDrag {
+				start,
+				end: start,
+			}),
+			_ => None,
+		};
+		return;
+	}
+
+	let drag = match selection.drag.as_mut() {
+		Some(drag) => drag,
+		None => return,
+	};
+
+	if input.is_action_hold(Action::PlaceTower) {
+		if let Some(end) = pointer {
+			drag.end = end;
+		}
+		if drag.is_box() {
+			let rect = egui::Rect::from_two_pos(egui::pos2(drag.start.x, drag.start.y), egui::pos2(drag.end.x, drag.end.y));
+			let painter = egui_overlay
+				.ctx
+				.layer_painter(egui::LayerId::background());
+			painter.rect_filled(rect, 0.0, egui::Color32::from_rgba_unmultiplied(120, 180, 255, 40));
+			painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::LIGHT_BLUE));
+		}
+		return;
+	}
+
+	if !input.is_action_deactivated(Action::PlaceTower) || !drag.is_box() {
+		return;
+	}
+
+	let drag = *drag;
+	let screen = egui_overlay
+		.ctx
+		.input()
+		.screen_rect;
+	let viewport = (screen.width(), screen.height());
+
+	let picked = world
+		.query::<(&Entity, &Tower, &Transform)>()
+		.filter(|(_, _, transform)| match picking::world_to_screen(&camera, transform.translate, viewport) {
+			Some(point) => drag.contains(point),
+			None => false,
+		})
+		.map(|(entity, _, _)| *entity)
+		.collect::<Vec<_>>();
+
+	if !input.modifiers.shift() {
+		selection.towers.clear();
+	}
+	selection.towers.extend(picked);
+}
+
+/// Sets the targeting priority of and upgrades every tower selected by `ui_box_select`, the commands
+/// are applied by `apply_tower_commands`
+fn ui_tower_priority(
+	mut selection: Mut<Selection>,
+	mut input: Mut<GameInput>,
+	world: Const<World>,
+	economy: Const<Economy>,
+	overlay: Const<Overlay>,
+) {
+	if selection.towers.is_empty() {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let priorities = world
+		.query::<(&Entity, &Tower)>()
+		.filter(|(entity, _)| selection.towers.contains(entity))
+		.map(|(_, tower)| tower.priority)
+		.collect::<Vec<_>>();
+	// Towers at the max level are left out of the upgrade
+	let upgrade_cost = world
+		.query::<(&Entity, &Tower)>()
+		.filter(|(entity, _)| selection.towers.contains(entity))
+		.filter_map(|(_, tower)| tower.upgrade_cost())
+		.sum::<u32>();
+	let upgradable = world
+		.query::<(&Entity, &Tower)>()
+		.any(|(entity, tower)| selection.towers.contains(entity) && tower.upgrade_cost().is_some());
+	// Selected towers may have been sold or removed on restart
+	if priorities.is_empty() {
+		selection.towers.clear();
+		return;
+	}
 
-	let dy = if input.mouse_scroll() > 0.0 {
-		SCROLL_SPEED * frame.delta().as_secs_f32()
-	} else if input.mouse_scroll() < 0.0 {
-		-(SCROLL_SPEED * frame.delta().as_secs_f32())
-	} else {
-		0.0
+	// Shared priority, `None` if the selected towers target differently
+	let shared = match priorities.first() {
+		Some(first) if priorities.iter().all(|priority| priority == first) => Some(*first),
+		_ => None,
 	};
 
-	let pos_x = camera.target.x - dx;
-	let pos_z = camera.target.z - dz;
-	let pos_y = camera.target.y - dy;
+	let mut chosen = None;
+	let mut upgrade = false;
+	let mut deselect = false;
 
-	camera.target = Point3::new(pos_x, pos_y, pos_z);
+	egui::containers::Window::new("Selected towers")
+		.resizable(false)
+		.collapsible(false)
+		.anchor(egui::Align2::LEFT_CENTER, egui::vec2(16.0, 0.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.label(format!("{} towers selected", priorities.len()));
+			ui.label("Target priority:");
+			for priority in TargetPriority::ALL {
+				if ui
+					.radio(shared == Some(priority), format!("{:?}", priority))
+					.clicked()
+				{
+					chosen = Some(priority);
+				}
+			}
+			if upgradable {
+				ui.vertical(|ui| {
+					ui.set_enabled(economy.gold >= upgrade_cost);
+					upgrade = ui
+						.button(format!("Upgrade ({} gold)", upgrade_cost))
+						.clicked();
+				});
+			} else {
+				ui.label("Max level");
+			}
+			deselect = ui.button("Deselect").clicked();
+		});
+
+	let cells = world
+		.query::<(&Entity, &Tower)>()
+		.filter(|(entity, _)| selection.towers.contains(entity))
+		.map(|(_, tower)| tower.cell)
+		.collect::<Vec<_>>();
+
+	if let Some(priority) = chosen {
+		input.command(UiCommand::TargetPriority {
+			cells: cells.clone(),
+			priority,
+		});
+	}
+
+	if upgrade {
+		input.command(UiCommand::Upgrade {
+			cells,
+		});
+	}
+
+	if deselect {
+		selection.towers.clear();
+	}
 }
 
-fn global_control(input: Const<Input>) {
-	if input.is_action_activated(Action::Exit) && input.modifiers == dotrix::input::Modifiers::CTRL {
-		std::process::exit(0);
+/// Applies the priorities and upgrades given in `ui_tower_priority` or by a replay to the towers on their cells
+fn apply_tower_commands(mut world: Mut<World>, mut assets: Mut<Assets>, mut economy: Mut<Economy>, input: Const<GameInput>) {
+	for command in input.commands() {
+		match command {
+			UiCommand::TargetPriority {
+				cells,
+				priority,
+			} => {
+				for (tower,) in world.query::<(&mut Tower,)>() {
+					if cells.contains(&tower.cell) {
+						tower.priority = *priority;
+					}
+				}
+			}
+			UiCommand::Upgrade {
+				cells,
+			} => {
+				// Towers at the max level are left out of the upgrade
+				let cost = world
+					.query::<(&Tower,)>()
+					.filter(|(tower,)| cells.contains(&tower.cell))
+					.filter_map(|(tower,)| tower.upgrade_cost())
+					.sum::<u32>();
+				if !economy.spend(cost) {
+					continue;
+				}
+
+				let upgraded = world
+					.query::<(&mut Tower, &mut pbr::Model, &mut pbr::Material)>()
+					.filter(|(tower, _, _)| cells.contains(&tower.cell) && tower.upgrade_cost().is_some());
+				for (tower, model, material) in upgraded {
+					tower.upgrade();
+					model.mesh = assets.register(&tower.mesh_name());
+					material.albedo = tower.color();
+				}
+			}
+			_ => {}
+		}
 	}
 }
 
-fn ui_main(mut state: Mut<State>, input: Const<Input>, overlay: Const<Overlay>, frame: Const<Frame>, camera: Const<Camera>) {
+/// Outlines the towers selected by `ui_box_select` with the screen rectangle around their boxes
+fn ui_selected_towers(world: Const<World>, overlay: Const<Overlay>, camera: Const<Camera>, selection: Const<Selection>) {
+	if selection.towers.is_empty() {
+		return;
+	}
+
 	let egui_overlay = overlay
 		.get::<Egui>()
 		.expect("Egui overlay must be added at startup");
 
-	let main_state = state
-		.get::<MainState>()
-		.expect("Unable to get main state");
+	let screen = egui_overlay
+		.ctx
+		.input()
+		.screen_rect;
+	let viewport = (screen.width(), screen.height());
+	let painter = egui_overlay
+		.ctx
+		.layer_painter(egui::LayerId::background());
+	let style = LineStyle::new(OUTLINE_WIDTH, egui::Color32::LIGHT_BLUE);
 
-	if input.is_action_activated(Action::TogglePause) {
-		state.push(PauseState {
-			name: String::from("Paused State"),
-			handled: false,
+	for (entity, tower, transform) in world.query::<(&Entity, &Tower, &Transform)>() {
+		if !selection.towers.contains(entity) {
+			continue;
+		}
+
+		let (min, max) = tower.bounds(transform.translate);
+		let corners = (0..8)
+			.map(|i| {
+				let corner = Vec3::new(
+					if i & 1 == 0 { min.x } else { max.x },
+					if i & 2 == 0 { min.y } else { max.y },
+					if i & 4 == 0 { min.z } else { max.z },
+				);
+				picking::world_to_screen(&camera, corner, viewport)
+			})
+			.collect::<Option<Vec<_>>>();
+
+		// Towers partly behind the camera are not outlined
+		if let Some(corners) = corners {
+			let (min, max) = corners
+				.iter()
+				.fold((egui::pos2(f32::MAX, f32::MAX), egui::pos2(f32::MIN, f32::MIN)), |(min, max), (x, y)| {
+					(egui::pos2(min.x.min(*x), min.y.min(*y)), egui::pos2(max.x.max(*x), max.y.max(*y)))
+				});
+			let outline = [min, egui::pos2(max.x, min.y), max, egui::pos2(min.x, max.y)].map(Some);
+			lines::draw_polyline(&painter, &outline, true, style);
+		}
+	}
+}
+
+/// Rings the range of the tower under the cursor, or of the selected kind if the hovered cell is free
+fn update_range_ring(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	grid: Const<TerrainGrid>,
+	selection: Const<Selection>,
+	build: Const<BuildState>,
+) {
+	let range = selection.cell.and_then(|cell| {
+		world
+			.query::<(&Tower,)>()
+			.find(|(tower,)| tower.cell == cell)
+			.map(|(tower,)| tower.stats().range)
+			.or_else(|| build.placing.then(|| build.selected_kind.range()))
+			.map(|range| (cell, range))
+	});
+
+	for (ring, transform) in world.query::<(&mut RangeRing, &mut Transform)>() {
+		let (cell, range) = match range {
+			Some(range) => range,
+			None => {
+				transform.scale = Vec3::new(0.0, 0.0, 0.0);
+				continue;
+			}
+		};
+		if ring.range != range {
+			if let Some(mesh) = assets.get_mut(ring.mesh) {
+				*mesh = mesh_gen::ring(range, RANGE_RING_SEGMENTS);
+			}
+			ring.range = range;
+		}
+		let center = grid.cell_to_world_center(cell);
+		transform.translate = Vec3::new(center.x, TERRAIN_BASE_HEIGHT + HIGHLIGHT_OFFSET, center.z);
+		transform.scale = Vec3::new(1.0, 1.0, 1.0);
+	}
+}
+
+fn ui_health_bars(world: Const<World>, overlay: Const<Overlay>, camera: Const<Camera>) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let screen = egui_overlay
+		.ctx
+		.input()
+		.screen_rect;
+	let viewport = (screen.width(), screen.height());
+	let painter = egui_overlay
+		.ctx
+		.layer_painter(egui::LayerId::background());
+	let (width, height) = HEALTH_BAR_SIZE;
+
+	for (enemy, transform) in world.query::<(&Enemy, &Transform)>() {
+		let point = transform.translate + Vec3::new(0.0, HEALTH_BAR_ELEVATION, 0.0);
+		let (x, y) = match picking::world_to_screen(&camera, point, viewport) {
+			Some(position) => position,
+			None => continue,
+		};
+		if !screen.contains(egui::pos2(x, y)) {
+			continue;
+		}
+
+		let ratio = (enemy.health / enemy.max_health).clamp(0.0, 1.0);
+		let bar = egui::Rect::from_min_size(egui::pos2(x - width / 2.0, y - height / 2.0), egui::vec2(width, height));
+		let fill = egui::Rect::from_min_size(bar.min, egui::vec2(width * ratio, height));
+
+		painter.rect_filled(bar, 0.0, egui::Color32::from_black_alpha(160));
+		painter.rect_filled(fill, 0.0, egui::Color32::from_rgb(60, 200, 60));
+	}
+}
+
+fn ui_minimap(world: Const<World>, overlay: Const<Overlay>, grid: Const<TerrainGrid>, mut camera: Mut<Camera>, mut input: Mut<GameInput>) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	// The longer side of the terrain spans the whole minimap
+	let (extent_x, extent_z) = grid.extent();
+	let scale = MINIMAP_SIZE / extent_x.max(extent_z);
+	let mut clicked = None;
+
+	egui::containers::Window::new("Minimap")
+		.resizable(false)
+		.collapsible(false)
+		.anchor(egui::Align2::RIGHT_TOP, egui::vec2(-16.0, 16.0))
+		.show(&egui_overlay.ctx, |ui| {
+			let (rect, response) = ui.allocate_exact_size(egui::vec2(extent_x * scale, extent_z * scale), egui::Sense::click());
+			let painter = ui.painter();
+			let to_map = |x: f32, z: f32| egui::pos2(rect.min.x + (x - grid.origin.x) * scale, rect.min.y + (z - grid.origin.z) * scale);
+
+			painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(160));
+			painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::GRAY));
+
+			for (transform, _) in world.query::<(&Transform, &Tower)>() {
+				painter.circle_filled(to_map(transform.translate.x, transform.translate.z), 3.0, egui::Color32::LIGHT_BLUE);
+			}
+
+			painter.circle_stroke(to_map(camera.target.x, camera.target.z), 4.0, egui::Stroke::new(2.0, DEBUG_YELLOW));
+
+			if response.clicked() {
+				clicked = response.interact_pointer_pos().map(|pos| {
+					(grid.origin.x + (pos.x - rect.min.x) / scale, grid.origin.z + (pos.y - rect.min.y) / scale)
+				});
+			}
 		});
 
+	if let Some((x, z)) = clicked {
+		input.command(UiCommand::FocusCamera(x, z));
+	}
+
+	for command in input.commands() {
+		if let UiCommand::FocusCamera(x, z) = command {
+			camera.target = Point3::new(*x, camera.target.y, *z);
+		}
+	}
+}
+
+/// Entity counts by kind, dotrix does not report draw calls so the totals are all there is
+#[allow(clippy::too_many_arguments)]
+fn ui_debug_stats(
+	world: Const<World>,
+	overlay: Const<Overlay>,
+	debug: Const<DebugOptions>,
+	culling: Const<CullingStats>,
+	frame_stats: Const<FrameStats>,
+	settings: Const<Settings>,
+	rng: Const<Rng>,
+	theme: Const<UiTheme>,
+) {
+	if !debug.stats {
 		return;
 	}
 
-	egui::Area::new("Information")
-		.fixed_pos(egui::pos2(16.0, 16.0))
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let counts = [
+		("Terrain", world.query::<(&Terrain,)>().count()),
+		("Towers", world.query::<(&Tower,)>().count()),
+		("Enemies", world.query::<(&Enemy,)>().count()),
+		(
+			"Projectiles",
+			world
+				.query::<(&Projectile,)>()
+				.filter(|(projectile,)| projectile.active)
+				.count(),
+		),
+		("Lights", world.query::<(&Light,)>().count()),
+	];
+
+	egui::Area::new("Debug stats")
+		.anchor(egui::Align2::LEFT_TOP, egui::vec2(16.0, 16.0))
+		.show(&egui_overlay.ctx, |ui| {
+			for (label, count) in counts.iter() {
+				ui.colored_label(theme.label, format!("{}: {}", label, count));
+			}
+			let culling_state = if debug.culling {
+				"on"
+			} else {
+				"off"
+			};
+			ui.colored_label(
+				theme.label,
+				format!("Chunks: {} visible, {} culled (culling {})", culling.visible, culling.culled, culling_state),
+			);
+			ui.colored_label(
+				theme.label,
+				format!("Effective FPS: {:.1} (cap {})", frame_stats.average_fps(), settings.frame_rate_cap.label()),
+			);
+			ui.colored_label(theme.label, format!("Seed: {}", rng.seed()));
+		});
+}
+
+/// Last frames of the `InputLog`, newest first, with buttons to write the whole log to a file or clear it
+fn ui_input_log(mut log: Mut<InputLog>, mut notifications: Mut<Notifications>, overlay: Const<Overlay>, debug: Const<DebugOptions>) {
+	if !debug.input_log {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let mut dump = false;
+	let mut clear = false;
+	egui::containers::Window::new("Input log")
+		.resizable(false)
+		.anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-16.0, -16.0))
 		.show(&egui_overlay.ctx, |ui| {
-			ui.colored_label(DEBUG_YELLOW, "Press ESC to pause and CTRL+C to exit.");
+			ui.horizontal(|ui| {
+				dump = ui
+					.button(format!("Dump to {}", input_log::INPUT_LOG_PATH))
+					.clicked();
+				clear = ui.button("Clear").clicked();
+			});
+			ui.separator();
+			for entry in log
+				.entries()
+				.rev()
+				.take(INPUT_LOG_LINES)
+			{
+				ui.monospace(entry.to_string());
+			}
 		});
 
-	egui::Area::new("FPS Counter")
-		.fixed_pos(egui::pos2(16.0, 32.0))
+	if dump {
+		match log.dump(input_log::INPUT_LOG_PATH) {
+			Ok(()) => notifications.notify(format!("Input log written to {}", input_log::INPUT_LOG_PATH)),
+			Err(err) => {
+				log::error!("Unable to write {}: {}", input_log::INPUT_LOG_PATH, err);
+				notifications.notify("Unable to write the input log");
+			}
+		}
+	}
+	if clear {
+		log.clear();
+	}
+}
+
+/// Developer console of the `dev` builds, its commands change the game directly. Gameplay hotkeys are
+/// muted while it is open, see `GameInput::console_open`.
+#[cfg(feature = "dev")]
+#[allow(clippy::too_many_arguments)]
+fn ui_console(
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut console: Mut<console::Console>,
+	mut game_input: Mut<GameInput>,
+	mut economy: Mut<Economy>,
+	mut base_health: Mut<BaseHealth>,
+	mut camera: Mut<Camera>,
+	mut rng: Mut<Rng>,
+	path: Const<Path>,
+	overlay: Const<Overlay>,
+) {
+	if game_input.is_action_activated(Action::ToggleConsole) {
+		console.open = !console.open;
+	}
+	game_input.console_open = console.open;
+	if !console.open {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let (submit, previous, next) = {
+		let input = egui_overlay.ctx.input();
+		(input.key_pressed(egui::Key::Enter), input.key_pressed(egui::Key::ArrowUp), input.key_pressed(egui::Key::ArrowDown))
+	};
+
+	egui::containers::Window::new("Console")
+		.resizable(false)
+		.anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(16.0, -16.0))
 		.show(&egui_overlay.ctx, |ui| {
-			ui.colored_label(DEBUG_YELLOW, format!("FPS: {:.1}", frame.fps()));
+			let skipped = console
+				.output
+				.len()
+				.saturating_sub(CONSOLE_LINES);
+			for line in console
+				.output
+				.iter()
+				.skip(skipped)
+			{
+				ui.monospace(line);
+			}
+			let response = ui.add(egui::TextEdit::singleline(&mut console.input).desired_width(CONSOLE_WIDTH));
+			response.request_focus();
 		});
 
-	egui::Area::new("Camera")
-		.fixed_pos(egui::pos2(16.0, 48.0))
+	// The toggle key types its character into the focused input
+	console
+		.input
+		.retain(|c| c != '`');
+
+	if previous {
+		console.history_previous();
+	} else if next {
+		console.history_next();
+	}
+
+	if !submit {
+		return;
+	}
+	let line = match console.submit() {
+		Some(line) => line,
+		None => return,
+	};
+	match console::parse(&line) {
+		Ok(console::Command::Gold(amount)) => {
+			economy.gold = amount;
+			console.print(format!("Gold set to {}", amount));
+		}
+		Ok(console::Command::Spawn(kind, count)) => {
+			let spawn_point = match path.waypoints.first() {
+				Some(spawn_point) => *spawn_point,
+				None => {
+					console.print("No path to spawn enemies on");
+					return;
+				}
+			};
+			for _ in 0..count {
+				let offset = enemies::lateral_offset(&path.waypoints, rng.range(-enemies::SPAWN_JITTER, enemies::SPAWN_JITTER));
+				let enemy = Enemy::new(kind, 1.0, path.waypoints.clone()).leaving_spawn();
+				spawn_enemy(&mut world, &mut assets, enemy, spawn_point + offset);
+			}
+			console.print(format!("Spawned {} {:?}", count, kind));
+		}
+		Ok(console::Command::Clear) => {
+			let enemies = world
+				.query::<(&Entity, &Enemy)>()
+				.map(|(entity, _)| *entity)
+				.collect::<Vec<_>>();
+			console.print(format!("Removed {} enemies", enemies.len()));
+			for entity in enemies {
+				world.exile(entity);
+			}
+		}
+		Ok(console::Command::Teleport(x, z)) => {
+			camera.target.x = x;
+			camera.target.z = z;
+			console.print(format!("Camera moved to {:.1} {:.1}", x, z));
+		}
+		Ok(console::Command::God) => {
+			base_health.invulnerable = !base_health.invulnerable;
+			console.print(if base_health.invulnerable { "God mode on" } else { "God mode off" });
+		}
+		Ok(console::Command::Help) => console.print(console::HELP),
+		Err(message) => console.print(format!("Error: {}", message)),
+	}
+}
+
+/// Tints every cell from green for the base cost to red for the maximal one, impassable cells are black
+fn ui_path_costs(overlay: Const<Overlay>, camera: Const<Camera>, nav_grid: Const<NavGrid>, debug: Const<DebugOptions>) {
+	if !debug.costs {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let screen = egui_overlay
+		.ctx
+		.input()
+		.screen_rect;
+	let viewport = (screen.width(), screen.height());
+	let painter = egui_overlay
+		.ctx
+		.layer_painter(egui::LayerId::background());
+
+	let grid = &nav_grid.grid;
+	for x in 0..grid.width as i32 {
+		for z in 0..grid.depth as i32 {
+			let cost = nav_grid.cost((x, z));
+			let fill = if cost == pathfinding::IMPASSABLE {
+				egui::Color32::from_black_alpha(160)
+			} else {
+				let t = (cost - pathfinding::BASE_COST) as f32 / (pathfinding::MAX_COST - pathfinding::BASE_COST) as f32;
+				egui::Color32::from_rgba_unmultiplied((255.0 * t) as u8, (255.0 * (1.0 - t)) as u8, 0, 80)
+			};
+
+			if let Some(corners) = cell_on_screen(&camera, grid, (x, z), viewport) {
+				painter.add(egui::Shape::convex_polygon(corners, fill, egui::Stroke::new(0.0, fill)));
+			}
+		}
+	}
+}
+
+/// Corners of the cell at the terrain base projected into egui points, `None` if any is behind the camera
+fn cell_on_screen(camera: &Camera, grid: &TerrainGrid, (x, z): (i32, i32), viewport: (f32, f32)) -> Option<Vec<egui::Pos2>> {
+	[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+		.iter()
+		.map(|(dx, dz)| {
+			let point = grid.origin + Vec3::new((x as f32 + dx) * grid.tile_scale, TERRAIN_BASE_HEIGHT, (z as f32 + dz) * grid.tile_scale);
+			picking::world_to_screen(camera, point, viewport).map(|(x, y)| egui::pos2(x, y))
+		})
+		.collect()
+}
+
+/// Tints the cells by their state for debugging the placement rules: red under a tower, blue on the
+/// enemy path and green where a tower can be built. The buildable cells are searched again only when
+/// the blocked cells change, the towers and the path are read every frame.
+fn ui_tile_states(
+	mut context: Context<TileStatesContext>,
+	world: Const<World>,
+	overlay: Const<Overlay>,
+	camera: Const<Camera>,
+	nav_grid: Const<NavGrid>,
+	path: Const<Path>,
+	debug: Const<DebugOptions>,
+) {
+	if !debug.tiles {
+		return;
+	}
+
+	let grid = &nav_grid.grid;
+	if context.revision != Some(nav_grid.revision) {
+		context.revision = Some(nav_grid.revision);
+		context.buildable = (0..grid.width as i32)
+			.flat_map(|x| (0..grid.depth as i32).map(move |z| (x, z)))
+			.filter(|cell| !nav_grid.blocked.contains(cell) && nav_grid.can_block(*cell))
+			.collect();
+	}
+
+	let towers = world
+		.query::<(&Tower,)>()
+		.map(|(tower,)| tower.cell)
+		.collect::<HashSet<_>>();
+	let on_path = path
+		.waypoints
+		.iter()
+		.filter_map(|waypoint| grid.world_to_cell(*waypoint))
+		.collect::<HashSet<_>>();
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let screen = egui_overlay
+		.ctx
+		.input()
+		.screen_rect;
+	let viewport = (screen.width(), screen.height());
+	let painter = egui_overlay
+		.ctx
+		.layer_painter(egui::LayerId::background());
+
+	for x in 0..grid.width as i32 {
+		for z in 0..grid.depth as i32 {
+			let cell = (x, z);
+			let fill = if towers.contains(&cell) {
+				TILE_STATE_TOWER
+			} else if on_path.contains(&cell) {
+				TILE_STATE_PATH
+			} else if context.buildable.contains(&cell) {
+				TILE_STATE_BUILDABLE
+			} else {
+				continue;
+			};
+
+			if let Some(corners) = cell_on_screen(&camera, grid, cell, viewport) {
+				painter.add(egui::Shape::convex_polygon(corners, fill, egui::Stroke::new(0.0, fill)));
+			}
+		}
+	}
+}
+
+/// Line through the waypoints of the enemy path from the spawn to the goal. The `Path` is replaced by
+/// `update_navigation` whenever it is recomputed, so the line follows every reroute around the towers.
+fn ui_enemy_path(overlay: Const<Overlay>, camera: Const<Camera>, path: Const<Path>, debug: Const<DebugOptions>) {
+	if !debug.path {
+		return;
+	}
+
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let screen = egui_overlay
+		.ctx
+		.input()
+		.screen_rect;
+	let viewport = (screen.width(), screen.height());
+	let painter = egui_overlay
+		.ctx
+		.layer_painter(egui::LayerId::background());
+
+	let points = path
+		.waypoints
+		.iter()
+		.map(|waypoint| {
+			let point = Vec3::new(waypoint.x, TERRAIN_BASE_HEIGHT + HIGHLIGHT_OFFSET, waypoint.z);
+			picking::world_to_screen(&camera, point, viewport).map(|(x, y)| egui::pos2(x, y))
+		})
+		.collect::<Vec<_>>();
+
+	lines::draw_polyline(&painter, &points, false, LineStyle::new(ENEMY_PATH_WIDTH, ENEMY_PATH_COLOR));
+}
+
+fn ui_notifications(mut notifications: Mut<Notifications>, overlay: Const<Overlay>, frame: Const<Frame>, theme: Const<UiTheme>) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	if notifications.queue.is_empty() {
+		return;
+	}
+
+	egui::Area::new("Notifications")
+		.anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-16.0, -16.0))
 		.show(&egui_overlay.ctx, |ui| {
-			ui.colored_label(DEBUG_YELLOW, format!("Camera X,Y,Z: [{:.1},{:.1},{:.1}]", camera.target.x, camera.target.y, camera.target.z));
+			for (message, _) in notifications.queue.iter() {
+				ui.colored_label(theme.warning, message);
+			}
+		});
+
+	let delta = frame.delta().as_secs_f32();
+	for (_, remaining) in notifications.queue.iter_mut() {
+		*remaining -= delta;
+	}
+	notifications
+		.queue
+		.retain(|(_, remaining)| *remaining > 0.0);
+}
+
+/// Binds the next pressed key to the action, unless another action already uses it
+fn capture_key_binding(input: &mut Input, settings_state: &mut SettingsState, action: Action) {
+	let key_code = input
+		.events
+		.iter()
+		.find_map(|event| match event {
+			dotrix::input::Event::Key(event) if event.pressed => Some(event.key_code),
+			_ => None,
 		});
 
-	let ms = main_state.clone();
-	egui::Area::new("Mouse")
-		.fixed_pos(egui::pos2(16.0, 64.0))
+	let key_code = match key_code {
+		Some(key_code) => key_code,
+		None => return,
+	};
+
+	if key_code == KeyCode::Escape {
+		settings_state.rebinding = None;
+		return;
+	}
+
+	let button = Button::Key(key_code);
+	let conflict = Action::ALL
+		.iter()
+		.find(|other| **other != action && input.action_mapped(**other) == Some(&button));
+
+	if let Some(other) = conflict {
+		settings_state.rebind_warning = Some(format!("{:?} is already bound to {:?}", key_code, other));
+		return;
+	}
+
+	input
+		.mapper_mut::<Mapper<Action>>()
+		.set(vec![(action, button)]);
+	settings_state.rebinding = None;
+	settings_state.rebind_warning = None;
+}
+
+/// Starts a new game, loads the saved one or opens the settings. The world is spawned only once a game
+/// is started, `load_control` restores the save on the first frame of the main state unless it is a new game.
+#[allow(clippy::too_many_arguments)]
+fn ui_menu(
+	mut state: Mut<State>,
+	mut world: Mut<World>,
+	mut assets: Mut<Assets>,
+	mut window: Mut<Window>,
+	mut skyboxes: Mut<SkyBoxes>,
+	mut game_input: Mut<GameInput>,
+	terrain_config: Const<TerrainConfig>,
+	overlay: Const<Overlay>,
+) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let saved = std::path::Path::new(save::SAVE_PATH).exists();
+	let mut buttons = Vec::new();
+
+	egui::containers::Window::new("Menu")
+		.resizable(false)
+		.collapsible(false)
+		.anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
 		.show(&egui_overlay.ctx, |ui| {
-			let pos = input.mouse_position().unwrap();
-			let ms.positions.filter(|p| p.x );
-			ui.colored_label(DEBUG_YELLOW, format!("Mouse X,Y: [{:.1},{:.1}]", pos.x, pos.y));
+			buttons.push(ui.button("New Game"));
+			buttons.push(ui.add(egui::Button::new("Load").enabled(saved)));
+			buttons.push(ui.button("Settings"));
+			buttons.push(ui.button("Quit"));
+		});
+
+	// Arrow keys walk the buttons the player can press, Enter presses the focused one
+	let widgets = buttons
+		.iter()
+		.filter(|button| button.enabled)
+		.collect::<Vec<_>>();
+	let chosen = keyboard_navigation(&egui_overlay.ctx, &widgets).map(|index| widgets[index].id);
+	let pressed = |index: usize| buttons[index].clicked() || chosen == Some(buttons[index].id);
+
+	if pressed(0) || pressed(1) {
+		game_input.new_game = pressed(0);
+		window.set_cursor_grab(true);
+		state.pop_any();
+		start_game(&mut assets, &mut world, &mut state, &mut skyboxes, &terrain_config);
+	} else if pressed(2) {
+		state.push(SettingsState {
+			name: String::from("Settings State"),
+			rebinding: None,
+			rebind_warning: None,
+			seed_text: None,
 		});
+	} else if pressed(3) {
+		std::process::exit(0);
+	}
 }
 
-fn ui_paused(mut state: Mut<State>, input: Const<Input>, overlay: Const<Overlay>, mut window: Mut<Window>) {
+#[allow(clippy::too_many_arguments)]
+fn ui_paused(
+	mut state: Mut<State>,
+	mut input: Mut<GameInput>,
+	mut window: Mut<Window>,
+	overlay: Const<Overlay>,
+	stats: Const<FrameStats>,
+	mut time_scale: Mut<TimeScale>,
+	theme: Const<UiTheme>,
+	mut world: Mut<World>,
+	mut spawner: Mut<WaveSpawner>,
+	mut economy: Mut<Economy>,
+	mut base_health: Mut<BaseHealth>,
+	mut rng: Mut<Rng>,
+	mut camera: Mut<Camera>,
+	mut selection: Mut<Selection>,
+	mut build: Mut<BuildState>,
+) {
 	window.set_cursor_grab(false);
 
 	let egui_overlay = overlay
@@ -303,25 +5057,269 @@ fn ui_paused(mut state: Mut<State>, input: Const<Input>, overlay: Const<Overlay>
 		.get_mut::<PauseState>()
 		.expect("Cannot find pause state");
 
-	let mut exit_state = pause_state.handled && input.is_action_activated(Action::TogglePause);
+	let exit_state = pause_state.handled && input.is_action_activated(Action::TogglePause);
 	pause_state.handled = true;
 
+	let mut open_settings = false;
+	let mut restart = false;
+	let mut speed = None;
+	let mut speeds = Vec::new();
+	let mut buttons = Vec::new();
+
 	egui::containers::Window::new("Paused")
 		.resizable(false)
 		.default_width(200.0)
 		.show(&egui_overlay.ctx, |ui| {
 			ui.label("Execution is paused. Camera is not controllable");
 			ui.label(format!("Current states stack: [\n {}\n]", states_stack_dump));
+
+			ui.separator();
+			ui.label(format!(
+				"FPS min: {:.1}, max: {:.1}, average: {:.1}",
+				stats.min_fps(),
+				stats.max_fps(),
+				stats.average_fps()
+			));
+			let fps = stats
+				.fps()
+				.enumerate()
+				.map(|(i, fps)| egui::plot::Value::new(i as f64, fps as f64));
+			ui.add(
+				egui::plot::Plot::new("FPS history")
+					.line(egui::plot::Line::new(egui::plot::Values::from_values_iter(fps)))
+					.include_y(0.0)
+					.height(80.0),
+			);
+
+			ui.separator();
+			ui.horizontal(|ui| {
+				ui.label("Game speed");
+				for factor in TimeScale::PRESETS {
+					let button = ui.selectable_label(time_scale.factor == factor, format!("{}x", factor));
+					if button.clicked() {
+						speed = Some(factor);
+					}
+					speeds.push(button);
+				}
+			});
+
+			ui.separator();
+			ui.horizontal(|ui| {
+				let button = ui.button("Settings");
+				open_settings = button.clicked();
+				buttons.push(button);
+				let button = ui.button("Restart");
+				restart = button.clicked();
+				buttons.push(button);
+			});
 		});
 
+	// Arrow keys walk the speed presets and then the buttons, Enter activates the focused one
+	let mut widgets = speeds.iter().collect::<Vec<_>>();
+	widgets.extend(buttons.iter());
+	match keyboard_navigation(&egui_overlay.ctx, &widgets) {
+		Some(index) if index < TimeScale::PRESETS.len() => speed = Some(TimeScale::PRESETS[index]),
+		Some(index) if index == TimeScale::PRESETS.len() => open_settings = true,
+		Some(_) => restart = true,
+		None => {}
+	}
+
 	egui::Area::new("Information")
 		.fixed_pos(egui::pos2(16.0, 16.0))
 		.show(&egui_overlay.ctx, |ui| {
-			ui.colored_label(DEBUG_YELLOW, "Press ESC to resume");
+			ui.colored_label(theme.label, "Press ESC to resume");
 		});
 
-	if exit_state {
+	if let Some(factor) = speed {
+		input.command(UiCommand::GameSpeed(factor));
+	}
+	if open_settings {
+		input.command(UiCommand::OpenSettings);
+	}
+	if restart {
+		input.command(UiCommand::Restart);
+	}
+
+	for command in input.commands() {
+		if let UiCommand::GameSpeed(factor) = command {
+			time_scale.factor = *factor;
+		}
+	}
+
+	if input.commands().contains(&UiCommand::OpenSettings) {
+		// The cursor stays released, `ui_paused` takes over again once the settings are closed
+		state.push(SettingsState {
+			name: String::from("Settings State"),
+			rebinding: None,
+			rebind_warning: None,
+			seed_text: None,
+		});
+	} else if input.commands().contains(&UiCommand::Restart) {
+		restart_game(&mut world, &mut spawner, &mut economy, &mut base_health, &mut rng, &mut camera, &mut selection, &mut build);
+		window.set_cursor_grab(true);
+		state.pop_any();
+	} else if exit_state {
 		window.set_cursor_grab(true);
 		state.pop_any();
 	}
 }
+
+#[allow(clippy::too_many_arguments)]
+fn ui_settings(
+	mut state: Mut<State>,
+	mut input: Mut<Input>,
+	mut game_input: Mut<GameInput>,
+	mut world: Mut<World>,
+	overlay: Const<Overlay>,
+	time_of_day: Const<TimeOfDay>,
+	mut settings: Mut<Settings>,
+	mut rng: Mut<Rng>,
+	theme: Const<UiTheme>,
+) {
+	let egui_overlay = overlay
+		.get::<Egui>()
+		.expect("Egui overlay must be added at startup");
+
+	let settings_state = state
+		.get_mut::<SettingsState>()
+		.expect("Cannot find settings state");
+
+	let capturing = settings_state.rebinding.is_some();
+	if let Some(action) = settings_state.rebinding {
+		capture_key_binding(&mut input, settings_state, action);
+	}
+
+	let escape = !capturing && game_input.is_action_activated(Action::TogglePause);
+	let mut back = false;
+
+	let bindings = Action::ALL
+		.iter()
+		.map(|action| (*action, input.action_mapped(*action).copied()))
+		.collect::<Vec<_>>();
+
+	egui::containers::Window::new("Settings")
+		.resizable(false)
+		.default_width(200.0)
+		.show(&egui_overlay.ctx, |ui| {
+			ui.label("Camera");
+			ui.add(egui::Slider::new(&mut settings.pan_speed, settings::PAN_SPEED_RANGE).text("Pan speed"));
+			ui.add(egui::Slider::new(&mut settings.scroll_speed, settings::SCROLL_SPEED_RANGE).text("Scroll speed"));
+			let angle_range = settings.camera_angle_range();
+			ui.add(egui::Slider::new(&mut settings.camera_angle, angle_range).text("Camera angle"));
+			if ui
+				.checkbox(&mut settings.top_down, "Allow top-down view")
+				.changed()
+			{
+				*settings = settings.clamped();
+			}
+			ui.add(egui::Slider::new(&mut settings.mouse_sensitivity, settings::SENSITIVITY_RANGE).text("Mouse sensitivity"));
+			ui.add(egui::Slider::new(&mut settings.edge_pan_sensitivity, settings::SENSITIVITY_RANGE).text("Edge pan sensitivity"));
+			ui.checkbox(&mut settings.camera_bounds, "Keep the camera over the terrain");
+			ui.checkbox(&mut settings.camera_shake, "Camera shake");
+			ui.add(egui::Slider::new(&mut settings.master_volume, settings::VOLUME_RANGE).text("Master volume"));
+
+			ui.separator();
+			ui.horizontal(|ui| {
+				ui.label("Theme");
+				for kind in ThemeKind::ALL {
+					ui.radio_value(&mut settings.theme, kind, format!("{:?}", kind));
+				}
+			});
+			ui.horizontal(|ui| {
+				ui.label("FPS cap");
+				for cap in FrameRateCap::ALL {
+					ui.radio_value(&mut settings.frame_rate_cap, cap, cap.label());
+				}
+			});
+			// A drag value edits the seed as `f64`, which loses the low bits of large seeds
+			let seed = settings.seed;
+			let seed_text = settings_state
+				.seed_text
+				.get_or_insert_with(|| seed.to_string());
+			ui.horizontal(|ui| {
+				ui.label("Random seed");
+				if ui
+					.text_edit_singleline(seed_text)
+					.changed()
+				{
+					if let Ok(seed) = seed_text.trim().parse::<u64>() {
+						settings.seed = seed;
+						rng.reseed(seed);
+					}
+				}
+			});
+			if seed_text
+				.trim()
+				.parse::<u64>()
+				.is_err()
+			{
+				ui.colored_label(theme.warning, format!("Seed must be a whole number from 0 to {}", u64::MAX));
+			}
+
+			ui.separator();
+			ui.label("Key bindings");
+			egui::Grid::new("Key bindings")
+				.striped(true)
+				.show(ui, |ui| {
+					for (action, button) in bindings.iter() {
+						ui.label(format!("{:?}", action));
+						ui.label(match button {
+							Some(button) => format!("{:?}", button),
+							None => String::from("-"),
+						});
+						let label = if settings_state.rebinding == Some(*action) {
+							"Press a key (ESC to cancel)"
+						} else {
+							"Rebind"
+						};
+						if ui.button(label).clicked() {
+							settings_state.rebinding = Some(*action);
+							settings_state.rebind_warning = None;
+						}
+						ui.end_row();
+					}
+				});
+			if let Some(warning) = settings_state.rebind_warning.as_ref() {
+				ui.colored_label(theme.warning, warning);
+			}
+
+			ui.separator();
+			ui.label("Lights");
+			for (light, switch) in world.query::<(&mut Light, &mut LightSwitch)>() {
+				if !ui
+					.checkbox(&mut switch.enabled, switch.label)
+					.changed()
+				{
+					continue;
+				}
+				match light {
+					Light::Ambient {
+						intensity,
+						..
+					} => *intensity = ambient_intensity(switch, &time_of_day),
+					Light::Simple {
+						enabled,
+						..
+					} => *enabled = switch.enabled,
+					_ => {}
+				}
+			}
+
+			ui.separator();
+			back = ui.button("Back").clicked();
+		});
+
+	egui::Area::new("Information")
+		.fixed_pos(egui::pos2(16.0, 16.0))
+		.show(&egui_overlay.ctx, |ui| {
+			ui.colored_label(theme.label, "Press ESC to go back");
+		});
+
+	if back {
+		game_input.command(UiCommand::CloseSettings);
+	}
+
+	if escape || game_input.commands().contains(&UiCommand::CloseSettings) {
+		state.pop_any();
+	}
+}
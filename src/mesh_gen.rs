@@ -0,0 +1,274 @@
+use dotrix::assets::Mesh;
+
+/// Width of the band `ring` generates, in world units
+pub const RING_WIDTH: f32 = 0.05;
+
+/// Builds a mesh with positions, normals and uvs in attribute slots 0, 1 and 2,
+/// normals are calculated from the positions by `flat_normals`
+pub fn build_mesh(positions: &[[f32; 3]], uvs: &[[f32; 2]]) -> Mesh {
+	let normals = flat_normals(positions);
+
+	let mut mesh = Mesh::default();
+
+	mesh.with_vertices(positions);
+	mesh.with_vertices(&normals);
+	mesh.with_vertices(uvs);
+
+	mesh
+}
+
+/// Unit normal of every vertex of the triangle list, the normal of its triangle. The triangles face the
+/// side their vertices are counter-clockwise from, degenerate ones and leftover vertices get zero normals.
+pub fn flat_normals(positions: &[[f32; 3]]) -> Vec<[f32; 3]> {
+	let mut normals = vec![[0.0; 3]; positions.len()];
+	for (triangle, triangle_normals) in positions
+		.chunks_exact(3)
+		.zip(normals.chunks_exact_mut(3))
+	{
+		let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+		let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+		let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+		let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+		let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+		if length > f32::EPSILON {
+			triangle_normals.fill([n[0] / length, n[1] / length, n[2] / length]);
+		}
+	}
+	normals
+}
+
+/// Grid of `width` by `depth` tiles of `scale` facing up, from the origin along positive X and Z
+pub fn grid_plane(width: usize, depth: usize, scale: f32) -> Mesh {
+	let (positions, uvs) = grid_plane_vertices(width, depth, scale);
+	build_mesh(&positions, &uvs)
+}
+
+/// Positions and uvs of `grid_plane`, two triangles per tile with the texture stretched over each tile.
+/// Tiles are generated column by column along Z, the order of `TerrainGrid::cell_vertices`.
+pub fn grid_plane_vertices(width: usize, depth: usize, scale: f32) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+	let mut positions = Vec::with_capacity(6 * width * depth);
+	let mut uvs = Vec::with_capacity(positions.capacity());
+	for x in 0..width {
+		let x0 = x as f32 * scale;
+		let x1 = x0 + scale;
+		for z in 0..depth {
+			let z0 = z as f32 * scale;
+			let z1 = z0 + scale;
+			positions.extend_from_slice(&[[x0, 0.0, z0], [x0, 0.0, z1], [x1, 0.0, z0], [x1, 0.0, z0], [x0, 0.0, z1], [x1, 0.0, z1]]);
+			uvs.extend_from_slice(&[[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]]);
+		}
+	}
+
+	(positions, uvs)
+}
+
+/// Box of `[width, height, depth]` standing on the XZ plane and centered by X and Z axis
+pub fn box_mesh(size: [f32; 3]) -> Mesh {
+	let (positions, uvs) = box_vertices(size);
+	build_mesh(&positions, &uvs)
+}
+
+/// Positions and uvs of `box_mesh`, two triangles per face
+pub fn box_vertices(size: [f32; 3]) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+	let [width, height, depth] = size;
+	let x = width / 2.0;
+	let z = depth / 2.0;
+	let h = height;
+
+	// Every face is listed counter-clockwise as seen from outside of the box
+	let faces = [
+		[[-x, h, -z], [-x, h, z], [x, h, z], [x, h, -z]],
+		[[-x, 0.0, -z], [x, 0.0, -z], [x, 0.0, z], [-x, 0.0, z]],
+		[[x, 0.0, -z], [x, h, -z], [x, h, z], [x, 0.0, z]],
+		[[-x, 0.0, -z], [-x, 0.0, z], [-x, h, z], [-x, h, -z]],
+		[[-x, 0.0, z], [x, 0.0, z], [x, h, z], [-x, h, z]],
+		[[-x, 0.0, -z], [-x, h, -z], [x, h, -z], [x, 0.0, -z]],
+	];
+
+	let mut positions = Vec::with_capacity(faces.len() * 6);
+	let mut uvs = Vec::with_capacity(faces.len() * 6);
+	for [a, b, c, d] in faces.iter() {
+		positions.extend_from_slice(&[*a, *b, *c, *a, *c, *d]);
+		uvs.extend_from_slice(&[[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]]);
+	}
+
+	(positions, uvs)
+}
+
+/// Closed cylinder standing on the XZ plane around the Y axis, its round side made of `segments` flat faces
+pub fn cylinder(radius: f32, height: f32, segments: usize) -> Mesh {
+	let (positions, uvs) = cylinder_vertices(radius, height, segments);
+	build_mesh(&positions, &uvs)
+}
+
+/// Positions and uvs of `cylinder`, four triangles per segment: two on the side and one on each cap
+pub fn cylinder_vertices(radius: f32, height: f32, segments: usize) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+	let segments = segments.max(3);
+	let points = circle_points(radius, segments);
+
+	// Caps are fans around their centers, the uvs map the cap circle into the unit square
+	let cap_uv = |x: f32, z: f32| [0.5 + x / (2.0 * radius), 0.5 + z / (2.0 * radius)];
+
+	let mut positions = Vec::with_capacity(segments * 12);
+	let mut uvs = Vec::with_capacity(positions.capacity());
+	for i in 0..segments {
+		let ([x0, z0], [x1, z1]) = (points[i], points[i + 1]);
+		let (u0, u1) = (i as f32 / segments as f32, (i + 1) as f32 / segments as f32);
+		let (b0, b1, t0, t1) = ([x0, 0.0, z0], [x1, 0.0, z1], [x0, height, z0], [x1, height, z1]);
+
+		positions.extend_from_slice(&[b0, t0, b1, b1, t0, t1]);
+		uvs.extend_from_slice(&[[u0, 1.0], [u0, 0.0], [u1, 1.0], [u1, 1.0], [u0, 0.0], [u1, 0.0]]);
+
+		positions.extend_from_slice(&[[0.0, height, 0.0], t1, t0]);
+		uvs.extend_from_slice(&[[0.5, 0.5], cap_uv(x1, z1), cap_uv(x0, z0)]);
+		positions.extend_from_slice(&[[0.0, 0.0, 0.0], b0, b1]);
+		uvs.extend_from_slice(&[[0.5, 0.5], cap_uv(x0, z0), cap_uv(x1, z1)]);
+	}
+
+	(positions, uvs)
+}
+
+/// Flat band of `RING_WIDTH` facing up, centered on the circle of `radius` around the origin
+pub fn ring(radius: f32, segments: usize) -> Mesh {
+	let (positions, uvs) = ring_vertices(radius, segments);
+	build_mesh(&positions, &uvs)
+}
+
+/// Positions and uvs of `ring`, two triangles per segment
+pub fn ring_vertices(radius: f32, segments: usize) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+	let segments = segments.max(3);
+	let inner = circle_points((radius - RING_WIDTH / 2.0).max(0.0), segments);
+	let outer = circle_points(radius + RING_WIDTH / 2.0, segments);
+
+	let mut positions = Vec::with_capacity(segments * 6);
+	let mut uvs = Vec::with_capacity(positions.capacity());
+	for i in 0..segments {
+		let (i0, i1) = ([inner[i][0], 0.0, inner[i][1]], [inner[i + 1][0], 0.0, inner[i + 1][1]]);
+		let (o0, o1) = ([outer[i][0], 0.0, outer[i][1]], [outer[i + 1][0], 0.0, outer[i + 1][1]]);
+		let (u0, u1) = (i as f32 / segments as f32, (i + 1) as f32 / segments as f32);
+
+		positions.extend_from_slice(&[i0, i1, o0, o0, i1, o1]);
+		uvs.extend_from_slice(&[[u0, 0.0], [u1, 0.0], [u0, 1.0], [u0, 1.0], [u1, 0.0], [u1, 1.0]]);
+	}
+
+	(positions, uvs)
+}
+
+/// `segments + 1` points by X and Z on the circle of `radius` around the origin, the last one closes
+/// the circle at the first
+pub fn circle_points(radius: f32, segments: usize) -> Vec<[f32; 2]> {
+	(0..=segments)
+		.map(|i| {
+			let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+			[radius * angle.cos(), radius * angle.sin()]
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+		for (a, e) in actual.iter().zip(expected.iter()) {
+			assert!((a - e).abs() < 1e-5, "{:?} is not {:?}", actual, expected);
+		}
+	}
+
+	fn assert_unit_normals(positions: &[[f32; 3]]) {
+		for [x, y, z] in flat_normals(positions) {
+			assert!(((x * x + y * y + z * z).sqrt() - 1.0).abs() < 1e-5, "normal {:?} is not unit length", [x, y, z]);
+		}
+	}
+
+	#[test]
+	fn grid_plane_vertex_count() {
+		let (positions, uvs) = grid_plane_vertices(4, 2, 0.5);
+		assert_eq!(positions.len(), 6 * 4 * 2);
+		assert_eq!(uvs.len(), positions.len());
+		assert_unit_normals(&positions);
+	}
+
+	#[test]
+	fn box_vertex_count() {
+		let (positions, uvs) = box_vertices([0.3, 0.6, 0.2]);
+		assert_eq!(positions.len(), 6 * 6);
+		assert_eq!(uvs.len(), positions.len());
+		assert_unit_normals(&positions);
+	}
+
+	#[test]
+	fn cylinder_vertex_count() {
+		for segments in [3, 8, 24] {
+			let (positions, uvs) = cylinder_vertices(0.5, 1.0, segments);
+			assert_eq!(positions.len(), 12 * segments);
+			assert_eq!(uvs.len(), positions.len());
+			assert_unit_normals(&positions);
+		}
+
+		// Fewer segments than a triangle are raised to three
+		assert_eq!(cylinder_vertices(0.5, 1.0, 1).0.len(), 12 * 3);
+	}
+
+	#[test]
+	fn ring_vertex_count() {
+		for segments in [3, 8, 48] {
+			let (positions, uvs) = ring_vertices(2.0, segments);
+			assert_eq!(positions.len(), 6 * segments);
+			assert_eq!(uvs.len(), positions.len());
+			assert_unit_normals(&positions);
+			for normal in flat_normals(&positions) {
+				assert_close(normal, [0.0, 1.0, 0.0]);
+			}
+		}
+
+		assert_eq!(ring_vertices(2.0, 0).0.len(), 6 * 3);
+	}
+
+	#[test]
+	fn ring_spans_its_width() {
+		let (positions, _) = ring_vertices(2.0, 16);
+		for [x, y, z] in positions {
+			let distance = x.hypot(z);
+			assert_eq!(y, 0.0);
+			assert!((distance - 2.0).abs() <= RING_WIDTH / 2.0 + 1e-5, "{} is off the ring", distance);
+		}
+	}
+
+	#[test]
+	fn slope_normals() {
+		// Tile rising by 1.0 along X, its normal leans back against the slope at 45 degrees
+		let (mut positions, _) = grid_plane_vertices(1, 1, 1.0);
+		for position in positions.iter_mut() {
+			position[1] = position[0];
+		}
+
+		let half = std::f32::consts::FRAC_1_SQRT_2;
+		for normal in flat_normals(&positions) {
+			assert_close(normal, [-half, half, 0.0]);
+		}
+	}
+
+	#[test]
+	fn recalculated_after_edit() {
+		let (mut positions, _) = grid_plane_vertices(2, 1, 1.0);
+		for normal in flat_normals(&positions) {
+			assert_close(normal, [0.0, 1.0, 0.0]);
+		}
+
+		// Raising the shared edge at x = 1.0 tilts the first tile up along X and the second one down
+		for position in positions.iter_mut() {
+			if position[0] == 1.0 {
+				position[1] = 1.0;
+			}
+		}
+		let half = std::f32::consts::FRAC_1_SQRT_2;
+		let normals = flat_normals(&positions);
+		for normal in &normals[..6] {
+			assert_close(*normal, [-half, half, 0.0]);
+		}
+		for normal in &normals[6..] {
+			assert_close(*normal, [half, half, 0.0]);
+		}
+	}
+}
@@ -0,0 +1,268 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::terrain::TerrainGrid;
+
+/// Cost of walking into a cell of plain terrain, the cheapest a cell can be
+pub const BASE_COST: u32 = 1;
+/// Cost of the roughest walkable terrain
+pub const MAX_COST: u32 = 8;
+/// Cost of a cell enemies can not walk into at all
+pub const IMPASSABLE: u32 = u32::MAX;
+
+/// Cost of a cell from its roughness between 0.0 and 1.0, the roughness of 1.0 is impassable
+pub fn cost_from_roughness(roughness: f32) -> u32 {
+	if roughness >= 1.0 {
+		return IMPASSABLE;
+	}
+	BASE_COST + (roughness.max(0.0) * (MAX_COST - BASE_COST) as f32).round() as u32
+}
+
+/// Walkable cells of the terrain grid, the cells towers stand on and the walls of the level are blocked
+pub struct NavGrid {
+	/// Grid the cells belong to
+	pub grid: TerrainGrid,
+	/// Cell enemies are spawned at
+	pub spawn: (i32, i32),
+	/// Cell enemies are walking to
+	pub goal: (i32, i32),
+	/// Cells towers stand on, taken from the towers by `update_navigation`
+	pub blocked: HashSet<(i32, i32)>,
+	/// Cells blocked by the level, painted in the level editor and saved with the level
+	walls: HashSet<(i32, i32)>,
+	/// Cost of walking into each cell, indexed by `TerrainGrid::cell_index`
+	costs: Vec<u32>,
+	/// Costs or the level changed since `update_navigation` computed the path
+	pub changed: bool,
+	/// Incremented by `update_navigation` whenever the walkable cells change, so the tile colors and
+	/// overlays can tell they are outdated
+	pub revision: u64,
+}
+
+impl NavGrid {
+	pub fn new(grid: TerrainGrid, spawn: (i32, i32), goal: (i32, i32)) -> Self {
+		Self {
+			grid,
+			spawn,
+			goal,
+			blocked: HashSet::new(),
+			walls: HashSet::new(),
+			costs: vec![BASE_COST; grid.cell_count()],
+			changed: false,
+			revision: 0,
+		}
+	}
+
+	/// Replaces the cell costs, ignored unless there is a cost for every cell
+	pub fn set_costs(&mut self, costs: Vec<u32>) {
+		if costs.len() != self.costs.len() {
+			return;
+		}
+		self.costs = costs;
+		self.changed = true;
+	}
+
+	pub fn walls(&self) -> &HashSet<(i32, i32)> {
+		&self.walls
+	}
+
+	/// Replaces the spawn, the goal and the walls, the level must have been validated against the grid
+	pub fn set_level(&mut self, spawn: (i32, i32), goal: (i32, i32), walls: HashSet<(i32, i32)>) {
+		self.spawn = spawn;
+		self.goal = goal;
+		self.walls = walls;
+		self.changed = true;
+	}
+
+	/// Walls the cell off unless it is taken or the goal could not be reached any more.
+	/// Returns `true` if the wall was added.
+	pub fn add_wall(&mut self, cell: (i32, i32)) -> bool {
+		if self.blocked.contains(&cell) || !self.can_block(cell) {
+			return false;
+		}
+		self.walls.insert(cell);
+		self.changed = true;
+		true
+	}
+
+	/// Returns `true` if there was a wall on the cell
+	pub fn remove_wall(&mut self, cell: (i32, i32)) -> bool {
+		let removed = self.walls.remove(&cell);
+		self.changed |= removed;
+		removed
+	}
+
+	/// Moves the spawn to a walkable cell the goal can be reached from. Returns `true` if it moved.
+	pub fn move_spawn(&mut self, cell: (i32, i32)) -> bool {
+		if cell == self.spawn || cell == self.goal || !self.is_walkable(cell) || self.find_path(cell, self.goal).is_none() {
+			return false;
+		}
+		self.spawn = cell;
+		self.changed = true;
+		true
+	}
+
+	/// Moves the goal to a walkable cell that can be reached from the spawn. Returns `true` if it moved.
+	pub fn move_goal(&mut self, cell: (i32, i32)) -> bool {
+		if cell == self.goal || cell == self.spawn || self.find_path(self.spawn, cell).is_none() {
+			return false;
+		}
+		self.goal = cell;
+		self.changed = true;
+		true
+	}
+
+	/// Cost of walking into the cell, `IMPASSABLE` outside of the grid
+	pub fn cost(&self, cell: (i32, i32)) -> u32 {
+		match self.grid.cell_index(cell) {
+			Some(index) => self.costs[index],
+			None => IMPASSABLE,
+		}
+	}
+
+	/// Cell is within the grid, passable, not blocked and not a wall
+	pub fn is_walkable(&self, cell: (i32, i32)) -> bool {
+		self.cost(cell) != IMPASSABLE && !self.blocked.contains(&cell) && !self.walls.contains(&cell)
+	}
+
+	/// Enemies can still walk from the spawn to the goal if the cell gets blocked, walls can not be built on
+	pub fn can_block(&self, cell: (i32, i32)) -> bool {
+		if cell == self.spawn || cell == self.goal || self.walls.contains(&cell) {
+			return false;
+		}
+
+		self.search(self.spawn, self.goal, Some(cell)).is_some()
+	}
+
+	/// Cheapest path of adjacent cells from `start` to `goal` inclusive, A* with the Manhattan heuristic.
+	/// The start cell does not have to be walkable, so an enemy standing on a new tower can leave it.
+	pub fn find_path(&self, start: (i32, i32), goal: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+		self.search(start, goal, None)
+	}
+
+	/// A* treating `also_blocked` as blocked, the heuristic stays admissible as no cell is cheaper
+	/// than `BASE_COST`
+	fn search(&self, start: (i32, i32), goal: (i32, i32), also_blocked: Option<(i32, i32)>) -> Option<Vec<(i32, i32)>> {
+		let walkable = |cell: (i32, i32)| Some(cell) != also_blocked && self.is_walkable(cell);
+
+		if !walkable(goal) {
+			return None;
+		}
+
+		let heuristic = |cell: (i32, i32)| ((cell.0 - goal.0).abs() + (cell.1 - goal.1).abs()) as u32 * BASE_COST;
+
+		let mut open = BinaryHeap::new();
+		let mut came_from = HashMap::new();
+		let mut cost = HashMap::new();
+
+		open.push(Reverse((heuristic(start), start)));
+		cost.insert(start, 0);
+
+		while let Some(Reverse((_, cell))) = open.pop() {
+			if cell == goal {
+				let mut path = vec![cell];
+				let mut current = cell;
+				while let Some(previous) = came_from.get(&current) {
+					path.push(*previous);
+					current = *previous;
+				}
+				path.reverse();
+				return Some(path);
+			}
+
+			for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+				let neighbour = (cell.0 + dx, cell.1 + dz);
+				if !walkable(neighbour) {
+					continue;
+				}
+				let next_cost = cost[&cell] + self.cost(neighbour);
+				let shorter = match cost.get(&neighbour) {
+					Some(known) => next_cost < *known,
+					None => true,
+				};
+				if shorter {
+					cost.insert(neighbour, next_cost);
+					came_from.insert(neighbour, cell);
+					open.push(Reverse((next_cost + heuristic(neighbour), neighbour)));
+				}
+			}
+		}
+
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::terrain::TerrainConfig;
+
+	fn nav_grid() -> NavGrid {
+		let grid = TerrainGrid::from_config(&TerrainConfig {
+			width: 3,
+			depth: 3,
+			..Default::default()
+		});
+		NavGrid::new(grid, (0, 0), (2, 0))
+	}
+
+	#[test]
+	fn tower_closing_a_line_is_rejected() {
+		let mut nav_grid = nav_grid();
+
+		// Towers across the middle column, the last one would wall the spawn off from the goal
+		for cell in [(1, 0), (1, 1)] {
+			assert!(nav_grid.can_block(cell));
+			nav_grid.blocked.insert(cell);
+		}
+		assert!(!nav_grid.can_block((1, 2)));
+		assert!(nav_grid
+			.find_path(nav_grid.spawn, nav_grid.goal)
+			.is_some());
+	}
+
+	#[test]
+	fn spawn_and_goal_can_not_be_blocked() {
+		let nav_grid = nav_grid();
+		assert!(!nav_grid.can_block(nav_grid.spawn));
+		assert!(!nav_grid.can_block(nav_grid.goal));
+	}
+
+	#[test]
+	fn walls_cutting_the_path_are_refused() {
+		let mut nav_grid = nav_grid();
+		assert!(nav_grid.add_wall((1, 0)));
+		assert!(nav_grid.add_wall((1, 1)));
+		assert!(!nav_grid.add_wall((1, 2)));
+		assert!(!nav_grid.add_wall(nav_grid.spawn));
+		assert!(!nav_grid.add_wall(nav_grid.goal));
+		assert_eq!(nav_grid.walls().len(), 2);
+	}
+
+	#[test]
+	fn walls_under_towers_are_refused() {
+		let mut nav_grid = nav_grid();
+		nav_grid.blocked.insert((1, 1));
+		assert!(!nav_grid.add_wall((1, 1)));
+		assert!(nav_grid.walls().is_empty());
+	}
+
+	#[test]
+	fn spawn_and_goal_only_move_to_reachable_cells() {
+		let mut nav_grid = nav_grid();
+
+		// Walls the corner cell (2, 2) off, it is walkable but can not be reached
+		assert!(nav_grid.add_wall((2, 1)));
+		assert!(nav_grid.add_wall((1, 2)));
+		assert!(!nav_grid.move_goal((2, 2)));
+		assert!(!nav_grid.move_spawn((2, 2)));
+		assert!(!nav_grid.move_goal((2, 1)));
+		assert!(!nav_grid.move_spawn((1, 2)));
+		assert!(!nav_grid.move_goal((3, 0)));
+		assert_eq!((nav_grid.spawn, nav_grid.goal), ((0, 0), (2, 0)));
+
+		assert!(nav_grid.move_goal((0, 2)));
+		assert!(nav_grid.move_spawn((1, 1)));
+		assert_eq!((nav_grid.spawn, nav_grid.goal), ((1, 1), (0, 2)));
+	}
+}
@@ -0,0 +1,267 @@
+use std::collections::HashSet;
+
+use dotrix::ecs::Entity;
+use dotrix::math::{InnerSpace, Vec2, Vec3};
+use dotrix::Camera;
+
+/// Drag distance in egui points along either axis after which a click becomes a box selection
+const BOX_DRAG_THRESHOLD: f32 = 4.0;
+
+/// Terrain cell and tower under the cursor, updated by `pick_terrain_cell`, and the towers
+/// selected with a box by `ui_box_select`
+#[derive(Default)]
+pub struct Selection {
+	pub cell: Option<(i32, i32)>,
+	/// Point where the cursor ray hits the terrain base plane
+	pub point: Option<Vec3>,
+	/// Nearest tower the cursor ray hits
+	pub tower: Option<Entity>,
+	pub towers: HashSet<Entity>,
+	/// Last drag of the left mouse button, kept after the release until the next press
+	pub drag: Option<BoxDrag>,
+}
+
+/// Corners of a dragged rectangle in egui points
+#[derive(Clone, Copy)]
+pub struct BoxDrag {
+	pub start: Vec2,
+	pub end: Vec2,
+}
+
+impl BoxDrag {
+	/// The mouse moved far enough to select with a box rather than click
+	pub fn is_box(&self) -> bool {
+		(self.end.x - self.start.x).abs() > BOX_DRAG_THRESHOLD || (self.end.y - self.start.y).abs() > BOX_DRAG_THRESHOLD
+	}
+
+	/// Point is within the rectangle, the drag may go in any direction
+	pub fn contains(&self, point: (f32, f32)) -> bool {
+		let (x, y) = point;
+		x >= self.start.x.min(self.end.x)
+			&& x <= self.start.x.max(self.end.x)
+			&& y >= self.start.y.min(self.end.y)
+			&& y <= self.start.y.max(self.end.y)
+	}
+}
+
+pub struct Ray {
+	pub origin: Vec3,
+	pub direction: Vec3,
+}
+
+impl Ray {
+	/// Ray from the camera eye through the cursor position in window pixels
+	pub fn from_cursor(camera: &Camera, cursor: &Vec2, viewport: (f32, f32)) -> Self {
+		let (width, height) = viewport;
+		let (origin, forward, right, up) = camera_basis(camera);
+
+		// Cursor in normalized device coordinates, Y axis of the window points down
+		let ndc_x = 2.0 * cursor.x / width - 1.0;
+		let ndc_y = 1.0 - 2.0 * cursor.y / height;
+		let tan_half_fov = (camera.fov / 2.0).tan();
+		let aspect = width / height;
+
+		let direction = (forward + right * (ndc_x * tan_half_fov * aspect) + up * (ndc_y * tan_half_fov)).normalize();
+
+		Self {
+			origin,
+			direction,
+		}
+	}
+
+	/// Intersection with the horizontal plane at `height`, `None` if the ray is parallel to it or points away
+	pub fn intersect_plane_y(&self, height: f32) -> Option<Vec3> {
+		if self.direction.y.abs() < f32::EPSILON {
+			return None;
+		}
+
+		let t = (height - self.origin.y) / self.direction.y;
+		if t < 0.0 {
+			return None;
+		}
+
+		Some(self.origin + self.direction * t)
+	}
+
+	/// Distance along the ray to the axis aligned box between `min` and `max`, `None` if the ray misses it
+	pub fn intersect_box(&self, min: Vec3, max: Vec3) -> Option<f32> {
+		let mut near = 0.0_f32;
+		let mut far = f32::MAX;
+
+		for axis in 0..3 {
+			let (origin, direction) = (self.origin[axis], self.direction[axis]);
+			if direction.abs() < f32::EPSILON {
+				if origin < min[axis] || origin > max[axis] {
+					return None;
+				}
+				continue;
+			}
+
+			let t1 = (min[axis] - origin) / direction;
+			let t2 = (max[axis] - origin) / direction;
+			near = near.max(t1.min(t2));
+			far = far.min(t1.max(t2));
+			if near > far {
+				return None;
+			}
+		}
+
+		Some(near)
+	}
+}
+
+/// Volume the camera sees as six planes, each a point on the plane and its normal pointing inside
+pub struct Frustum {
+	planes: [(Vec3, Vec3); 6],
+}
+
+impl Frustum {
+	pub fn from_camera(camera: &Camera, viewport: (f32, f32)) -> Self {
+		let (width, height) = viewport;
+		let (origin, forward, right, up) = camera_basis(camera);
+		Self::from_view(origin, (forward, right, up), camera.fov, width / height, (camera.near_plane, camera.far_plane))
+	}
+
+	/// Frustum of an eye at `origin` looking along the unit `forward` vector of the `(forward, right, up)`
+	/// basis, with the vertical `fov` in radians, the `aspect` ratio and the `(near, far)` plane distances
+	pub fn from_view(origin: Vec3, (forward, right, up): (Vec3, Vec3, Vec3), fov: f32, aspect: f32, (near, far): (f32, f32)) -> Self {
+		let tan_half_fov = (fov / 2.0).tan();
+		let half_width = tan_half_fov * aspect;
+
+		// Edges of the view through the middles of the window sides, the side planes contain them
+		let right_edge = forward + right * half_width;
+		let left_edge = forward - right * half_width;
+		let top_edge = forward + up * tan_half_fov;
+		let bottom_edge = forward - up * tan_half_fov;
+
+		Self {
+			planes: [
+				(origin + forward * near, forward),
+				(origin + forward * far, -forward),
+				(origin, up
+					.cross(right_edge)
+					.normalize()),
+				(origin, left_edge
+					.cross(up)
+					.normalize()),
+				(origin, top_edge
+					.cross(right)
+					.normalize()),
+				(origin, right
+					.cross(bottom_edge)
+					.normalize()),
+			],
+		}
+	}
+
+	/// Axis aligned box is at least partly inside, boxes near the frustum corners may pass while
+	/// being outside, which is fine for culling
+	pub fn intersects_box(&self, min: Vec3, max: Vec3) -> bool {
+		self.planes.iter().all(|(point, normal)| {
+			// Corner of the box farthest along the normal is enough to tell the box is fully outside
+			let corner = Vec3::new(
+				if normal.x >= 0.0 { max.x } else { min.x },
+				if normal.y >= 0.0 { max.y } else { min.y },
+				if normal.z >= 0.0 { max.z } else { min.z },
+			);
+			(corner - *point).dot(*normal) >= 0.0
+		})
+	}
+}
+
+/// Window position in pixels of a world point, `None` if the point is behind the camera
+pub fn world_to_screen(camera: &Camera, point: Vec3, viewport: (f32, f32)) -> Option<(f32, f32)> {
+	let (width, height) = viewport;
+	let (origin, forward, right, up) = camera_basis(camera);
+
+	let offset = point - origin;
+	let depth = offset.dot(forward);
+	if depth <= camera.near_plane {
+		return None;
+	}
+
+	let tan_half_fov = (camera.fov / 2.0).tan();
+	let aspect = width / height;
+	let ndc_x = offset.dot(right) / (depth * tan_half_fov * aspect);
+	let ndc_y = offset.dot(up) / (depth * tan_half_fov);
+
+	Some(((ndc_x + 1.0) / 2.0 * width, (1.0 - ndc_y) / 2.0 * height))
+}
+
+/// Eye position and the forward, right and up unit vectors of the camera
+fn camera_basis(camera: &Camera) -> (Vec3, Vec3, Vec3, Vec3) {
+	let origin = eye_position(camera);
+	let target = Vec3::new(camera.target.x, camera.target.y, camera.target.z);
+
+	let forward = (target - origin).normalize();
+	let right = forward
+		.cross(Vec3::unit_y())
+		.normalize();
+	let up = right.cross(forward);
+
+	(origin, forward, right, up)
+}
+
+/// World position of the camera, which orbits its target at `distance`
+pub fn eye_position(camera: &Camera) -> Vec3 {
+	let dy = camera.distance * camera.xz_angle.sin();
+	let dxz = camera.distance * camera.xz_angle.cos();
+	let dx = dxz * camera.y_angle.cos();
+	let dz = dxz * camera.y_angle.sin();
+
+	Vec3::new(camera.target.x + dx, camera.target.y + dy, camera.target.z + dz)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Eye at the origin looking along negative Z with a 90 degree field of view, the view spans
+	/// as far sideways and up as it reaches ahead
+	fn frustum() -> Frustum {
+		let basis = (Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+		Frustum::from_view(Vec3::new(0.0, 0.0, 0.0), basis, std::f32::consts::FRAC_PI_2, 1.0, (0.1, 100.0))
+	}
+
+	/// Chunk sized box of `size` at `center`, flat like the terrain
+	fn chunk(center: Vec3, size: f32) -> (Vec3, Vec3) {
+		let half = Vec3::new(size / 2.0, 0.5, size / 2.0);
+		(center - half, center + half)
+	}
+
+	#[test]
+	fn chunks_in_view_intersect() {
+		let frustum = frustum();
+		for center in [Vec3::new(0.0, 0.0, -10.0), Vec3::new(8.0, 0.0, -10.0), Vec3::new(0.0, -8.0, -50.0)] {
+			let (min, max) = chunk(center, 4.0);
+			assert!(frustum.intersects_box(min, max), "{:?} is culled", center);
+		}
+	}
+
+	#[test]
+	fn chunks_partly_in_view_intersect() {
+		// Chunk around the right edge of the view and one reaching over the far plane
+		let frustum = frustum();
+		for center in [Vec3::new(11.0, 0.0, -10.0), Vec3::new(0.0, 0.0, -101.0)] {
+			let (min, max) = chunk(center, 4.0);
+			assert!(frustum.intersects_box(min, max), "{:?} is culled", center);
+		}
+	}
+
+	#[test]
+	fn chunks_out_of_view_are_culled() {
+		// Behind the eye, beside, above and below the view, and beyond the far plane
+		let frustum = frustum();
+		for center in [
+			Vec3::new(0.0, 0.0, 10.0),
+			Vec3::new(20.0, 0.0, -10.0),
+			Vec3::new(-20.0, 0.0, -10.0),
+			Vec3::new(0.0, 20.0, -10.0),
+			Vec3::new(0.0, -20.0, -10.0),
+			Vec3::new(0.0, 0.0, -110.0),
+		] {
+			let (min, max) = chunk(center, 4.0);
+			assert!(!frustum.intersects_box(min, max), "{:?} is not culled", center);
+		}
+	}
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use dotrix::Input;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::gamepad::GamepadAxes;
+
+/// File a recording is written to when `--record` is given no path
+pub const REPLAY_PATH: &str = "replay.json";
+
+/// State of an action in one frame, as `Input` reported it
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct ActionState<A> {
+	pub action: A,
+	pub activated: bool,
+	pub hold: bool,
+	pub deactivated: bool,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ModifierKeys {
+	pub shift: bool,
+	pub ctrl: bool,
+	pub alt: bool,
+}
+
+/// Input of one frame, `C` are the commands given through the UI
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputFrame<A, C> {
+	/// Seconds since the recording started
+	pub time: f32,
+	/// Real duration of the frame, a replay steps the game by it instead of by its own frame time
+	pub delta: f32,
+	/// Actions in any state, the missing ones are released
+	pub actions: Vec<ActionState<A>>,
+	pub mouse_position: Option<[f32; 2]>,
+	pub mouse_delta: [f32; 2],
+	pub mouse_scroll: f32,
+	pub modifiers: ModifierKeys,
+	/// Recordings made before the gamepad support play back a resting controller
+	#[serde(default)]
+	pub gamepad: GamepadAxes,
+	/// Commands in the order they were given, recordings without them play back no commands
+	#[serde(default)]
+	pub commands: Vec<C>,
+}
+
+impl<A: PartialEq, C> InputFrame<A, C> {
+	pub fn state(&self, action: A) -> Option<&ActionState<A>> {
+		self.actions
+			.iter()
+			.find(|state| state.action == action)
+	}
+}
+
+impl<A, C> Default for InputFrame<A, C> {
+	fn default() -> Self {
+		Self {
+			time: 0.0,
+			delta: 0.0,
+			actions: Vec::new(),
+			mouse_position: None,
+			mouse_delta: [0.0, 0.0],
+			mouse_scroll: 0.0,
+			modifiers: ModifierKeys::default(),
+			gamepad: GamepadAxes::default(),
+			commands: Vec::new(),
+		}
+	}
+}
+
+/// Where the input of every frame comes from, the live devices or a replay
+pub trait InputSource<A, C>: Send + Sync {
+	/// Input of the next frame lasting `delta` seconds, `None` once the source has run out. `gamepad` are the
+	/// axes of the connected controller, at rest without one.
+	fn next_frame(&mut self, live: &Input, gamepad: GamepadAxes, delta: f32) -> Option<InputFrame<A, C>>;
+}
+
+/// Recorded session, the seed makes the gameplay randomness repeat
+#[derive(Serialize, Deserialize)]
+pub struct Recording<A, C> {
+	pub seed: u64,
+	pub frames: Vec<InputFrame<A, C>>,
+}
+
+/// Plays back the frames of a `Recording` in order, the live input is ignored
+pub struct ReplayInput<A, C> {
+	frames: VecDeque<InputFrame<A, C>>,
+}
+
+impl<A, C> ReplayInput<A, C> {
+	pub fn new(recording: Recording<A, C>) -> Self {
+		Self {
+			frames: recording.frames.into(),
+		}
+	}
+}
+
+impl<A: Send + Sync, C: Send + Sync> InputSource<A, C> for ReplayInput<A, C> {
+	fn next_frame(&mut self, _live: &Input, _gamepad: GamepadAxes, _delta: f32) -> Option<InputFrame<A, C>> {
+		self.frames.pop_front()
+	}
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+	Io(std::io::Error),
+	Format(serde_json::Error),
+}
+
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ReplayError::Io(err) => write!(f, "replay file I/O error: {}", err),
+			ReplayError::Format(err) => write!(f, "malformed replay file: {}", err),
+		}
+	}
+}
+
+impl From<std::io::Error> for ReplayError {
+	fn from(err: std::io::Error) -> Self {
+		ReplayError::Io(err)
+	}
+}
+
+impl From<serde_json::Error> for ReplayError {
+	fn from(err: serde_json::Error) -> Self {
+		ReplayError::Format(err)
+	}
+}
+
+pub fn save_recording<A: Serialize, C: Serialize>(path: &str, recording: &Recording<A, C>) -> Result<(), ReplayError> {
+	let json = serde_json::to_string(recording)?;
+	std::fs::write(path, json)?;
+	Ok(())
+}
+
+pub fn load_recording<A: DeserializeOwned, C: DeserializeOwned>(path: &str) -> Result<Recording<A, C>, ReplayError> {
+	let json = std::fs::read_to_string(path)?;
+	Ok(serde_json::from_str(&json)?)
+}
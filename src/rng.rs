@@ -0,0 +1,47 @@
+/// Seed of a new game unless the settings or a save file set another one
+pub const DEFAULT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Xorshift64* generator all the gameplay randomness goes through, so a run can be reproduced
+/// from its seed
+pub struct Rng {
+	seed: u64,
+	state: u64,
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Self {
+		Self {
+			seed,
+			// Xorshift gets stuck at zero, any other constant would do
+			state: if seed == 0 { DEFAULT_SEED } else { seed },
+		}
+	}
+
+	/// Seed the generator was last started from
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	/// Starts the sequence over from `seed`
+	pub fn reseed(&mut self, seed: u64) {
+		*self = Self::new(seed);
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+	}
+
+	/// Uniform value in `0.0..1.0`
+	pub fn next_f32(&mut self) -> f32 {
+		// The upper 24 bits fill the mantissa exactly
+		(self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+	}
+
+	/// Uniform value in `min..max`
+	pub fn range(&mut self, min: f32, max: f32) -> f32 {
+		min + (max - min) * self.next_f32()
+	}
+}
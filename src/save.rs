@@ -0,0 +1,91 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bookmarks::CameraBookmarks;
+use crate::settings::Settings;
+use crate::towers::{TargetPriority, TowerKind};
+
+/// File the game is saved to with `Action::Save` and restored from at startup
+pub const SAVE_PATH: &str = "save.json";
+/// File the game is saved to when paused, see `autosave`
+pub const AUTOSAVE_PATH: &str = "autosave.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedTower {
+	pub kind: TowerKind,
+	pub cell: (i32, i32),
+	/// Towers of saves made before upgrades existed are at the first level
+	#[serde(default = "first_level")]
+	pub level: u8,
+	/// Towers of saves made before priorities existed target the closest enemy
+	#[serde(default = "closest")]
+	pub priority: TargetPriority,
+}
+
+fn first_level() -> u8 {
+	1
+}
+
+fn closest() -> TargetPriority {
+	TargetPriority::Closest
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+	/// Terrain mesh vertices as stored in `MainState`
+	pub positions: Vec<[f32; 3]>,
+	pub camera_target: [f32; 3],
+	pub gold: u32,
+	pub towers: Vec<SavedTower>,
+	/// Missing from the files saved before the settings existed
+	#[serde(default)]
+	pub settings: Settings,
+	#[serde(default)]
+	pub bookmarks: CameraBookmarks,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+	Io(std::io::Error),
+	Format(serde_json::Error),
+}
+
+impl SaveError {
+	/// Save file does not exist, which is expected on the first run
+	pub fn is_missing(&self) -> bool {
+		matches!(self, SaveError::Io(err) if err.kind() == std::io::ErrorKind::NotFound)
+	}
+}
+
+impl fmt::Display for SaveError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SaveError::Io(err) => write!(f, "save file I/O error: {}", err),
+			SaveError::Format(err) => write!(f, "malformed save file: {}", err),
+		}
+	}
+}
+
+impl From<std::io::Error> for SaveError {
+	fn from(err: std::io::Error) -> Self {
+		SaveError::Io(err)
+	}
+}
+
+impl From<serde_json::Error> for SaveError {
+	fn from(err: serde_json::Error) -> Self {
+		SaveError::Format(err)
+	}
+}
+
+pub fn save_game(path: &str, game: &SaveGame) -> Result<(), SaveError> {
+	let json = serde_json::to_string_pretty(game)?;
+	std::fs::write(path, json)?;
+	Ok(())
+}
+
+pub fn load_game(path: &str) -> Result<SaveGame, SaveError> {
+	let json = std::fs::read_to_string(path)?;
+	Ok(serde_json::from_str(&json)?)
+}
@@ -0,0 +1,74 @@
+use std::ops::RangeInclusive;
+
+use dotrix::math::Vec3;
+
+pub const RADIUS_RANGE: RangeInclusive<f32> = 0.5..=5.0;
+pub const STRENGTH_RANGE: RangeInclusive<f32> = 0.1..=5.0;
+
+/// Shape of the brush influence from its center to the radius
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushFalloff {
+	Linear,
+	/// Smoothstep, blends the edits into the surrounding terrain
+	Smooth,
+	/// Full strength up to the radius, raises and lowers the area uniformly
+	Constant,
+}
+
+impl BrushFalloff {
+	pub const ALL: [BrushFalloff; 3] = [BrushFalloff::Linear, BrushFalloff::Smooth, BrushFalloff::Constant];
+}
+
+/// Brush `sculpt_terrain` raises and lowers the terrain with, adjustable in `ui_sculpt`
+pub struct SculptBrush {
+	/// Distance from the cursor in world units within which vertices are moved
+	pub radius: f32,
+	/// Height change per second in world units at the brush center
+	pub strength: f32,
+	pub falloff: BrushFalloff,
+}
+
+impl SculptBrush {
+	/// Share of the strength applied at `distance` from the center, 0.0 from the radius on
+	pub fn influence(&self, distance: f32) -> f32 {
+		if distance >= self.radius {
+			return 0.0;
+		}
+
+		let t = 1.0 - distance / self.radius;
+		match self.falloff {
+			BrushFalloff::Linear => t,
+			BrushFalloff::Smooth => t * t * (3.0 - 2.0 * t),
+			BrushFalloff::Constant => 1.0,
+		}
+	}
+
+	/// Moves the vertices around `center` up by `amount` at the center, or down if it is negative.
+	/// Positions are relative to `origin`, the world position of the terrain mesh.
+	/// Returns `true` if any vertex moved.
+	pub fn apply(&self, positions: &mut [[f32; 3]], origin: Vec3, center: Vec3, amount: f32) -> bool {
+		let mut moved = false;
+
+		for position in positions.iter_mut() {
+			let dx = position[0] + origin.x - center.x;
+			let dz = position[2] + origin.z - center.z;
+			let weight = self.influence((dx * dx + dz * dz).sqrt());
+			if weight > 0.0 {
+				position[1] += amount * weight;
+				moved = true;
+			}
+		}
+
+		moved
+	}
+}
+
+impl Default for SculptBrush {
+	fn default() -> Self {
+		Self {
+			radius: 1.5,
+			strength: 1.0,
+			falloff: BrushFalloff::Smooth,
+		}
+	}
+}
@@ -0,0 +1,127 @@
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rng;
+use crate::theme::ThemeKind;
+
+pub const PAN_SPEED_RANGE: RangeInclusive<f32> = 5.0..=100.0;
+pub const SCROLL_SPEED_RANGE: RangeInclusive<f32> = 10.0..=200.0;
+pub const VOLUME_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+/// Factors of the free camera mouse look and the edge panning, 0.0 turns the input off
+pub const SENSITIVITY_RANGE: RangeInclusive<f32> = 0.0..=3.0;
+/// Pitch of the isometric camera in radians, from a low view over the terrain to a steep one
+pub const CAMERA_ANGLE_RANGE: RangeInclusive<f32> = 0.35..=1.45;
+/// Steepest pitch with `Settings::top_down`, short of straight down where the camera has no screen up direction
+pub const TOP_DOWN_ANGLE: f32 = 1.55;
+
+/// Frame rate `limit_frame_rate` holds the game to
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum FrameRateCap {
+	Fps30,
+	Fps60,
+	Fps120,
+	Uncapped,
+}
+
+impl FrameRateCap {
+	pub const ALL: [FrameRateCap; 4] = [FrameRateCap::Fps30, FrameRateCap::Fps60, FrameRateCap::Fps120, FrameRateCap::Uncapped];
+
+	/// Shortest time between two frames, `None` when uncapped
+	pub fn interval(&self) -> Option<Duration> {
+		match self {
+			FrameRateCap::Fps30 => Some(Duration::from_secs(1) / 30),
+			FrameRateCap::Fps60 => Some(Duration::from_secs(1) / 60),
+			FrameRateCap::Fps120 => Some(Duration::from_secs(1) / 120),
+			FrameRateCap::Uncapped => None,
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			FrameRateCap::Fps30 => "30",
+			FrameRateCap::Fps60 => "60",
+			FrameRateCap::Fps120 => "120",
+			FrameRateCap::Uncapped => "Uncapped",
+		}
+	}
+}
+
+/// Camera tuning, angle, sensitivity and shake, the volume, the UI theme and the frame rate cap adjustable from the pause menu and kept in the save file,
+/// values missing from older saves are taken from the defaults
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+	/// Maximal panning speed in units per second
+	pub pan_speed: f32,
+	/// Zoom speed in units per second of mouse wheel scrolling
+	pub scroll_speed: f32,
+	/// Pitch of the isometric camera in radians, see `camera_angle_range`
+	pub camera_angle: f32,
+	/// Lets the camera angle go up to a top-down view
+	pub top_down: bool,
+	/// Factor of the free camera mouse look speed
+	pub mouse_sensitivity: f32,
+	/// Factor of the panning speed when the cursor touches the window border
+	pub edge_pan_sensitivity: f32,
+	/// Keeps the screen center over the terrain and a margin around it, see `CameraBounds`
+	pub camera_bounds: bool,
+	/// Shake the camera when the base loses a life
+	pub camera_shake: bool,
+	/// Scale of every sound played, muted at 0.0
+	pub master_volume: f32,
+	/// Seed of the gameplay randomness, the same seed replays the same waves
+	pub seed: u64,
+	pub theme: ThemeKind,
+	pub frame_rate_cap: FrameRateCap,
+}
+
+impl Settings {
+	/// Range of the camera angle slider, reaching up to `TOP_DOWN_ANGLE` if top-down views are allowed
+	pub fn camera_angle_range(&self) -> RangeInclusive<f32> {
+		if self.top_down {
+			*CAMERA_ANGLE_RANGE.start()..=TOP_DOWN_ANGLE
+		} else {
+			CAMERA_ANGLE_RANGE
+		}
+	}
+
+	/// Keeps the values within the slider ranges, values from a save file may be out of them
+	pub fn clamped(self) -> Self {
+		let angle_range = self.camera_angle_range();
+		Self {
+			pan_speed: self.pan_speed.clamp(*PAN_SPEED_RANGE.start(), *PAN_SPEED_RANGE.end()),
+			scroll_speed: self.scroll_speed.clamp(*SCROLL_SPEED_RANGE.start(), *SCROLL_SPEED_RANGE.end()),
+			camera_angle: self.camera_angle.clamp(*angle_range.start(), *angle_range.end()),
+			top_down: self.top_down,
+			mouse_sensitivity: self.mouse_sensitivity.clamp(*SENSITIVITY_RANGE.start(), *SENSITIVITY_RANGE.end()),
+			edge_pan_sensitivity: self.edge_pan_sensitivity.clamp(*SENSITIVITY_RANGE.start(), *SENSITIVITY_RANGE.end()),
+			camera_bounds: self.camera_bounds,
+			camera_shake: self.camera_shake,
+			master_volume: self.master_volume.clamp(*VOLUME_RANGE.start(), *VOLUME_RANGE.end()),
+			seed: self.seed,
+			theme: self.theme,
+			frame_rate_cap: self.frame_rate_cap,
+		}
+	}
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			pan_speed: 30.0,
+			scroll_speed: 60.0,
+			camera_angle: 1.2,
+			top_down: false,
+			mouse_sensitivity: 1.0,
+			edge_pan_sensitivity: 1.0,
+			camera_bounds: true,
+			camera_shake: true,
+			master_volume: 0.8,
+			seed: rng::DEFAULT_SEED,
+			theme: ThemeKind::Dark,
+			frame_rate_cap: FrameRateCap::Uncapped,
+		}
+	}
+}
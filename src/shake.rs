@@ -0,0 +1,67 @@
+use dotrix::math::Vec3;
+
+use crate::rng::Rng;
+
+/// Offset of the camera target in units at the start of a shake
+pub const SHAKE_MAGNITUDE: f32 = 0.35;
+pub const SHAKE_SECONDS: f32 = 0.3;
+/// Seed of the shake noise, kept apart from the gameplay `Rng` so shaking does not change the waves
+const SHAKE_SEED: u64 = 0x5EED_CA3E_5A4E_0001;
+
+/// Shake of the camera started when the base loses a life. `shake_camera` adds the offset to
+/// `camera.target` every frame and `settle_camera` takes it off again before anything else
+/// reads the target, so the camera ends where it was.
+pub struct CameraShake {
+	/// Largest offset of the current shake, it fades out over the shake
+	pub magnitude: f32,
+	/// Seconds until the shake is over
+	pub remaining: f32,
+	/// Offset added to the target this frame
+	offset: Vec3,
+	noise: Rng,
+}
+
+impl CameraShake {
+	/// Starts a shake, a running one is restarted unless it is stronger
+	pub fn start(&mut self, magnitude: f32) {
+		if self.magnitude * self.remaining / SHAKE_SECONDS < magnitude {
+			self.magnitude = magnitude;
+			self.remaining = SHAKE_SECONDS;
+		}
+	}
+
+	/// Takes the offset of the last frame, the target is unshaken afterwards
+	pub fn take_offset(&mut self) -> Vec3 {
+		std::mem::replace(&mut self.offset, Vec3::new(0.0, 0.0, 0.0))
+	}
+
+	/// Advances the shake by `delta` seconds and returns the offset for this frame
+	pub fn step(&mut self, delta: f32) -> Vec3 {
+		if self.remaining <= 0.0 {
+			return Vec3::new(0.0, 0.0, 0.0);
+		}
+		let strength = self.magnitude * self.remaining / SHAKE_SECONDS;
+		self.remaining = (self.remaining - delta).max(0.0);
+		self.offset = Vec3::new(
+			self.noise.range(-strength, strength),
+			self.noise.range(-strength, strength) * 0.5,
+			self.noise.range(-strength, strength),
+		);
+		self.offset
+	}
+
+	pub fn stop(&mut self) {
+		self.remaining = 0.0;
+	}
+}
+
+impl Default for CameraShake {
+	fn default() -> Self {
+		Self {
+			magnitude: 0.0,
+			remaining: 0.0,
+			offset: Vec3::new(0.0, 0.0, 0.0),
+			noise: Rng::new(SHAKE_SEED),
+		}
+	}
+}
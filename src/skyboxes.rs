@@ -0,0 +1,72 @@
+use dotrix::assets::Texture;
+use dotrix::{Assets, CubeMap, Id};
+
+/// Prefixes of the skybox sets preloaded at startup, the first available one is shown initially
+pub const SKYBOX_SETS: [&str; 3] = ["skybox", "skybox_night", "skybox_alien"];
+
+/// Cube faces in the order they are assigned to a `CubeMap`
+const FACES: [&str; 6] = ["right", "left", "top", "bottom", "back", "front"];
+
+pub struct SkyBoxSet {
+	pub prefix: String,
+	faces: [Id<Texture>; 6],
+}
+
+impl SkyBoxSet {
+	/// Imports `assets/<prefix>_<face>.png` for every face, `None` if any of the files is missing
+	pub fn load(assets: &mut Assets, prefix: &str) -> Option<Self> {
+		let paths = FACES.map(|face| format!("assets/{}_{}.png", prefix, face));
+		if let Some(missing) = paths
+			.iter()
+			.find(|path| !std::path::Path::new(path).exists())
+		{
+			log::warn!("Skipping skybox set '{}', {} is missing", prefix, missing);
+			return None;
+		}
+
+		for path in paths.iter() {
+			assets.import(path);
+		}
+
+		Some(Self {
+			prefix: String::from(prefix),
+			faces: FACES.map(|face| assets.register(&format!("{}_{}", prefix, face))),
+		})
+	}
+
+	/// Face textures, which are loaded asynchronously
+	pub fn textures(&self) -> &[Id<Texture>] {
+		&self.faces
+	}
+
+	pub fn cubemap(&self) -> CubeMap {
+		let [right, left, top, bottom, back, front] = self.faces;
+		CubeMap {
+			right,
+			left,
+			top,
+			bottom,
+			back,
+			front,
+			..Default::default()
+		}
+	}
+}
+
+/// Preloaded skybox sets, `current` is the one shown on the SkyBox entity
+#[derive(Default)]
+pub struct SkyBoxes {
+	pub sets: Vec<SkyBoxSet>,
+	pub current: usize,
+}
+
+impl SkyBoxes {
+	/// Switches to the next set and returns it, `None` if nothing is loaded
+	pub fn cycle(&mut self) -> Option<&SkyBoxSet> {
+		if self.sets.is_empty() {
+			return None;
+		}
+		self.current = (self.current + 1) % self.sets.len();
+		self.sets.get(self.current)
+	}
+}
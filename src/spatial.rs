@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use dotrix::math::Vec3;
+
+/// Uniform hash of items by their position on the XZ plane, rebuilt whenever the items move.
+/// A query visits the cells overlapping the square around a point only, instead of every item.
+pub struct SpatialGrid<T> {
+	/// Side of a cell in world units
+	cell_size: f32,
+	cells: HashMap<(i32, i32), Vec<usize>>,
+	items: Vec<(Vec3, T)>,
+}
+
+impl<T> SpatialGrid<T> {
+	/// Empty grid of `cell_size` cells, the size is kept positive
+	pub fn new(cell_size: f32) -> Self {
+		Self {
+			cell_size: cell_size.max(0.01),
+			cells: HashMap::new(),
+			items: Vec::new(),
+		}
+	}
+
+	pub fn insert(&mut self, position: Vec3, item: T) {
+		let cell = self.cell(position.x, position.z);
+		self.cells
+			.entry(cell)
+			.or_default()
+			.push(self.items.len());
+		self.items.push((position, item));
+	}
+
+	/// Items in the cells overlapping the square of `radius` around `center` by X and Z axis. The
+	/// items may be farther than `radius`, the caller compares the exact distances.
+	pub fn query(&self, center: Vec3, radius: f32) -> impl Iterator<Item = &(Vec3, T)> + '_ {
+		let radius = radius.max(0.0);
+		let (min_x, min_z) = self.cell(center.x - radius, center.z - radius);
+		let (max_x, max_z) = self.cell(center.x + radius, center.z + radius);
+
+		(min_x..=max_x)
+			.flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+			.filter_map(move |cell| self.cells.get(&cell))
+			.flatten()
+			.map(move |index| &self.items[*index])
+	}
+
+	fn cell(&self, x: f32, z: f32) -> (i32, i32) {
+		((x / self.cell_size).floor() as i32, (z / self.cell_size).floor() as i32)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rng::Rng;
+
+	#[test]
+	fn query_matches_brute_force() {
+		for seed in 1..=20 {
+			let mut rng = Rng::new(seed);
+			let cell_size = rng.range(0.5, 4.0);
+			let points = (0..200)
+				.map(|_| Vec3::new(rng.range(-20.0, 20.0), rng.range(-1.0, 1.0), rng.range(-20.0, 20.0)))
+				.collect::<Vec<_>>();
+
+			let mut grid = SpatialGrid::new(cell_size);
+			for (index, point) in points.iter().enumerate() {
+				grid.insert(*point, index);
+			}
+
+			for _ in 0..20 {
+				let center = Vec3::new(rng.range(-25.0, 25.0), 0.0, rng.range(-25.0, 25.0));
+				let radius = rng.range(0.0, 10.0);
+				let within = |point: &Vec3| (point.x - center.x).hypot(point.z - center.z) <= radius;
+
+				let mut found = grid
+					.query(center, radius)
+					.filter(|(point, _)| within(point))
+					.map(|(_, index)| *index)
+					.collect::<Vec<_>>();
+				found.sort_unstable();
+				let expected = (0..points.len())
+					.filter(|index| within(&points[*index]))
+					.collect::<Vec<_>>();
+
+				assert_eq!(found, expected, "seed {}, center {:?}, radius {}", seed, center, radius);
+
+				// Nearest item in range, the way towers pick the closest enemy
+				let distance = |index: &usize| (points[*index].x - center.x).hypot(points[*index].z - center.z);
+				let nearest = |indices: &[usize]| {
+					indices
+						.iter()
+						.copied()
+						.min_by(|a, b| distance(a).total_cmp(&distance(b)))
+				};
+				assert_eq!(nearest(&found), nearest(&expected));
+			}
+		}
+	}
+}
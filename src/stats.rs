@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+/// Number of frames kept in the `FrameStats` history
+pub const FRAME_HISTORY: usize = 120;
+
+/// Terrain chunks drawn and hidden by `cull_terrain_chunks` in the last frame
+#[derive(Default)]
+pub struct CullingStats {
+	pub visible: usize,
+	pub culled: usize,
+}
+
+/// Rolling history of frame deltas in seconds, oldest first
+pub struct FrameStats {
+	deltas: VecDeque<f32>,
+}
+
+impl FrameStats {
+	pub fn push(&mut self, delta: f32) {
+		if self.deltas.len() == FRAME_HISTORY {
+			self.deltas.pop_front();
+		}
+		self.deltas.push_back(delta);
+	}
+
+	/// Frames per second of every recorded frame, oldest first
+	pub fn fps(&self) -> impl Iterator<Item = f32> + '_ {
+		self.deltas
+			.iter()
+			.filter(|delta| **delta > 0.0)
+			.map(|delta| 1.0 / delta)
+	}
+
+	pub fn min_fps(&self) -> f32 {
+		self.fps()
+			.reduce(f32::min)
+			.unwrap_or(0.0)
+	}
+
+	pub fn max_fps(&self) -> f32 {
+		self.fps().fold(0.0, f32::max)
+	}
+
+	/// Average over the total history time rather than the mean of per-frame values
+	pub fn average_fps(&self) -> f32 {
+		let total: f32 = self.deltas.iter().sum();
+		if total > 0.0 {
+			self.deltas.len() as f32 / total
+		} else {
+			0.0
+		}
+	}
+}
+
+impl Default for FrameStats {
+	fn default() -> Self {
+		Self {
+			deltas: VecDeque::with_capacity(FRAME_HISTORY),
+		}
+	}
+}
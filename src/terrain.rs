@@ -0,0 +1,438 @@
+use std::ops::Range;
+
+use dotrix::assets::{Mesh, Texture};
+use dotrix::math::Vec3;
+use serde::Deserialize;
+
+use crate::mesh_gen::build_mesh;
+
+/// Number of mesh vertices per tile, two triangles generated by `init_terrain`
+pub const TILE_VERTICES: usize = 6;
+/// Color of the tiles no tint is drawn over, see `TerrainChunk::build_tint_mesh`
+pub const TILE_WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Terrain generation parameters, read by `init_terrain` at startup. The `[terrain]` table of the config
+/// file and the `--terrain` and `--chunk-size` flags override the defaults.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+	/// Number of tiles along X axis
+	pub width: usize,
+	/// Number of tiles along Z axis
+	pub depth: usize,
+	/// Length of a tile side in world units
+	pub tile_scale: f32,
+	/// Path to a PNG of `width + 1` by `depth + 1` pixels, one per grid vertex, the red channel is the
+	/// vertex height
+	pub heightmap: Option<String>,
+	/// Path to a PNG of the heightmap size, the red channel is the terrain roughness which makes the cells
+	/// costlier to walk. Without one every cell costs `pathfinding::BASE_COST`.
+	pub cost_map: Option<String>,
+	/// World height of a white heightmap pixel
+	pub height_scale: f32,
+	/// Number of tiles along each side of a terrain chunk, every chunk is spawned as its own mesh.
+	/// A chunk size of at least `width` and `depth` renders the terrain as a single mesh.
+	pub chunk_size: usize,
+}
+
+impl TerrainConfig {
+	/// Keeps at least two cells for the spawn and the goal, positive tiles and chunks of at least one tile
+	pub fn clamped(self) -> Self {
+		Self {
+			width: self.width.max(2),
+			depth: self.depth.max(1),
+			tile_scale: if self.tile_scale > 0.0 { self.tile_scale } else { 1.0 },
+			chunk_size: self.chunk_size.max(1),
+			..self
+		}
+	}
+
+	/// Distance from the grid origin to its center by X and Z axis
+	pub fn half_extent(&self) -> (f32, f32) {
+		(self.width as f32 * self.tile_scale / 2.0, self.depth as f32 * self.tile_scale / 2.0)
+	}
+}
+
+impl Default for TerrainConfig {
+	fn default() -> Self {
+		Self {
+			width: 5,
+			depth: 5,
+			tile_scale: 1.0,
+			heightmap: None,
+			cost_map: None,
+			height_scale: 1.0,
+			chunk_size: 16,
+		}
+	}
+}
+
+/// Mapping between world space and terrain grid cells
+#[derive(Debug, Clone, Copy)]
+pub struct TerrainGrid {
+	/// Number of cells along X axis
+	pub width: usize,
+	/// Number of cells along Z axis
+	pub depth: usize,
+	pub tile_scale: f32,
+	/// World position of the corner of cell `(0, 0)`
+	pub origin: Vec3,
+}
+
+impl TerrainGrid {
+	/// Grid of the terrain spawned by `init_terrain`, which is shifted to be centered at the world origin
+	pub fn from_config(config: &TerrainConfig) -> Self {
+		let (shift_x, shift_z) = config.half_extent();
+		Self {
+			width: config.width,
+			depth: config.depth,
+			tile_scale: config.tile_scale,
+			origin: Vec3::new(-shift_x, 0.0, -shift_z),
+		}
+	}
+
+	/// Side lengths of the grid in world units by X and Z axis
+	pub fn extent(&self) -> (f32, f32) {
+		(self.width as f32 * self.tile_scale, self.depth as f32 * self.tile_scale)
+	}
+
+	pub fn cell_count(&self) -> usize {
+		self.width * self.depth
+	}
+
+	/// Index of the cell in per cell data, cells are ordered column by column along Z.
+	/// `None` if the cell is outside of the grid.
+	pub fn cell_index(&self, cell: (i32, i32)) -> Option<usize> {
+		if cell.0 < 0 || cell.1 < 0 || cell.0 >= self.width as i32 || cell.1 >= self.depth as i32 {
+			return None;
+		}
+		Some(cell.0 as usize * self.depth + cell.1 as usize)
+	}
+
+	/// Cell containing the point by X and Z axis, `None` if the point is outside of the terrain
+	pub fn world_to_cell(&self, p: Vec3) -> Option<(i32, i32)> {
+		let x = ((p.x - self.origin.x) / self.tile_scale).floor();
+		let z = ((p.z - self.origin.z) / self.tile_scale).floor();
+		if x < 0.0 || z < 0.0 || x >= self.width as f32 || z >= self.depth as f32 {
+			return None;
+		}
+
+		Some((x as i32, z as i32))
+	}
+
+	/// Range of the terrain mesh vertices of the cell, tiles are generated column by column along Z
+	pub fn cell_vertices(&self, cell: (i32, i32)) -> std::ops::Range<usize> {
+		let start = (cell.0 as usize * self.depth + cell.1 as usize) * TILE_VERTICES;
+		start..start + TILE_VERTICES
+	}
+
+	/// World position of the cell center on the terrain base plane
+	pub fn cell_to_world_center(&self, cell: (i32, i32)) -> Vec3 {
+		Vec3::new(
+			self.origin.x + (cell.0 as f32 + 0.5) * self.tile_scale,
+			self.origin.y,
+			self.origin.z + (cell.1 as f32 + 0.5) * self.tile_scale,
+		)
+	}
+
+	/// First and last cell of the square of cells reaching `radius` around the center by X and Z axis,
+	/// the cells may be outside of the grid
+	pub fn cell_bounds(&self, center: Vec3, radius: f32) -> ((i32, i32), (i32, i32)) {
+		let cell = |x: f32, z: f32| {
+			(
+				((x - self.origin.x) / self.tile_scale).floor() as i32,
+				((z - self.origin.z) / self.tile_scale).floor() as i32,
+			)
+		};
+		(cell(center.x - radius, center.z - radius), cell(center.x + radius, center.z + radius))
+	}
+
+	/// Splits the grid into chunks of `size` by `size` cells, the chunks at the far edges are smaller
+	/// if the grid size is not a multiple of `size`
+	pub fn chunks(&self, size: usize) -> Vec<TerrainChunk> {
+		let size = size.max(1);
+		let (width, depth) = (self.width, self.depth);
+
+		(0..width)
+			.step_by(size)
+			.flat_map(|x| {
+				(0..depth).step_by(size).map(move |z| TerrainChunk {
+					x: x..(x + size).min(width),
+					z: z..(z + size).min(depth),
+				})
+			})
+			.collect()
+	}
+}
+
+/// Block of terrain cells rendered as one mesh, see `TerrainGrid::chunks`. Cell coordinates and
+/// vertex indices stay those of the whole grid, a chunk only selects which of them its mesh holds.
+#[derive(Debug, Clone)]
+pub struct TerrainChunk {
+	/// Cells of the chunk along X axis
+	pub x: Range<usize>,
+	/// Cells of the chunk along Z axis
+	pub z: Range<usize>,
+}
+
+impl TerrainChunk {
+	/// Chunk has a cell between the `min` and `max` cells inclusive
+	pub fn overlaps(&self, min: (i32, i32), max: (i32, i32)) -> bool {
+		min.0 < self.x.end as i32 && max.0 >= self.x.start as i32 && min.1 < self.z.end as i32 && max.1 >= self.z.start as i32
+	}
+
+	/// Indices of the vertices of the chunk cells in the terrain vertex arrays
+	pub fn vertices(&self, grid: &TerrainGrid) -> Vec<usize> {
+		self.x
+			.clone()
+			.flat_map(|x| self.z.clone().map(move |z| (x as i32, z as i32)))
+			.flat_map(|cell| grid.cell_vertices(cell))
+			.collect()
+	}
+
+	/// World space bounding box of the chunk vertices
+	pub fn bounds(&self, grid: &TerrainGrid, positions: &[[f32; 3]]) -> (Vec3, Vec3) {
+		let (low, high) = self
+			.vertices(grid)
+			.into_iter()
+			.map(|i| positions[i][1])
+			.fold((f32::MAX, f32::MIN), |(min, max), y| (min.min(y), max.max(y)));
+
+		let min = Vec3::new(self.x.start as f32 * grid.tile_scale, low, self.z.start as f32 * grid.tile_scale);
+		let max = Vec3::new(self.x.end as f32 * grid.tile_scale, high, self.z.end as f32 * grid.tile_scale);
+		(min + grid.origin, max + grid.origin)
+	}
+
+	/// Builds the mesh of the chunk from the vertex arrays of the whole terrain.
+	///
+	/// GPU buffers of a new mesh are created when it is rendered first, so replacing the chunk
+	/// mesh asset with the result is enough to upload changed positions.
+	pub fn build_mesh(&self, grid: &TerrainGrid, positions: &[[f32; 3]], uvs: &[[f32; 2]]) -> Mesh {
+		let vertices = self.vertices(grid);
+		let chunk_positions = vertices
+			.iter()
+			.map(|i| positions[*i])
+			.collect::<Vec<_>>();
+		let chunk_uvs = vertices
+			.iter()
+			.map(|i| uvs[*i])
+			.collect::<Vec<_>>();
+
+		build_mesh(&chunk_positions, &chunk_uvs)
+	}
+
+	/// Positions of the chunk tiles colored `tint`, lifted by `offset` to be drawn over the terrain in the
+	/// tint color. The other tiles are collapsed into a point, so the vertex count stays that of the chunk.
+	pub fn tint_positions(&self, grid: &TerrainGrid, positions: &[[f32; 3]], colors: &[[f32; 4]], tint: [f32; 4], offset: f32) -> Vec<[f32; 3]> {
+		let vertices = self.vertices(grid);
+		let mut tinted = Vec::with_capacity(vertices.len());
+		for tile in vertices.chunks(TILE_VERTICES) {
+			if colors[tile[0]] == tint {
+				tinted.extend(
+					tile.iter()
+						.map(|i| [positions[*i][0], positions[*i][1] + offset, positions[*i][2]]),
+				);
+			} else {
+				tinted.extend(std::iter::repeat(positions[tile[0]]).take(tile.len()));
+			}
+		}
+		tinted
+	}
+
+	/// Mesh of the `tint_positions`, the tint is the albedo of its entity
+	pub fn build_tint_mesh(&self, grid: &TerrainGrid, positions: &[[f32; 3]], colors: &[[f32; 4]], tint: [f32; 4], offset: f32) -> Mesh {
+		let tinted = self.tint_positions(grid, positions, colors, tint, offset);
+		build_mesh(&tinted, &vec![[0.0, 0.0]; tinted.len()])
+	}
+}
+
+/// Vertex heights sampled from the red channel of a texture
+pub struct Heightmap {
+	/// Number of pixels along X axis
+	columns: usize,
+	/// Number of pixels along Z axis
+	rows: usize,
+	heights: Vec<f32>,
+}
+
+impl Heightmap {
+	/// Reads the red channel of an RGBA texture, returns `None` unless it is `columns` by `rows` pixels
+	pub fn from_texture(texture: &Texture, columns: usize, rows: usize) -> Option<Self> {
+		if texture.width as usize != columns || texture.height as usize != rows {
+			return None;
+		}
+
+		Some(Self {
+			columns,
+			rows,
+			heights: red_channel(&texture.data, columns, rows)?,
+		})
+	}
+
+	/// Normalized height of the grid vertex at `(x, z)`
+	pub fn sample(&self, x: usize, z: usize) -> f32 {
+		self.heights[z.min(self.rows - 1) * self.columns + x.min(self.columns - 1)]
+	}
+
+	/// Moves every position up by its vertex height, positions must be in tile units scaled by `tile_scale`
+	pub fn displace(&self, positions: &mut [[f32; 3]], tile_scale: f32, height_scale: f32) {
+		for position in positions.iter_mut() {
+			let x = (position[0] / tile_scale).round() as usize;
+			let z = (position[2] / tile_scale).round() as usize;
+			position[1] = self.sample(x, z) * height_scale;
+		}
+	}
+}
+
+/// Terrain roughness sampled from the red channel of a texture of the heightmap size, kept apart from the
+/// heightmap so a grayscale heightmap does not make the terrain costly or impassable to walk
+pub struct CostMap {
+	/// Number of pixels along X axis
+	columns: usize,
+	/// Number of pixels along Z axis
+	rows: usize,
+	roughness: Vec<f32>,
+}
+
+impl CostMap {
+	/// Reads the red channel of an RGBA texture, returns `None` unless it is `columns` by `rows` pixels
+	pub fn from_texture(texture: &Texture, columns: usize, rows: usize) -> Option<Self> {
+		if texture.width as usize != columns || texture.height as usize != rows {
+			return None;
+		}
+		Self::from_pixels(&texture.data, columns, rows)
+	}
+
+	/// Cost map of RGBA pixels, `None` unless there are `columns` by `rows` of them
+	pub fn from_pixels(data: &[u8], columns: usize, rows: usize) -> Option<Self> {
+		Some(Self {
+			columns,
+			rows,
+			roughness: red_channel(data, columns, rows)?,
+		})
+	}
+
+	/// Roughness of every cell averaged from its corner vertices, in the order of `TerrainGrid::cell_index`
+	pub fn cell_roughness(&self) -> Vec<f32> {
+		let (width, depth) = (self.columns.saturating_sub(1), self.rows.saturating_sub(1));
+		let vertex = |x: usize, z: usize| self.roughness[z * self.columns + x];
+
+		(0..width)
+			.flat_map(|x| (0..depth).map(move |z| (x, z)))
+			.map(|(x, z)| (vertex(x, z) + vertex(x + 1, z) + vertex(x, z + 1) + vertex(x + 1, z + 1)) / 4.0)
+			.collect()
+	}
+}
+
+/// Red channel of RGBA pixels between 0.0 and 1.0, `None` unless there are `columns` by `rows` of them
+fn red_channel(data: &[u8], columns: usize, rows: usize) -> Option<Vec<f32>> {
+	if data.len() != columns * rows * 4 {
+		return None;
+	}
+	Some(
+		data.chunks(4)
+			.map(|pixel| pixel[0] as f32 / 255.0)
+			.collect(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn grid() -> TerrainGrid {
+		TerrainGrid::from_config(&TerrainConfig {
+			width: 4,
+			depth: 3,
+			tile_scale: 2.0,
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn cell_centers_round_trip() {
+		let grid = grid();
+		for cell in [(0, 0), (3, 0), (0, 2), (3, 2), (1, 1), (2, 1)] {
+			assert_eq!(grid.world_to_cell(grid.cell_to_world_center(cell)), Some(cell));
+		}
+	}
+
+	#[test]
+	fn edge_points() {
+		let grid = grid();
+		let (extent_x, extent_z) = grid.extent();
+
+		// Near edge belongs to the first cell, far edge is outside of the terrain
+		assert_eq!(grid.world_to_cell(grid.origin), Some((0, 0)));
+		assert_eq!(grid.world_to_cell(grid.origin + Vec3::new(2.0, 0.0, 4.0)), Some((1, 2)));
+		assert_eq!(grid.world_to_cell(grid.origin + Vec3::new(extent_x, 0.0, 0.0)), None);
+		assert_eq!(grid.world_to_cell(grid.origin + Vec3::new(0.0, 0.0, extent_z)), None);
+	}
+
+	#[test]
+	fn rectangular_terrain() {
+		let config = TerrainConfig {
+			width: 3,
+			depth: 7,
+			..Default::default()
+		};
+		let (positions, uvs) = crate::mesh_gen::grid_plane_vertices(config.width, config.depth, config.tile_scale);
+		assert_eq!(positions.len(), 3 * 2 * config.width * config.depth);
+		assert_eq!(uvs.len(), positions.len());
+
+		// Last cell ends the vertices, the grid is centered on each axis by its own size
+		let grid = TerrainGrid::from_config(&config);
+		assert_eq!(grid.cell_vertices((2, 6)).end, positions.len());
+		assert_eq!(config.half_extent(), (1.5, 3.5));
+		assert_eq!(grid.world_to_cell(Vec3::new(0.0, 0.0, 3.4)), Some((1, 6)));
+		assert_eq!(grid.world_to_cell(Vec3::new(1.6, 0.0, 0.0)), None);
+	}
+
+	#[test]
+	fn out_of_bounds() {
+		let grid = grid();
+		assert_eq!(grid.world_to_cell(grid.origin - Vec3::new(0.1, 0.0, 0.0)), None);
+		assert_eq!(grid.world_to_cell(grid.origin - Vec3::new(0.0, 0.0, 0.1)), None);
+		assert_eq!(grid.world_to_cell(Vec3::new(100.0, 0.0, 0.0)), None);
+	}
+
+	#[test]
+	fn cost_map_averages_cell_corners() {
+		// 3x2 vertices of 2x1 cells, the rough vertex at (2, 1) only touches the second cell
+		let mut pixels = vec![0; 3 * 2 * 4];
+		pixels[(3 + 2) * 4] = 255;
+		let cost_map = CostMap::from_pixels(&pixels, 3, 2).unwrap();
+		assert_eq!(cost_map.cell_roughness(), vec![0.0, 0.25]);
+
+		assert!(CostMap::from_pixels(&pixels, 3, 3).is_none());
+	}
+
+	#[test]
+	fn tint_covers_the_tiles_of_its_color() {
+		let grid = grid();
+		let (positions, _) = crate::mesh_gen::grid_plane_vertices(grid.width, grid.depth, grid.tile_scale);
+		let mut colors = vec![TILE_WHITE; positions.len()];
+		let red = [1.0, 0.0, 0.0, 0.5];
+		for vertex in grid.cell_vertices((1, 2)) {
+			colors[vertex] = red;
+		}
+
+		let chunk = TerrainChunk {
+			x: 0..4,
+			z: 0..3,
+		};
+		let tinted = chunk.tint_positions(&grid, &positions, &colors, red, 0.5);
+		assert_eq!(tinted.len(), positions.len());
+
+		// The lifted tile keeps its extent, the other tiles are degenerate
+		let lifted = tinted
+			.chunks(TILE_VERTICES)
+			.filter(|tile| tile.iter().any(|p| *p != tile[0]))
+			.collect::<Vec<_>>();
+		assert_eq!(lifted.len(), 1);
+		for p in lifted[0] {
+			assert_eq!(p[1], 0.5);
+			assert!((2.0..=4.0).contains(&p[0]) && (4.0..=6.0).contains(&p[2]), "{:?} is off the tile", p);
+		}
+	}
+}
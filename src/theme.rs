@@ -0,0 +1,73 @@
+use dotrix::egui::{self, Color32};
+use serde::{Deserialize, Serialize};
+
+/// Preset of `UiTheme`, chosen in the settings and kept in the save file
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum ThemeKind {
+	Dark,
+	HighContrast,
+}
+
+impl ThemeKind {
+	pub const ALL: [ThemeKind; 2] = [ThemeKind::Dark, ThemeKind::HighContrast];
+}
+
+/// Named colors of the UI, the labels drawn over the scene take theirs from here and the egui
+/// widgets from the visuals set by `apply`
+pub struct UiTheme {
+	pub kind: ThemeKind,
+	/// Labels drawn over the scene
+	pub label: Color32,
+	/// Messages about something the player has to deal with
+	pub warning: Color32,
+	/// Fill of the buttons and other inactive widgets
+	pub button: Color32,
+	/// Text of the widgets, `None` keeps the egui default
+	pub text: Option<Color32>,
+}
+
+impl UiTheme {
+	pub fn preset(kind: ThemeKind) -> Self {
+		match kind {
+			ThemeKind::Dark => Self {
+				kind,
+				label: Color32::from_rgb(255, 255, 0),
+				warning: Color32::from_rgb(255, 140, 0),
+				button: Color32::from_rgb(60, 60, 60),
+				text: None,
+			},
+			ThemeKind::HighContrast => Self {
+				kind,
+				label: Color32::WHITE,
+				warning: Color32::from_rgb(255, 60, 60),
+				button: Color32::BLACK,
+				text: Some(Color32::WHITE),
+			},
+		}
+	}
+
+	/// Sets the egui visuals of the theme, they last until the next call
+	pub fn apply(&self, ctx: &egui::CtxRef) {
+		let mut visuals = egui::Visuals::dark();
+		visuals.override_text_color = self.text;
+		visuals.widgets.inactive.bg_fill = self.button;
+
+		if self.kind == ThemeKind::HighContrast {
+			let outline = egui::Stroke::new(1.0, Color32::WHITE);
+			visuals.widgets.noninteractive.bg_fill = Color32::BLACK;
+			visuals.widgets.noninteractive.bg_stroke = outline;
+			visuals.widgets.inactive.bg_stroke = outline;
+			visuals.widgets.hovered.bg_stroke = egui::Stroke::new(2.0, Color32::YELLOW);
+			visuals.widgets.active.bg_stroke = egui::Stroke::new(2.0, Color32::YELLOW);
+			visuals.extreme_bg_color = Color32::BLACK;
+		}
+
+		ctx.set_visuals(visuals);
+	}
+}
+
+impl Default for UiTheme {
+	fn default() -> Self {
+		Self::preset(ThemeKind::Dark)
+	}
+}
@@ -0,0 +1,385 @@
+use dotrix::math::Vec3;
+use dotrix::ecs::Entity;
+use dotrix::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::effects::StatusEffect;
+use crate::history::ActionHistory;
+
+/// Name of the mesh asset projectiles are spawned with
+pub const PROJECTILE_MESH: &str = "projectile";
+/// Name of the ring mesh of radius 1.0 splash rings are spawned with
+pub const SPLASH_RING_MESH: &str = "splash_ring";
+pub const PROJECTILE_SPEED: f32 = 8.0;
+/// Distance from a projectile to an enemy that counts as a hit
+pub const PROJECTILE_HIT_RADIUS: f32 = 0.25;
+/// Share of the cost in percent the player gets back for selling a tower
+pub const SELL_REFUND_PERCENT: u32 = 50;
+/// Seconds the ring drawn at the impact of a splash projectile stays on screen
+pub const SPLASH_RING_SECONDS: f32 = 0.3;
+/// Level a tower can not be upgraded beyond, towers are built at level 1
+pub const MAX_LEVEL: u8 = 3;
+/// Cost in percent of the build cost of upgrading a level 1 tower, every further level costs as much again
+const UPGRADE_COST_PERCENT: u32 = 60;
+/// Increase of the range per level above the first
+const RANGE_PER_LEVEL: f32 = 0.15;
+/// Increase of the damage per level above the first
+const DAMAGE_PER_LEVEL: f32 = 0.5;
+/// Factor of the fire interval per level above the first
+const FIRE_INTERVAL_PER_LEVEL: f32 = 0.85;
+/// Increase of the mesh height per level above the first
+const HEIGHT_PER_LEVEL: f32 = 0.25;
+/// Share of white mixed into the tower color per level above the first
+const TINT_PER_LEVEL: f32 = 0.2;
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
+pub enum TowerKind {
+	Arrow,
+	Cannon,
+	/// Slows the enemies it hits
+	Frost,
+	/// Deals damage over time to the enemies it hits
+	Poison,
+}
+
+impl TowerKind {
+	pub const ALL: [TowerKind; 4] = [TowerKind::Arrow, TowerKind::Cannon, TowerKind::Frost, TowerKind::Poison];
+
+	/// Radius of the area the tower covers in world units
+	pub fn range(&self) -> f32 {
+		match self {
+			TowerKind::Arrow => 2.5,
+			TowerKind::Cannon => 2.0,
+			TowerKind::Frost => 3.0,
+			TowerKind::Poison => 2.5,
+		}
+	}
+
+	/// Gold the player pays to build the tower
+	pub fn cost(&self) -> u32 {
+		match self {
+			TowerKind::Arrow => 25,
+			TowerKind::Cannon => 50,
+			TowerKind::Frost => 40,
+			TowerKind::Poison => 35,
+		}
+	}
+
+	/// Gold the player gets for selling the tower
+	pub fn refund(&self) -> u32 {
+		self.cost() * SELL_REFUND_PERCENT / 100
+	}
+
+	/// Damage of a single projectile
+	pub fn damage(&self) -> f32 {
+		match self {
+			TowerKind::Arrow => 2.0,
+			TowerKind::Cannon => 6.0,
+			TowerKind::Frost => 1.0,
+			TowerKind::Poison => 0.5,
+		}
+	}
+
+	/// Effect the projectiles leave on the enemy they hit
+	pub fn effect(&self) -> Option<StatusEffect> {
+		match self {
+			TowerKind::Arrow | TowerKind::Cannon => None,
+			TowerKind::Frost => Some(StatusEffect::Slow {
+				factor: 0.5,
+				duration: 2.0,
+			}),
+			TowerKind::Poison => Some(StatusEffect::Poison {
+				damage_per_second: 2.0,
+				duration: 3.0,
+			}),
+		}
+	}
+
+	/// Damage dealt around the impact instead of to the hit enemy alone
+	pub fn splash(&self) -> Option<Splash> {
+		match self {
+			TowerKind::Cannon => Some(Splash {
+				radius: 1.0,
+				edge_factor: 0.25,
+			}),
+			TowerKind::Arrow | TowerKind::Frost | TowerKind::Poison => None,
+		}
+	}
+
+	/// Seconds between two shots
+	pub fn fire_interval(&self) -> f32 {
+		match self {
+			TowerKind::Arrow => 0.8,
+			TowerKind::Cannon => 2.0,
+			TowerKind::Frost => 0.5,
+			TowerKind::Poison => 1.2,
+		}
+	}
+
+	/// Footprint and height of the tower mesh
+	pub fn dimensions(&self) -> [f32; 3] {
+		match self {
+			TowerKind::Arrow => [0.5, 1.2, 0.5],
+			TowerKind::Cannon => [0.7, 0.8, 0.7],
+			TowerKind::Frost => [0.4, 1.5, 0.4],
+			TowerKind::Poison => [0.45, 1.0, 0.45],
+		}
+	}
+
+	/// Color the tower is rendered with
+	pub fn color(&self) -> Color {
+		match self {
+			TowerKind::Arrow => Color::rgb(0.6, 0.6, 0.7),
+			TowerKind::Cannon => Color::rgb(0.4, 0.3, 0.3),
+			TowerKind::Frost => Color::rgb(0.5, 0.8, 1.0),
+			TowerKind::Poison => Color::rgb(0.3, 0.7, 0.2),
+		}
+	}
+
+	/// Name of the mesh asset stored by `init_tower_meshes`
+	pub fn mesh_name(&self) -> &'static str {
+		match self {
+			TowerKind::Arrow => "tower_arrow",
+			TowerKind::Cannon => "tower_cannon",
+			TowerKind::Frost => "tower_frost",
+			TowerKind::Poison => "tower_poison",
+		}
+	}
+}
+
+/// Damage of a projectile dealt to every enemy within `radius` of the impact, falling off linearly
+/// from the full damage at the hit enemy to `edge_factor` of it at the radius
+#[derive(Debug, Clone, Copy)]
+pub struct Splash {
+	pub radius: f32,
+	pub edge_factor: f32,
+}
+
+impl Splash {
+	/// Share of the damage dealt at `distance` from the impact, 0.0 beyond the radius
+	pub fn factor(&self, distance: f32) -> f32 {
+		if distance > self.radius {
+			return 0.0;
+		}
+		1.0 - (1.0 - self.edge_factor) * distance / self.radius
+	}
+}
+
+/// Enemy in range a tower shoots at, see `tower_targeting`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetPriority {
+	/// Furthest along the path
+	First,
+	/// Least far along the path
+	Last,
+	Closest,
+	/// Highest health
+	Strongest,
+}
+
+impl TargetPriority {
+	pub const ALL: [TargetPriority; 4] = [TargetPriority::First, TargetPriority::Last, TargetPriority::Closest, TargetPriority::Strongest];
+}
+
+/// Tower kind `place_tower` builds, chosen in the build bar, and the build operations to undo
+pub struct BuildState {
+	pub selected_kind: TowerKind,
+	/// Build mode, a click places `selected_kind`. Entered from the build bar and left after one placement
+	/// unless SHIFT is held.
+	pub placing: bool,
+	/// Last tower was placed with SHIFT held, build mode ends once SHIFT is released
+	pub repeating: bool,
+	pub history: ActionHistory,
+	/// Mouse buttons sculpt the terrain instead of placing and selling towers, see `sculpt_terrain`
+	pub sculpt: bool,
+	/// Left mouse button paints the level with the `LevelBrush` instead of placing towers, see `edit_level`
+	pub level_editor: bool,
+}
+
+impl BuildState {
+	/// Sculpt mode or the level editor has the mouse buttons
+	pub fn editing(&self) -> bool {
+		self.sculpt || self.level_editor
+	}
+}
+
+impl Default for BuildState {
+	fn default() -> Self {
+		Self {
+			selected_kind: TowerKind::Arrow,
+			placing: false,
+			repeating: false,
+			history: ActionHistory::default(),
+			sculpt: false,
+			level_editor: false,
+		}
+	}
+}
+
+/// Combat stats of a tower at one level
+#[derive(Clone, Copy)]
+pub struct TowerStats {
+	pub range: f32,
+	pub damage: f32,
+	pub fire_interval: f32,
+}
+
+pub struct Tower {
+	pub kind: TowerKind,
+	pub cell: (i32, i32),
+	/// From 1 to `MAX_LEVEL`, raised by `upgrade`
+	pub level: u8,
+	/// Seconds until the tower can fire again
+	pub cooldown: f32,
+	/// Gameplay time of the last shot in `GameClock::elapsed` seconds, `None` until the tower fires
+	pub last_fired: Option<f32>,
+	/// Enemies finished off by projectiles of the tower
+	pub kills: u32,
+	pub priority: TargetPriority,
+}
+
+impl Tower {
+	pub fn new(kind: TowerKind, cell: (i32, i32)) -> Self {
+		Self {
+			kind,
+			cell,
+			level: 1,
+			cooldown: 0.0,
+			last_fired: None,
+			kills: 0,
+			priority: TargetPriority::Closest,
+		}
+	}
+
+	pub fn stats(&self) -> TowerStats {
+		stats_at(self.kind, self.level)
+	}
+
+	/// Stats after the next upgrade, `None` at `MAX_LEVEL`
+	pub fn upgraded_stats(&self) -> Option<TowerStats> {
+		self.upgrade_cost()
+			.map(|_| stats_at(self.kind, self.level + 1))
+	}
+
+	/// Gold the next upgrade costs, `None` at `MAX_LEVEL`
+	pub fn upgrade_cost(&self) -> Option<u32> {
+		if self.level >= MAX_LEVEL {
+			return None;
+		}
+		Some(upgrade_cost_at(self.kind, self.level))
+	}
+
+	/// Raises the level by one, the caller pays `upgrade_cost` and swaps the mesh and color
+	pub fn upgrade(&mut self) {
+		self.level = (self.level + 1).min(MAX_LEVEL);
+	}
+
+	/// Gold the player gets for selling the tower, upgrades are refunded at the same share as the build cost
+	pub fn refund(&self) -> u32 {
+		self.kind.refund() + self.upgrades_cost() * SELL_REFUND_PERCENT / 100
+	}
+
+	/// Share of the fire interval the tower has reloaded for, 1.0 once it can fire. An upgrade shortening the
+	/// interval may leave a longer cooldown, which counts from 0.0.
+	pub fn reload_fraction(&self) -> f32 {
+		let interval = self.stats().fire_interval;
+		if interval <= 0.0 {
+			return 1.0;
+		}
+		(1.0 - self.cooldown / interval).clamp(0.0, 1.0)
+	}
+
+	/// Gold paid for the tower and its upgrades
+	pub fn value(&self) -> u32 {
+		self.kind.cost() + self.upgrades_cost()
+	}
+
+	fn upgrades_cost(&self) -> u32 {
+		(1..self.level)
+			.map(|level| upgrade_cost_at(self.kind, level))
+			.sum()
+	}
+
+	/// Footprint and height of the tower mesh, taller with every level
+	pub fn dimensions(&self) -> [f32; 3] {
+		let [width, height, depth] = self.kind.dimensions();
+		[width, height * (1.0 + HEIGHT_PER_LEVEL * self.levels_above_first()), depth]
+	}
+
+	/// Color of the kind, lighter with every level
+	pub fn color(&self) -> Color {
+		let color = self.kind.color();
+		let tint = TINT_PER_LEVEL * self.levels_above_first();
+		Color::rgb(
+			color.r + (1.0 - color.r) * tint,
+			color.g + (1.0 - color.g) * tint,
+			color.b + (1.0 - color.b) * tint,
+		)
+	}
+
+	/// Name of the mesh asset stored by `init_tower_meshes` for the kind and level
+	pub fn mesh_name(&self) -> String {
+		format!("{}_{}", self.kind.mesh_name(), self.level)
+	}
+
+	/// Point projectiles are launched from, relative to the tower base
+	pub fn muzzle(&self) -> Vec3 {
+		Vec3::new(0.0, self.dimensions()[1], 0.0)
+	}
+
+	/// Minimal and maximal corners of the box of a tower standing at `base`
+	pub fn bounds(&self, base: Vec3) -> (Vec3, Vec3) {
+		let [width, height, depth] = self.dimensions();
+		(
+			Vec3::new(base.x - width / 2.0, base.y, base.z - depth / 2.0),
+			Vec3::new(base.x + width / 2.0, base.y + height, base.z + depth / 2.0),
+		)
+	}
+
+	fn levels_above_first(&self) -> f32 {
+		f32::from(self.level.saturating_sub(1))
+	}
+}
+
+fn stats_at(kind: TowerKind, level: u8) -> TowerStats {
+	let above_first = level.saturating_sub(1);
+	TowerStats {
+		range: kind.range() * (1.0 + RANGE_PER_LEVEL * f32::from(above_first)),
+		damage: kind.damage() * (1.0 + DAMAGE_PER_LEVEL * f32::from(above_first)),
+		fire_interval: kind.fire_interval() * FIRE_INTERVAL_PER_LEVEL.powi(i32::from(above_first)),
+	}
+}
+
+/// Gold the upgrade from `level` to the next one costs
+fn upgrade_cost_at(kind: TowerKind, level: u8) -> u32 {
+	kind.cost() * UPGRADE_COST_PERCENT / 100 * u32::from(level)
+}
+
+pub struct Projectile {
+	/// World units per second
+	pub velocity: Vec3,
+	pub damage: f32,
+	pub effect: Option<StatusEffect>,
+	/// Only the hit enemy is damaged without a splash
+	pub splash: Option<Splash>,
+	/// Seconds until the projectile is despawned if it hits nothing
+	pub lifetime: f32,
+	/// Tower the projectile was fired by, credited with the kill
+	pub source: Entity,
+	/// Inactive projectiles are hidden in the `ProjectilePool` until the next shot reuses them
+	pub active: bool,
+}
+
+/// Counts of the projectile entities spawned and reused. Projectiles are not despawned when they hit
+/// or expire, they are deactivated and the next shots take them over instead of spawning new entities.
+#[derive(Default)]
+pub struct ProjectilePool {
+	pub spawned: usize,
+	pub reused: usize,
+}
+
+/// Ring around the impact of a splash projectile, scaled to the splash radius
+pub struct SplashRing {
+	/// Seconds until the ring is despawned, it fades out meanwhile
+	pub remaining: f32,
+}
@@ -0,0 +1,412 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::enemies::{self, EnemyKind};
+
+/// File the wave definitions are read from at startup, see `load_waves`
+pub const WAVES_PATH: &str = "waves.json";
+/// Number of waves in `default_waves`
+const DEFAULT_WAVE_COUNT: u32 = 10;
+/// Seconds the player gets to build before a wave starts
+pub const BUILD_TIME: f32 = 15.0;
+/// Gold per second of the build time left when the player starts the next wave early
+pub const EARLY_START_BONUS: f32 = 1.0;
+
+/// Enemies of one kind in a wave
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct WaveGroup {
+	pub kind: EnemyKind,
+	pub count: u32,
+}
+
+/// One wave of `waves.json`, for example
+/// `{ "interval": 1.5, "groups": [{ "kind": "Swarm", "count": 4 }, { "kind": "Fast", "count": 1 }] }`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WaveDef {
+	/// Seconds between two spawns
+	pub interval: f32,
+	/// Groups take turns while spawning, so the kinds come mixed
+	pub groups: Vec<WaveGroup>,
+	/// Largest distance across the path of a spawned enemy from the spawn point
+	#[serde(default = "default_jitter")]
+	pub jitter: f32,
+	/// Distance the last spawned enemy has to walk from the spawn point before the next one spawns,
+	/// a spawn waits longer than `interval` for it
+	#[serde(default = "default_spacing")]
+	pub spacing: f32,
+}
+
+fn default_jitter() -> f32 {
+	enemies::SPAWN_JITTER
+}
+
+fn default_spacing() -> f32 {
+	enemies::SPAWN_SPACING
+}
+
+impl WaveDef {
+	pub fn enemy_count(&self) -> u32 {
+		self.groups
+			.iter()
+			.map(|group| group.count)
+			.sum()
+	}
+}
+
+/// Waves used when there is no waves file, every wave brings more of every kind
+pub fn default_waves() -> Vec<WaveDef> {
+	(1..=DEFAULT_WAVE_COUNT)
+		.map(|wave| WaveDef {
+			interval: 1.5,
+			groups: vec![
+				WaveGroup {
+					kind: EnemyKind::Swarm,
+					count: 4 + 3 * (wave - 1),
+				},
+				WaveGroup {
+					kind: EnemyKind::Fast,
+					count: wave,
+				},
+				WaveGroup {
+					kind: EnemyKind::Tank,
+					count: wave - 1,
+				},
+			],
+			jitter: enemies::SPAWN_JITTER,
+			spacing: enemies::SPAWN_SPACING,
+		})
+		.collect()
+}
+
+#[derive(Debug)]
+pub enum WavesError {
+	Io(std::io::Error),
+	/// Display of the JSON error includes the line and column
+	Format(serde_json::Error),
+	/// File parsed but describes waves that can not be played
+	Invalid(String),
+}
+
+impl WavesError {
+	/// Waves file does not exist, the default waves are used then
+	pub fn is_missing(&self) -> bool {
+		matches!(self, WavesError::Io(err) if err.kind() == std::io::ErrorKind::NotFound)
+	}
+}
+
+impl fmt::Display for WavesError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			WavesError::Io(err) => write!(f, "waves file I/O error: {}", err),
+			WavesError::Format(err) => write!(f, "malformed waves file: {}", err),
+			WavesError::Invalid(reason) => write!(f, "invalid waves file: {}", reason),
+		}
+	}
+}
+
+impl From<std::io::Error> for WavesError {
+	fn from(err: std::io::Error) -> Self {
+		WavesError::Io(err)
+	}
+}
+
+impl From<serde_json::Error> for WavesError {
+	fn from(err: serde_json::Error) -> Self {
+		WavesError::Format(err)
+	}
+}
+
+pub fn load_waves(path: &str) -> Result<Vec<WaveDef>, WavesError> {
+	let json = std::fs::read_to_string(path)?;
+	let waves: Vec<WaveDef> = serde_json::from_str(&json)?;
+	validate(&waves)?;
+	Ok(waves)
+}
+
+fn validate(waves: &[WaveDef]) -> Result<(), WavesError> {
+	if waves.is_empty() {
+		return Err(WavesError::Invalid(String::from("no waves defined")));
+	}
+
+	for (index, wave) in waves
+		.iter()
+		.enumerate()
+	{
+		if !(wave.interval.is_finite() && wave.interval > 0.0) {
+			return Err(WavesError::Invalid(format!(
+				"wave {} has the spawn interval {}, it must be above 0",
+				index + 1,
+				wave.interval
+			)));
+		}
+		if !(wave.jitter.is_finite() && wave.jitter >= 0.0) {
+			return Err(WavesError::Invalid(format!(
+				"wave {} has the spawn jitter {}, it must not be negative",
+				index + 1,
+				wave.jitter
+			)));
+		}
+		if !(wave.spacing.is_finite() && wave.spacing >= 0.0) {
+			return Err(WavesError::Invalid(format!(
+				"wave {} has the spawn spacing {}, it must not be negative",
+				index + 1,
+				wave.spacing
+			)));
+		}
+		if wave.enemy_count() == 0 {
+			return Err(WavesError::Invalid(format!("wave {} has no enemies", index + 1)));
+		}
+	}
+
+	Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WavePhase {
+	/// Counting down to the next wave, see `WaveSpawner::countdown`
+	Building,
+	/// Enemies of the current wave are spawning or still alive
+	Spawning,
+	/// Last wave is defeated
+	Cleared,
+}
+
+/// Spawns the waves one by one with a build phase before each, see `spawn_waves`
+pub struct WaveSpawner {
+	/// Number of the current wave, starting from 1, it is the upcoming one while building
+	pub wave: u32,
+	pub phase: WavePhase,
+	/// Seconds left until the next wave starts in `WavePhase::Building`
+	pub countdown: f32,
+	/// Seconds between two spawns of the current wave
+	pub interval: f32,
+	/// Seconds since the last spawn
+	pub elapsed: f32,
+	/// Largest distance across the path of an enemy of the current wave from the spawn point
+	pub jitter: f32,
+	/// Distance the last enemy has to get from the spawn point before the next one of the current wave spawns
+	pub spacing: f32,
+	/// Wave definitions, never empty
+	waves: Vec<WaveDef>,
+	/// Enemies of the current wave that are not spawned yet, in reverse spawn order
+	queue: Vec<EnemyKind>,
+}
+
+impl WaveSpawner {
+	/// Spawner building for the first wave, the default waves are used if `waves` is empty
+	pub fn new(waves: Vec<WaveDef>) -> Self {
+		let waves = if waves.is_empty() {
+			default_waves()
+		} else {
+			waves
+		};
+		Self {
+			wave: 1,
+			phase: WavePhase::Building,
+			countdown: BUILD_TIME,
+			interval: 0.0,
+			elapsed: 0.0,
+			jitter: enemies::SPAWN_JITTER,
+			spacing: enemies::SPAWN_SPACING,
+			waves,
+			queue: Vec::new(),
+		}
+	}
+
+	/// Spawner with the same wave definitions starting over from the first wave
+	pub fn restarted(&self) -> Self {
+		Self::new(self.waves.clone())
+	}
+
+	pub fn wave_count(&self) -> u32 {
+		self.waves.len() as u32
+	}
+
+	/// Enemies of the current wave that are not spawned yet
+	pub fn remaining(&self) -> u32 {
+		self.queue.len() as u32
+	}
+
+	/// Kind of the next enemy to spawn, `None` once the wave is spawned
+	pub fn pop(&mut self) -> Option<EnemyKind> {
+		self.queue.pop()
+	}
+
+	/// Gold for starting the next wave now, nothing unless building
+	pub fn early_start_bonus(&self) -> u32 {
+		if self.phase != WavePhase::Building {
+			return 0;
+		}
+		(self.countdown.max(0.0) * EARLY_START_BONUS).floor() as u32
+	}
+
+	/// Queues the enemies of the current wave and starts spawning them, the groups take turns
+	/// so the kinds come mixed
+	pub fn start_wave(&mut self) {
+		let wave = &self.waves[(self.wave as usize - 1).min(self.waves.len() - 1)];
+		self.phase = WavePhase::Spawning;
+		self.countdown = 0.0;
+		self.elapsed = 0.0;
+		self.interval = wave.interval;
+		self.jitter = wave.jitter;
+		self.spacing = wave.spacing;
+
+		let mut counts = wave
+			.groups
+			.iter()
+			.map(|group| (group.kind, group.count))
+			.collect::<Vec<_>>();
+		let mut order = Vec::new();
+		while counts
+			.iter()
+			.any(|(_, count)| *count > 0)
+		{
+			for (kind, count) in counts.iter_mut() {
+				if *count > 0 {
+					*count -= 1;
+					order.push(*kind);
+				}
+			}
+		}
+
+		order.reverse();
+		self.queue = order;
+	}
+
+	/// Current wave is defeated, builds for the next one or clears the game after the last wave.
+	/// Returns `true` if the game is cleared.
+	pub fn finish_wave(&mut self) -> bool {
+		if self.wave >= self.wave_count() {
+			self.phase = WavePhase::Cleared;
+			return true;
+		}
+		self.wave += 1;
+		self.phase = WavePhase::Building;
+		self.countdown = BUILD_TIME;
+		false
+	}
+}
+
+impl Default for WaveSpawner {
+	fn default() -> Self {
+		Self::new(default_waves())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn wave(groups: &[(EnemyKind, u32)]) -> WaveDef {
+		WaveDef {
+			interval: 1.0,
+			groups: groups
+				.iter()
+				.map(|(kind, count)| WaveGroup {
+					kind: *kind,
+					count: *count,
+				})
+				.collect(),
+			jitter: enemies::SPAWN_JITTER,
+			spacing: enemies::SPAWN_SPACING,
+		}
+	}
+
+	#[test]
+	fn default_waves_are_valid() {
+		assert!(validate(&default_waves()).is_ok());
+	}
+
+	#[test]
+	fn invalid_waves() {
+		assert!(matches!(validate(&[]), Err(WavesError::Invalid(_))));
+		assert!(matches!(validate(&[wave(&[])]), Err(WavesError::Invalid(_))));
+		assert!(matches!(validate(&[wave(&[(EnemyKind::Fast, 0)])]), Err(WavesError::Invalid(_))));
+
+		let invalid = [
+			WaveDef {
+				interval: 0.0,
+				..wave(&[(EnemyKind::Fast, 1)])
+			},
+			WaveDef {
+				interval: f32::NAN,
+				..wave(&[(EnemyKind::Fast, 1)])
+			},
+			WaveDef {
+				jitter: -0.1,
+				..wave(&[(EnemyKind::Fast, 1)])
+			},
+			WaveDef {
+				spacing: -0.1,
+				..wave(&[(EnemyKind::Fast, 1)])
+			},
+		];
+		for definition in invalid {
+			// The error names the wave, here the second one
+			match validate(&[wave(&[(EnemyKind::Tank, 1)]), definition]) {
+				Err(WavesError::Invalid(reason)) => assert!(reason.starts_with("wave 2 "), "{}", reason),
+				_ => panic!("invalid wave accepted"),
+			}
+		}
+	}
+
+	#[test]
+	fn spacing_and_jitter_default() {
+		let definition: WaveDef = serde_json::from_str(r#"{ "interval": 1.5, "groups": [{ "kind": "Swarm", "count": 4 }] }"#).unwrap();
+		assert_eq!(definition.jitter, enemies::SPAWN_JITTER);
+		assert_eq!(definition.spacing, enemies::SPAWN_SPACING);
+		assert_eq!(definition.enemy_count(), 4);
+	}
+
+	#[test]
+	fn groups_take_turns() {
+		let mut spawner = WaveSpawner::new(vec![wave(&[(EnemyKind::Swarm, 3), (EnemyKind::Fast, 1), (EnemyKind::Tank, 2)])]);
+		spawner.start_wave();
+		assert_eq!(spawner.phase, WavePhase::Spawning);
+		assert_eq!(spawner.remaining(), 6);
+
+		let order = std::iter::from_fn(|| spawner.pop()).collect::<Vec<_>>();
+		assert_eq!(
+			order,
+			[EnemyKind::Swarm, EnemyKind::Fast, EnemyKind::Tank, EnemyKind::Swarm, EnemyKind::Tank, EnemyKind::Swarm]
+		);
+		assert_eq!(spawner.remaining(), 0);
+	}
+
+	#[test]
+	fn waves_follow_each_other() {
+		let mut spawner = WaveSpawner::new(vec![wave(&[(EnemyKind::Fast, 1)]), wave(&[(EnemyKind::Tank, 1)])]);
+		assert_eq!(spawner.early_start_bonus(), (BUILD_TIME * EARLY_START_BONUS).floor() as u32);
+
+		spawner.start_wave();
+		assert_eq!(spawner.early_start_bonus(), 0);
+		assert!(!spawner.finish_wave());
+		assert_eq!((spawner.wave, spawner.phase), (2, WavePhase::Building));
+
+		spawner.start_wave();
+		assert_eq!(spawner.pop(), Some(EnemyKind::Tank));
+		assert!(spawner.finish_wave());
+		assert_eq!(spawner.phase, WavePhase::Cleared);
+	}
+
+	#[test]
+	fn early_start_bonus_pays_the_countdown_left() {
+		let mut spawner = WaveSpawner::new(vec![wave(&[(EnemyKind::Fast, 1)])]);
+		spawner.countdown = 7.6;
+		assert_eq!(spawner.early_start_bonus(), (7.6 * EARLY_START_BONUS).floor() as u32);
+		spawner.countdown = -1.0;
+		assert_eq!(spawner.early_start_bonus(), 0);
+
+		// Nothing is paid once the last wave is cleared
+		spawner.countdown = BUILD_TIME;
+		spawner.start_wave();
+		assert!(spawner.finish_wave());
+		assert_eq!(spawner.early_start_bonus(), 0);
+	}
+
+	#[test]
+	fn empty_definitions_use_the_default_waves() {
+		assert_eq!(WaveSpawner::new(Vec::new()).wave_count(), DEFAULT_WAVE_COUNT);
+	}
+}